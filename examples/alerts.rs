@@ -0,0 +1,54 @@
+//! Operator alerts example
+use onionoo::{
+    Client, QueryParameters,
+    alerts::{AlertConfig, AlertEngine, AlertSink, StdoutSink},
+    endpoints,
+};
+
+const RULES: &str = r#"{
+    "rules": [
+        {
+            "name": "relay-health",
+            "severity": "critical",
+            "select": { "contact": "torproject" },
+            "conditions": [
+                { "type": "not_running" },
+                { "type": "missing_flag", "flag": "Stable" },
+                { "type": "overloaded" }
+            ]
+        },
+        {
+            "name": "relay-maintenance",
+            "severity": "warning",
+            "select": { "contact": "torproject" },
+            "conditions": [
+                { "type": "version_status" },
+                { "type": "rate_limited" },
+                { "type": "fd_exhausted" }
+            ]
+        }
+    ]
+}"#;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = Client::new();
+    // `missing_flag` compares with the previous evaluation, so a long-running monitor
+    // keeps one engine and evaluates it on every poll
+    let mut engine = AlertEngine::new(AlertConfig::from_json(RULES)?);
+
+    let details = endpoints::details(&client, QueryParameters::new().contact("torproject")).await?;
+    let bandwidth =
+        endpoints::bandwidth(&client, QueryParameters::new().contact("torproject")).await?;
+
+    let alerts = engine.evaluate(&details, Some(&bandwidth));
+    println!(
+        "Evaluated {} relays, {} alerts raised",
+        details.relays.len(),
+        alerts.len()
+    );
+
+    StdoutSink.deliver(&alerts).await?;
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
 //! Error handling example
+use std::time::Duration;
+
 use onionoo::{
-    Client, QueryParameters, endpoints,
+    Client, QueryParameters,
+    client::ClientError,
+    endpoints,
+    models::SummaryResponse,
     selection::{BoolValue, TypeValue},
 };
 
@@ -20,9 +25,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 );
             }
         }
-        Err(e) => {
-            println!("Error fetching relays: {}", e);
-        }
+        Err(e) => report(&e),
     }
 
     // Test empty results
@@ -37,33 +40,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 );
             }
         }
-        Err(e) => {
-            println!("Error with limit=0: {}", e);
-        }
+        Err(e) => report(&e),
     }
 
-    // Test retry logic
-    let mut attempts = 0;
-    let max_attempts = 3;
+    // Test an unknown endpoint path
+    let result: Result<SummaryResponse, _> = client
+        .get("/no-such-endpoint", QueryParameters::new())
+        .await;
+    match result {
+        Err(e) if e.is_not_found() => println!("Unknown endpoint: {}", e),
+        Err(e) => report(&e),
+        Ok(_) => println!("Unexpected success"),
+    }
 
-    while attempts < max_attempts {
-        attempts += 1;
-        println!("Attempt {} of {}...", attempts, max_attempts);
+    // Test a response that does not match the expected document type
+    let result: Result<SummaryResponse, _> =
+        client.get("/uptime", QueryParameters::new().limit(1)).await;
+    if let Err(e) = result {
+        report(&e);
+    }
 
-        match endpoints::summary(&client, QueryParameters::new().limit(1)).await {
-            Ok(_) => {
-                println!("Retry successful on attempt {}", attempts);
-                break;
-            }
-            Err(e) if attempts < max_attempts => {
-                println!("Attempt {} failed: {}, retrying...", attempts, e);
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            }
-            Err(e) => {
-                println!("All retry attempts failed: {}", e);
-                break;
-            }
-        }
+    // Test retry logic: only transient failures are retried
+    match with_retries(&client, 3).await {
+        Ok(response) => println!("Fetched {} relays", response.relays.len()),
+        Err(e) => println!("Giving up: {}", e),
     }
 
     Ok(())
@@ -74,7 +74,7 @@ async fn fetch_relays_safely(
     type_value: TypeValue,
     running: BoolValue,
     limit: u32,
-) -> Result<Vec<onionoo::models::RelaySummary>, Box<dyn std::error::Error>> {
+) -> Result<Vec<onionoo::models::RelaySummary>, ClientError> {
     let params = QueryParameters::new()
         .type_param(type_value)
         .running(running)
@@ -83,3 +83,57 @@ async fn fetch_relays_safely(
     let response = endpoints::summary(client, params).await?;
     Ok(response.relays)
 }
+
+async fn with_retries(client: &Client, max_attempts: u32) -> Result<SummaryResponse, ClientError> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        println!("Attempt {} of {}...", attempts, max_attempts);
+
+        match endpoints::summary(client, QueryParameters::new().limit(1)).await {
+            Err(e) if e.is_retryable() && attempts < max_attempts => {
+                let delay = e
+                    .retry_after()
+                    .unwrap_or(Duration::from_millis(100 * 2u64.pow(attempts)));
+                println!(
+                    "Attempt {} failed: {}, retrying in {:?}",
+                    attempts, e, delay
+                );
+                client.report_retry(&e);
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn report(error: &ClientError) {
+    match error {
+        ClientError::StatusCode { status, body, .. } => {
+            println!("Server answered {}: {}", status, body);
+        }
+        ClientError::Deserialization {
+            path,
+            line,
+            column,
+            snippet,
+            ..
+        } => {
+            println!(
+                "Unexpected document at {} (line {}, column {})",
+                path, line, column
+            );
+            println!("  near: {}", snippet);
+        }
+        ClientError::InvalidParameters(report) => {
+            for issue in report.issues() {
+                println!("Invalid parameter: {}", issue);
+            }
+        }
+        e if e.is_timeout() => println!("Timed out: {}", e),
+        e => println!("Error: {}", e),
+    }
+    if let Some(context) = error.context() {
+        println!("  while requesting {} ({})", context.endpoint, context.url);
+    }
+}
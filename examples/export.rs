@@ -0,0 +1,51 @@
+//! CSV and NDJSON export example
+//!
+//! Writes the exit relays of a country to `exits.csv` and their bandwidth histories to
+//! `bandwidth.ndjson`.
+use std::fs::File;
+use std::io::BufWriter;
+
+use onionoo::{
+    Client, QueryParameters, endpoints,
+    endpoints::PartialDetails,
+    models::PartialRelayDetails,
+    parameters::DetailsField,
+    tabular::{ListEncoding, Series, TableWriter},
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let country = std::env::args().nth(1).unwrap_or_else(|| "de".to_string());
+    let client = Client::new();
+
+    let mut relays =
+        TableWriter::<_, PartialRelayDetails>::csv(BufWriter::new(File::create("exits.csv")?))
+            .select([
+                DetailsField::Fingerprint,
+                DetailsField::Nickname,
+                DetailsField::OrAddresses,
+                DetailsField::Flags,
+                DetailsField::ConsensusWeight,
+            ])
+            .list_encoding(ListEncoding::Delimited(' '));
+    let params = QueryParameters::new()
+        .country(country.as_str())
+        .flag("Exit")
+        .fields(relays.fields_list());
+    let details = client.fetch::<PartialDetails>(params).await?;
+    relays.write_all(&details.relays)?;
+    relays.finish()?;
+    println!("Wrote {} relays to exits.csv", details.relays.len());
+
+    let params = QueryParameters::new()
+        .country(country.as_str())
+        .flag("Exit");
+    let bandwidth = endpoints::bandwidth(&client, params).await?;
+    let points = bandwidth.series();
+    let mut series = TableWriter::ndjson(BufWriter::new(File::create("bandwidth.ndjson")?));
+    series.write_all(&points)?;
+    series.finish()?;
+    println!("Wrote {} data points to bandwidth.ndjson", points.len());
+
+    Ok(())
+}
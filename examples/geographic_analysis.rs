@@ -1,7 +1,7 @@
 //! Geographic analysis example
 use onionoo::{
     Client, QueryParameters, endpoints,
-    selection::{BoolValue, TypeValue},
+    selection::{AsNumber, BoolValue, CountryCode, TypeValue},
 };
 
 #[tokio::main]
@@ -35,7 +35,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         if let Some(as_number) = &relay.as_number {
-            *as_stats.entry(as_number.clone()).or_insert(0) += 1;
+            *as_stats.entry(*as_number).or_insert(0) += 1;
         }
 
         if let (Some(lat), Some(lon)) = (relay.latitude, relay.longitude) {
@@ -50,7 +50,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     println!("\nTop Countries by Relay Count:");
-    let mut country_vec: Vec<(&String, &u32)> = country_stats.iter().collect();
+    let mut country_vec: Vec<(&CountryCode, &u32)> = country_stats.iter().collect();
     country_vec.sort_by(|a, b| b.1.cmp(a.1));
 
     for (country, count) in country_vec.iter().take(10) {
@@ -59,12 +59,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     println!("\nTop Autonomous Systems:");
-    let mut as_vec: Vec<(&String, &u32)> = as_stats.iter().collect();
+    let mut as_vec: Vec<(&AsNumber, &u32)> = as_stats.iter().collect();
     as_vec.sort_by(|a, b| b.1.cmp(a.1));
 
     for (as_number, count) in as_vec.iter().take(5) {
         let percentage = (**count as f64 / response.relays.len() as f64) * 100.0;
-        println!("  AS{}: {} relays ({:.1}%)", as_number, count, percentage);
+        println!("  {}: {} relays ({:.1}%)", as_number, count, percentage);
     }
 
     println!("\nSample Coordinates:");
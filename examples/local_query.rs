@@ -0,0 +1,50 @@
+//! Local query example: many views of a single details document
+use onionoo::{
+    Client, QueryParameters, endpoints,
+    parameters::{OrderKey, OrderList, helpers::version_list},
+    query::LocalQuery,
+    selection::{BoolValue, TypeValue},
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = Client::new();
+
+    let params = QueryParameters::new()
+        .type_param(TypeValue::Relay)
+        .running(BoolValue::True);
+    let details = endpoints::details(&client, params).await?;
+    println!("Fetched {} running relays", details.relays.len());
+
+    let views = [
+        (
+            "Running exits in DE",
+            QueryParameters::new().country("de").flag("Exit"),
+        ),
+        (
+            "Relays in AS24940 on 0.4.8",
+            QueryParameters::new()
+                .as_param("AS24940")
+                .version(version_list("0.4.8")),
+        ),
+        (
+            "Top 5 relays by consensus weight",
+            QueryParameters::new()
+                .order(OrderList::new().desc(OrderKey::ConsensusWeight))
+                .limit(5),
+        ),
+    ];
+
+    for (title, params) in views {
+        let view = details.query(&params)?;
+        println!("\n{}: {}", title, view.relays.len());
+        for relay in view.relays.iter().take(5) {
+            println!(
+                "  {} ({}) weight {}",
+                relay.nickname, relay.fingerprint, relay.consensus_weight
+            );
+        }
+    }
+
+    Ok(())
+}
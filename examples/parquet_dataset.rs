@@ -0,0 +1,25 @@
+//! Parquet dataset example
+//!
+//! Appends today's details snapshot and bandwidth histories to a partitioned dataset.
+//! Run it daily, e.g. `cargo run --example parquet_dataset --features arrow -- onionoo-data`.
+use onionoo::{Client, QueryParameters, arrow::Dataset, endpoints};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "onionoo-data".to_string());
+    let client = Client::new();
+    let dataset = Dataset::new(root);
+
+    let details = endpoints::details(&client, QueryParameters::new()).await?;
+    for path in dataset.append_details(&details)? {
+        println!("Wrote {}", path.display());
+    }
+
+    let bandwidth = endpoints::bandwidth(&client, QueryParameters::new()).await?;
+    let path = dataset.append_history("bandwidth", &bandwidth)?;
+    println!("Wrote {}", path.display());
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Prometheus exporter example
+//!
+//! Serves the metrics of the relays matching a contact on http://127.0.0.1:9100/metrics.
+use std::time::Duration;
+
+use onionoo::{
+    Client, QueryParameters,
+    exporter::{Exporter, MetricsServer, RelayDocuments},
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let contact = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "torproject".to_string());
+    let server = MetricsServer::bind("127.0.0.1:9100").await?;
+    println!("Serving metrics on {}", server.url());
+
+    let client = Client::new();
+    let exporter = Exporter::new();
+    loop {
+        let params = QueryParameters::new().contact(contact.as_str());
+        match RelayDocuments::fetch(&client, params).await {
+            Ok(documents) => {
+                println!("Updated {} relays", documents.details.relays.len());
+                server.update(exporter.render(&documents));
+            }
+            Err(e) => eprintln!("Refresh failed, serving previous metrics: {}", e),
+        }
+        // Onionoo documents are updated once an hour
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+    }
+}
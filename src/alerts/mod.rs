@@ -0,0 +1,640 @@
+//! Operator Alert Rules
+//!
+//! This module provides a declarative rules engine for relay operators. Rules are
+//! loaded from JSON (or TOML with the `toml` feature), select relays by fingerprint,
+//! family or contact, and are evaluated against details and bandwidth documents to
+//! produce alert records that can be handed to an [`AlertSink`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+
+use crate::models::{BandwidthResponse, DetailsResponse, RelayBandwidth, RelayDetails};
+
+/// Custom error type for alert configuration and delivery
+#[derive(Debug)]
+pub enum AlertError {
+    /// The rules configuration could not be parsed
+    Config(String),
+    /// HTTP request to a webhook failed
+    Request(reqwest::Error),
+    /// Webhook returned an error status code
+    StatusCode(u16, String),
+}
+
+impl fmt::Display for AlertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertError::Config(msg) => write!(f, "Invalid alert configuration: {}", msg),
+            AlertError::Request(e) => write!(f, "Alert delivery failed: {}", e),
+            AlertError::StatusCode(code, msg) => {
+                write!(f, "Alert webhook returned HTTP {}: {}", code, msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AlertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AlertError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for AlertError {
+    fn from(error: reqwest::Error) -> Self {
+        AlertError::Request(error)
+    }
+}
+
+/// Severity of an alert
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Relay selection for a rule
+///
+/// A relay is selected if it matches any of the given criteria. An empty selector
+/// selects every relay in the document.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Selector {
+    /// Relay fingerprints (40 hex characters, optionally `$`-prefixed)
+    #[serde(default)]
+    pub fingerprints: Vec<String>,
+    /// Fingerprint of a relay whose effective family should be selected
+    pub family: Option<String>,
+    /// Case-insensitive substring of the operator contact line
+    pub contact: Option<String>,
+}
+
+impl Selector {
+    fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty() && self.family.is_none() && self.contact.is_none()
+    }
+
+    /// Returns whether the given relay is selected
+    pub fn matches(&self, relay: &RelayDetails) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        if self
+            .fingerprints
+            .iter()
+            .any(|fp| same_fingerprint(fp, &relay.fingerprint))
+        {
+            return true;
+        }
+
+        if let Some(ref family) = self.family {
+            if same_fingerprint(family, &relay.fingerprint) {
+                return true;
+            }
+            if let Some(ref members) = relay.effective_family {
+                if members.iter().any(|fp| same_fingerprint(fp, family)) {
+                    return true;
+                }
+            }
+        }
+
+        if let (Some(needle), Some(contact)) = (&self.contact, &relay.contact) {
+            if contact.to_lowercase().contains(&needle.to_lowercase()) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+fn same_fingerprint(a: &str, b: &str) -> bool {
+    a.trim_start_matches('$')
+        .eq_ignore_ascii_case(b.trim_start_matches('$'))
+}
+
+fn has_flag(flags: &[String], flag: &str) -> bool {
+    flags.iter().any(|f| f.eq_ignore_ascii_case(flag))
+}
+
+fn default_version_statuses() -> Vec<String> {
+    vec!["obsolete".to_string(), "unrecommended".to_string()]
+}
+
+/// Condition that triggers an alert when it holds for a selected relay
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// Relay is not listed as running in the last consensus
+    NotRunning,
+    /// Relay had the given flag (e.g. "Stable", "Guard") in the previous evaluation
+    /// and no longer has it
+    MissingFlag { flag: String },
+    /// Relay reports an `overload_general_timestamp`
+    Overloaded,
+    /// Relay's `version_status` is one of the given statuses
+    VersionStatus {
+        #[serde(default = "default_version_statuses")]
+        statuses: Vec<String>,
+    },
+    /// Bandwidth document reports `overload_ratelimits`
+    RateLimited,
+    /// Bandwidth document reports `overload_fd_exhausted`
+    FdExhausted,
+}
+
+impl Condition {
+    /// Short identifier of the condition, used in deduplication keys
+    pub fn kind(&self) -> String {
+        match self {
+            Condition::NotRunning => "not_running".to_string(),
+            Condition::MissingFlag { flag } => format!("missing_flag:{}", flag.to_lowercase()),
+            Condition::Overloaded => "overloaded".to_string(),
+            Condition::VersionStatus { .. } => "version_status".to_string(),
+            Condition::RateLimited => "rate_limited".to_string(),
+            Condition::FdExhausted => "fd_exhausted".to_string(),
+        }
+    }
+
+    /// Evaluates the condition, returning a message if it holds
+    ///
+    /// `previous_flags` are the relay's flags in the previous evaluation, if known.
+    /// `MissingFlag` only holds if the flag was among them and is no longer among the
+    /// relay's current flags; relays whose flags were omitted never match.
+    pub fn evaluate(
+        &self,
+        relay: &RelayDetails,
+        previous_flags: Option<&[String]>,
+        bandwidth: Option<&RelayBandwidth>,
+    ) -> Option<String> {
+        match self {
+            Condition::NotRunning => (!relay.running).then(|| "relay is not running".to_string()),
+            Condition::MissingFlag { flag } => {
+                let had_flag = previous_flags.is_some_and(|flags| has_flag(flags, flag));
+                let lost_flag = relay
+                    .flags
+                    .as_ref()
+                    .is_some_and(|flags| !has_flag(flags, flag));
+                (had_flag && lost_flag).then(|| format!("relay lost the {} flag", flag))
+            }
+            Condition::Overloaded => relay
+                .overload_general_timestamp
+                .map(|ts| format!("relay reported general overload at {}", ts)),
+            Condition::VersionStatus { statuses } => relay
+                .version_status
+                .as_ref()
+                .filter(|status| statuses.iter().any(|s| s.eq_ignore_ascii_case(status)))
+                .map(|status| format!("relay version status is {}", status)),
+            Condition::RateLimited => bandwidth
+                .and_then(|bw| bw.overload_ratelimits.as_ref())
+                .map(|overload| match overload.timestamp {
+                    Some(ts) => format!("relay hit its rate limits at {}", ts),
+                    None => "relay hit its rate limits".to_string(),
+                }),
+            Condition::FdExhausted => bandwidth
+                .and_then(|bw| bw.overload_fd_exhausted.as_ref())
+                .map(|overload| match overload.timestamp {
+                    Some(ts) => format!("relay ran out of file descriptors at {}", ts),
+                    None => "relay ran out of file descriptors".to_string(),
+                }),
+        }
+    }
+}
+
+/// A single alert rule
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rule {
+    /// Unique rule name
+    pub name: String,
+    /// Severity of alerts raised by this rule
+    #[serde(default)]
+    pub severity: Severity,
+    /// Relays the rule applies to
+    #[serde(default)]
+    pub select: Selector,
+    /// Conditions checked for every selected relay
+    pub conditions: Vec<Condition>,
+}
+
+/// Alert rules configuration
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl AlertConfig {
+    /// Parse a rules configuration from JSON
+    pub fn from_json(json: &str) -> Result<Self, AlertError> {
+        serde_json::from_str(json).map_err(|e| AlertError::Config(e.to_string()))
+    }
+
+    /// Parse a rules configuration from TOML
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, AlertError> {
+        toml::from_str(toml).map_err(|e| AlertError::Config(e.to_string()))
+    }
+}
+
+/// Alert record produced by the rules engine
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Alert {
+    /// Name of the rule that raised the alert
+    pub rule: String,
+    /// Severity of the rule
+    pub severity: Severity,
+    /// Fingerprint of the affected relay
+    pub fingerprint: String,
+    /// Nickname of the affected relay
+    pub nickname: String,
+    /// Identifier of the condition that holds
+    pub condition: String,
+    /// Human-readable description
+    pub message: String,
+    /// Key identifying this alert across evaluations
+    pub dedup_key: String,
+}
+
+/// Rules engine evaluating a configuration against Onionoo documents
+///
+/// The engine remembers the flags of every relay it has evaluated, so that
+/// [`Condition::MissingFlag`] can detect a lost flag. Keep one engine across polls.
+#[derive(Debug, Clone)]
+pub struct AlertEngine {
+    config: AlertConfig,
+    previous_flags: HashMap<String, Vec<String>>,
+}
+
+impl AlertEngine {
+    pub fn new(config: AlertConfig) -> Self {
+        Self {
+            config,
+            previous_flags: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &AlertConfig {
+        &self.config
+    }
+
+    /// Evaluate all rules against a details document and an optional bandwidth document
+    ///
+    /// Afterwards, the flags of the document's relays replace the remembered flags.
+    /// Relays whose flags were omitted keep their previously remembered flags.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, QueryParameters, endpoints, alerts::{AlertConfig, AlertEngine}};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = AlertConfig::from_json(r#"{
+    ///     "rules": [{
+    ///         "name": "my-relays-down",
+    ///         "severity": "critical",
+    ///         "select": { "contact": "ops@example.com" },
+    ///         "conditions": [{ "type": "not_running" }]
+    ///     }]
+    /// }"#)?;
+    ///
+    /// let client = Client::new();
+    /// let mut engine = AlertEngine::new(config);
+    /// let details = endpoints::details(&client, QueryParameters::new().contact("ops@example.com")).await?;
+    /// for alert in engine.evaluate(&details, None) {
+    ///     println!("[{}] {}: {}", alert.severity.as_str(), alert.nickname, alert.message);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn evaluate(
+        &mut self,
+        details: &DetailsResponse,
+        bandwidth: Option<&BandwidthResponse>,
+    ) -> Vec<Alert> {
+        let bandwidth_by_fingerprint: HashMap<String, &RelayBandwidth> = bandwidth
+            .map(|doc| {
+                doc.relays
+                    .iter()
+                    .map(|bw| (bw.fingerprint.to_uppercase(), bw))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut alerts = Vec::new();
+        for rule in &self.config.rules {
+            for relay in details.relays.iter().filter(|r| rule.select.matches(r)) {
+                let fingerprint = relay.fingerprint.to_uppercase();
+                let bw = bandwidth_by_fingerprint.get(&fingerprint).copied();
+                let previous_flags = self.previous_flags.get(&fingerprint).map(Vec::as_slice);
+                for condition in &rule.conditions {
+                    if let Some(message) = condition.evaluate(relay, previous_flags, bw) {
+                        let kind = condition.kind();
+                        alerts.push(Alert {
+                            rule: rule.name.clone(),
+                            severity: rule.severity,
+                            fingerprint: relay.fingerprint.clone(),
+                            nickname: relay.nickname.clone(),
+                            dedup_key: format!("{}:{}:{}", rule.name, relay.fingerprint, kind),
+                            condition: kind,
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+
+        for relay in &details.relays {
+            if let Some(ref flags) = relay.flags {
+                self.previous_flags
+                    .insert(relay.fingerprint.to_uppercase(), flags.clone());
+            }
+        }
+        alerts
+    }
+}
+
+/// Suppresses alerts that were already reported in a previous evaluation
+///
+/// An alert is reported again once its condition has cleared and fires anew.
+#[derive(Debug, Clone, Default)]
+pub struct Deduplicator {
+    active: HashSet<String>,
+}
+
+impl Deduplicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the alerts that were not active during the previous call
+    pub fn filter_new(&mut self, alerts: Vec<Alert>) -> Vec<Alert> {
+        let current: HashSet<String> = alerts.iter().map(|a| a.dedup_key.clone()).collect();
+        let fresh = alerts
+            .into_iter()
+            .filter(|a| !self.active.contains(&a.dedup_key))
+            .collect();
+        self.active = current;
+        fresh
+    }
+}
+
+/// Delivery target for alerts
+pub trait AlertSink {
+    /// Deliver a batch of alerts
+    fn deliver(&self, alerts: &[Alert]) -> impl Future<Output = Result<(), AlertError>> + Send;
+}
+
+/// Prints alerts to standard output, one per line
+#[derive(Debug, Clone, Default)]
+pub struct StdoutSink;
+
+impl AlertSink for StdoutSink {
+    async fn deliver(&self, alerts: &[Alert]) -> Result<(), AlertError> {
+        for alert in alerts {
+            println!(
+                "[{}] {} {} ({}): {}",
+                alert.severity.as_str(),
+                alert.rule,
+                alert.nickname,
+                alert.fingerprint,
+                alert.message
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Posts alerts as a JSON array to a webhook URL
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    http_client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl AlertSink for WebhookSink {
+    async fn deliver(&self, alerts: &[Alert]) -> Result<(), AlertError> {
+        if alerts.is_empty() {
+            return Ok(());
+        }
+
+        let response = self.http_client.post(&self.url).json(alerts).send().await?;
+        if !response.status().is_success() {
+            let status_code = response.status().as_u16();
+            let error_message = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(AlertError::StatusCode(status_code, error_message));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FP_A: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+    const FP_B: &str = "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB";
+
+    fn details() -> DetailsResponse {
+        serde_json::from_value(serde_json::json!({
+            "version": "8.0",
+            "relays_published": "2024-01-01 00:00:00",
+            "bridges_published": "2024-01-01 00:00:00",
+            "relays": [
+                {
+                    "nickname": "alpha", "fingerprint": FP_A, "or_addresses": [],
+                    "last_seen": "", "last_changed_address_or_port": "", "first_seen": "",
+                    "running": false, "consensus_weight": 1, "flags": ["Running", "Valid"],
+                    "contact": "Ops <ops@example.com>", "version_status": "obsolete",
+                    "effective_family": [FP_A, FP_B]
+                },
+                {
+                    "nickname": "beta", "fingerprint": FP_B, "or_addresses": [],
+                    "last_seen": "", "last_changed_address_or_port": "", "first_seen": "",
+                    "running": true, "consensus_weight": 1, "flags": ["Guard", "Stable"],
+                    "overload_general_timestamp": 1700000000000u64
+                }
+            ],
+            "bridges": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_config_from_json() {
+        let config = AlertConfig::from_json(
+            r#"{"rules":[{"name":"r","conditions":[{"type":"missing_flag","flag":"Guard"},{"type":"version_status"}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.rules[0].severity, Severity::Warning);
+        assert!(matches!(
+            &config.rules[0].conditions[1],
+            Condition::VersionStatus { statuses } if statuses.len() == 2
+        ));
+        assert!(AlertConfig::from_json(r#"{"rules":[{"name":"r"}]}"#).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_config_from_toml() {
+        let config = AlertConfig::from_toml(
+            r#"
+            [[rules]]
+            name = "guards"
+            severity = "critical"
+            select = { fingerprints = ["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"] }
+            conditions = [{ type = "missing_flag", flag = "Guard" }, { type = "fd_exhausted" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.rules[0].severity, Severity::Critical);
+        assert_eq!(config.rules[0].conditions.len(), 2);
+    }
+
+    #[test]
+    fn test_selector() {
+        let doc = details();
+        let by_contact = Selector {
+            contact: Some("OPS@example".to_string()),
+            ..Default::default()
+        };
+        assert!(by_contact.matches(&doc.relays[0]));
+        assert!(!by_contact.matches(&doc.relays[1]));
+
+        let by_family = Selector {
+            family: Some(format!("${}", FP_B)),
+            ..Default::default()
+        };
+        assert!(by_family.matches(&doc.relays[0]));
+        assert!(by_family.matches(&doc.relays[1]));
+    }
+
+    #[test]
+    fn test_evaluate_and_deduplicate() {
+        let config = AlertConfig {
+            rules: vec![Rule {
+                name: "health".to_string(),
+                severity: Severity::Critical,
+                select: Selector::default(),
+                conditions: vec![
+                    Condition::NotRunning,
+                    Condition::MissingFlag {
+                        flag: "stable".to_string(),
+                    },
+                    Condition::Overloaded,
+                    Condition::VersionStatus {
+                        statuses: default_version_statuses(),
+                    },
+                ],
+            }],
+        };
+        let alerts = AlertEngine::new(config).evaluate(&details(), None);
+        let keys: Vec<&str> = alerts.iter().map(|a| a.dedup_key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                format!("health:{}:not_running", FP_A),
+                format!("health:{}:version_status", FP_A),
+                format!("health:{}:overloaded", FP_B),
+            ]
+        );
+
+        let mut dedup = Deduplicator::new();
+        assert_eq!(dedup.filter_new(alerts.clone()).len(), 3);
+        assert!(dedup.filter_new(alerts.clone()).is_empty());
+        dedup.filter_new(Vec::new());
+        assert_eq!(dedup.filter_new(alerts).len(), 3);
+    }
+
+    fn guard_engine() -> AlertEngine {
+        AlertEngine::new(AlertConfig {
+            rules: vec![Rule {
+                name: "guards".to_string(),
+                severity: Severity::Warning,
+                select: Selector::default(),
+                conditions: vec![Condition::MissingFlag {
+                    flag: "Guard".to_string(),
+                }],
+            }],
+        })
+    }
+
+    fn set_flags(doc: &mut DetailsResponse, index: usize, flags: Option<&[&str]>) {
+        doc.relays[index].flags = flags.map(|f| f.iter().map(|f| f.to_string()).collect());
+    }
+
+    #[test]
+    fn test_missing_flag_never_had_flag() {
+        // alpha never had Guard, so it is not reported on any evaluation
+        let mut engine = guard_engine();
+        let doc = details();
+        assert!(engine.evaluate(&doc, None).is_empty());
+        assert!(engine.evaluate(&doc, None).is_empty());
+    }
+
+    #[test]
+    fn test_missing_flag_flags_omitted() {
+        let mut engine = guard_engine();
+        let mut doc = details();
+        set_flags(&mut doc, 1, None);
+        assert!(engine.evaluate(&doc, None).is_empty());
+
+        // Omitted flags neither raise an alert nor forget the flags seen before
+        let mut doc = details();
+        engine.evaluate(&doc, None);
+        set_flags(&mut doc, 1, None);
+        assert!(engine.evaluate(&doc, None).is_empty());
+        set_flags(&mut doc, 1, Some(&["Stable"]));
+        assert_eq!(engine.evaluate(&doc, None).len(), 1);
+    }
+
+    #[test]
+    fn test_missing_flag_lost() {
+        let mut engine = guard_engine();
+        let mut doc = details();
+        assert!(engine.evaluate(&doc, None).is_empty());
+
+        set_flags(&mut doc, 1, Some(&["Stable"]));
+        let alerts = engine.evaluate(&doc, None);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].fingerprint, FP_B);
+        assert_eq!(alerts[0].condition, "missing_flag:guard");
+        assert_eq!(alerts[0].message, "relay lost the Guard flag");
+
+        // The flag is only lost once
+        assert!(engine.evaluate(&doc, None).is_empty());
+    }
+}
@@ -0,0 +1,830 @@
+//! Arrow and Parquet Export
+//!
+//! This module converts details documents and graph histories into Arrow record
+//! batches and writes them as Parquet files. Unlike the [`tabular`](crate::tabular)
+//! writers, the columns are typed: timestamps are UTC timestamps, lists such as
+//! `flags` and `or_addresses` are list columns, and exit policy summaries are structs.
+//!
+//! A [`Dataset`] appends snapshots to a directory partitioned by table and date, e.g.
+//! `relays/date=2024-05-01/part-20240501T120000.parquet`, which DuckDB, Polars and
+//! Spark read as one table with a `date` column.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Float64Array, ListArray, RecordBatch, StringArray, StructArray,
+    TimestampMillisecondArray, TimestampSecondArray, UInt32Array, UInt64Array,
+};
+use arrow_buffer::NullBuffer;
+use arrow_schema::{ArrowError, DataType, Field, Fields, Schema, SchemaRef};
+use chrono::NaiveDateTime;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+
+use crate::models::{
+    BridgeDetails, DetailsResponse, ExitPolicySummary, OnionooResponse, RelayDetails,
+};
+use crate::tabular::{Series, SeriesPoint};
+use crate::utils::parse_timestamp;
+
+/// Error type for Arrow and Parquet export
+#[derive(Debug)]
+pub enum DatasetError {
+    /// Reading or writing files failed
+    Io(io::Error),
+    /// A record batch could not be built
+    Arrow(ArrowError),
+    /// A Parquet file could not be written
+    Parquet(ParquetError),
+    /// The publication time of a document could not be parsed
+    InvalidTimestamp(String),
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::Io(e) => write!(f, "I/O error: {}", e),
+            DatasetError::Arrow(e) => write!(f, "Arrow error: {}", e),
+            DatasetError::Parquet(e) => write!(f, "Parquet error: {}", e),
+            DatasetError::InvalidTimestamp(timestamp) => {
+                write!(f, "Invalid publication time: {}", timestamp)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatasetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DatasetError::Io(e) => Some(e),
+            DatasetError::Arrow(e) => Some(e),
+            DatasetError::Parquet(e) => Some(e),
+            DatasetError::InvalidTimestamp(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for DatasetError {
+    fn from(err: io::Error) -> Self {
+        DatasetError::Io(err)
+    }
+}
+
+impl From<ArrowError> for DatasetError {
+    fn from(err: ArrowError) -> Self {
+        DatasetError::Arrow(err)
+    }
+}
+
+impl From<ParquetError> for DatasetError {
+    fn from(err: ParquetError) -> Self {
+        DatasetError::Parquet(err)
+    }
+}
+
+const UTC: &str = "UTC";
+
+/// Collects the columns of a record batch
+#[derive(Default)]
+struct Columns {
+    fields: Vec<Field>,
+    arrays: Vec<ArrayRef>,
+}
+
+impl Columns {
+    fn push(&mut self, name: &str, array: ArrayRef, nullable: bool) {
+        self.fields
+            .push(Field::new(name, array.data_type().clone(), nullable));
+        self.arrays.push(array);
+    }
+
+    fn finish(self) -> Result<RecordBatch, ArrowError> {
+        RecordBatch::try_new(Arc::new(Schema::new(self.fields)), self.arrays)
+    }
+}
+
+fn strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+    Arc::new(values.collect::<StringArray>())
+}
+
+fn booleans(values: impl Iterator<Item = Option<bool>>) -> ArrayRef {
+    Arc::new(values.collect::<BooleanArray>())
+}
+
+fn floats(values: impl Iterator<Item = Option<f64>>) -> ArrayRef {
+    Arc::new(values.collect::<Float64Array>())
+}
+
+fn integers(values: impl Iterator<Item = Option<u64>>) -> ArrayRef {
+    Arc::new(values.collect::<UInt64Array>())
+}
+
+/// Onionoo timestamps as seconds since the epoch, unparseable ones as null
+fn timestamps<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+    let seconds = values.map(|value| value.and_then(parse_timestamp).map(seconds));
+    Arc::new(seconds.collect::<TimestampSecondArray>().with_timezone(UTC))
+}
+
+fn seconds(timestamp: NaiveDateTime) -> i64 {
+    timestamp.and_utc().timestamp()
+}
+
+fn lists<'a>(values: impl Iterator<Item = Option<&'a Vec<String>>>) -> ListArray {
+    let mut builder = ListBuilder::new(StringBuilder::new())
+        .with_field(Arc::new(Field::new_list_field(DataType::Utf8, false)));
+    for value in values {
+        match value {
+            Some(items) => {
+                for item in items {
+                    builder.values().append_value(item);
+                }
+                builder.append(true);
+            }
+            None => builder.append(false),
+        }
+    }
+    builder.finish()
+}
+
+fn list_column<'a>(values: impl Iterator<Item = Option<&'a Vec<String>>>) -> ArrayRef {
+    Arc::new(lists(values))
+}
+
+/// Exit policy summaries as structs of `accept` and `reject` lists
+fn policies(values: &[Option<&ExitPolicySummary>]) -> Result<ArrayRef, ArrowError> {
+    let accept = lists(values.iter().map(|v| v.and_then(|s| s.accept.as_ref())));
+    let reject = lists(values.iter().map(|v| v.and_then(|s| s.reject.as_ref())));
+    let fields = Fields::from(vec![
+        Field::new("accept", accept.data_type().clone(), true),
+        Field::new("reject", reject.data_type().clone(), true),
+    ]);
+    let nulls = NullBuffer::from(values.iter().map(Option::is_some).collect::<Vec<_>>());
+    let array = StructArray::try_new(
+        fields,
+        vec![Arc::new(accept), Arc::new(reject)],
+        Some(nulls),
+    )?;
+    Ok(Arc::new(array))
+}
+
+fn published_column(published: NaiveDateTime, rows: usize) -> ArrayRef {
+    let values = vec![seconds(published); rows];
+    Arc::new(TimestampSecondArray::from(values).with_timezone(UTC))
+}
+
+/// Parses the publication time of a document
+fn parse_published(timestamp: &str) -> Result<NaiveDateTime, DatasetError> {
+    parse_timestamp(timestamp).ok_or_else(|| DatasetError::InvalidTimestamp(timestamp.to_string()))
+}
+
+/// Record batch of relays, one row per relay
+///
+/// The first column, `published`, is the `relays_published` time of the snapshot.
+/// The other columns are named as the fields of [`RelayDetails`], with `as` as an
+/// unsigned integer, timestamps as UTC timestamps (`overload_general_timestamp` in
+/// milliseconds) and `exit_policy_summary` and `exit_policy_v6_summary` as structs of
+/// `accept` and `reject` lists.
+pub fn relays_batch(
+    relays: &[RelayDetails],
+    published: NaiveDateTime,
+) -> Result<RecordBatch, DatasetError> {
+    let mut c = Columns::default();
+    c.push(
+        "published",
+        published_column(published, relays.len()),
+        false,
+    );
+    c.push(
+        "nickname",
+        strings(relays.iter().map(|r| Some(r.nickname.as_str()))),
+        false,
+    );
+    c.push(
+        "fingerprint",
+        strings(relays.iter().map(|r| Some(r.fingerprint.as_str()))),
+        false,
+    );
+    c.push(
+        "or_addresses",
+        list_column(relays.iter().map(|r| Some(&r.or_addresses))),
+        false,
+    );
+    c.push(
+        "exit_addresses",
+        list_column(relays.iter().map(|r| r.exit_addresses.as_ref())),
+        true,
+    );
+    c.push(
+        "dir_address",
+        strings(relays.iter().map(|r| r.dir_address.as_deref())),
+        true,
+    );
+    c.push(
+        "last_seen",
+        timestamps(relays.iter().map(|r| Some(r.last_seen.as_str()))),
+        true,
+    );
+    c.push(
+        "last_changed_address_or_port",
+        timestamps(
+            relays
+                .iter()
+                .map(|r| Some(r.last_changed_address_or_port.as_str())),
+        ),
+        true,
+    );
+    c.push(
+        "first_seen",
+        timestamps(relays.iter().map(|r| Some(r.first_seen.as_str()))),
+        true,
+    );
+    c.push(
+        "running",
+        booleans(relays.iter().map(|r| Some(r.running))),
+        false,
+    );
+    c.push(
+        "hibernating",
+        booleans(relays.iter().map(|r| r.hibernating)),
+        true,
+    );
+    c.push(
+        "flags",
+        list_column(relays.iter().map(|r| r.flags.as_ref())),
+        true,
+    );
+    c.push(
+        "country",
+        strings(
+            relays
+                .iter()
+                .map(|r| r.country.as_ref().map(|c| c.as_str())),
+        ),
+        true,
+    );
+    c.push(
+        "country_name",
+        strings(relays.iter().map(|r| r.country_name.as_deref())),
+        true,
+    );
+    c.push(
+        "region_name",
+        strings(relays.iter().map(|r| r.region_name.as_deref())),
+        true,
+    );
+    c.push(
+        "city_name",
+        strings(relays.iter().map(|r| r.city_name.as_deref())),
+        true,
+    );
+    c.push("latitude", floats(relays.iter().map(|r| r.latitude)), true);
+    c.push(
+        "longitude",
+        floats(relays.iter().map(|r| r.longitude)),
+        true,
+    );
+    let as_numbers = relays.iter().map(|r| r.as_number.map(|n| n.0));
+    c.push("as", Arc::new(as_numbers.collect::<UInt32Array>()), true);
+    c.push(
+        "as_name",
+        strings(relays.iter().map(|r| r.as_name.as_deref())),
+        true,
+    );
+    c.push(
+        "consensus_weight",
+        integers(relays.iter().map(|r| Some(r.consensus_weight))),
+        false,
+    );
+    c.push(
+        "verified_host_names",
+        list_column(relays.iter().map(|r| r.verified_host_names.as_ref())),
+        true,
+    );
+    c.push(
+        "unverified_host_names",
+        list_column(relays.iter().map(|r| r.unverified_host_names.as_ref())),
+        true,
+    );
+    c.push(
+        "last_restarted",
+        timestamps(relays.iter().map(|r| r.last_restarted.as_deref())),
+        true,
+    );
+    c.push(
+        "bandwidth_rate",
+        integers(relays.iter().map(|r| r.bandwidth_rate)),
+        true,
+    );
+    c.push(
+        "bandwidth_burst",
+        integers(relays.iter().map(|r| r.bandwidth_burst)),
+        true,
+    );
+    c.push(
+        "observed_bandwidth",
+        integers(relays.iter().map(|r| r.observed_bandwidth)),
+        true,
+    );
+    c.push(
+        "advertised_bandwidth",
+        integers(relays.iter().map(|r| r.advertised_bandwidth)),
+        true,
+    );
+    c.push(
+        "overload_general_timestamp",
+        overload_timestamps(relays.iter().map(|r| r.overload_general_timestamp)),
+        true,
+    );
+    c.push(
+        "exit_policy",
+        list_column(relays.iter().map(|r| r.exit_policy.as_ref())),
+        true,
+    );
+    let summaries: Vec<_> = relays
+        .iter()
+        .map(|r| r.exit_policy_summary.as_ref())
+        .collect();
+    c.push("exit_policy_summary", policies(&summaries)?, true);
+    let summaries: Vec<_> = relays
+        .iter()
+        .map(|r| r.exit_policy_v6_summary.as_ref())
+        .collect();
+    c.push("exit_policy_v6_summary", policies(&summaries)?, true);
+    c.push(
+        "contact",
+        strings(relays.iter().map(|r| r.contact.as_deref())),
+        true,
+    );
+    c.push(
+        "platform",
+        strings(relays.iter().map(|r| r.platform.as_deref())),
+        true,
+    );
+    c.push(
+        "version",
+        strings(relays.iter().map(|r| r.version.as_deref())),
+        true,
+    );
+    c.push(
+        "recommended_version",
+        booleans(relays.iter().map(|r| r.recommended_version)),
+        true,
+    );
+    c.push(
+        "version_status",
+        strings(relays.iter().map(|r| r.version_status.as_deref())),
+        true,
+    );
+    c.push(
+        "effective_family",
+        list_column(relays.iter().map(|r| r.effective_family.as_ref())),
+        true,
+    );
+    c.push(
+        "alleged_family",
+        list_column(relays.iter().map(|r| r.alleged_family.as_ref())),
+        true,
+    );
+    c.push(
+        "indirect_family",
+        list_column(relays.iter().map(|r| r.indirect_family.as_ref())),
+        true,
+    );
+    c.push(
+        "consensus_weight_fraction",
+        floats(relays.iter().map(|r| r.consensus_weight_fraction)),
+        true,
+    );
+    c.push(
+        "guard_probability",
+        floats(relays.iter().map(|r| r.guard_probability)),
+        true,
+    );
+    c.push(
+        "middle_probability",
+        floats(relays.iter().map(|r| r.middle_probability)),
+        true,
+    );
+    c.push(
+        "exit_probability",
+        floats(relays.iter().map(|r| r.exit_probability)),
+        true,
+    );
+    c.push(
+        "measured",
+        booleans(relays.iter().map(|r| r.measured)),
+        true,
+    );
+    c.push(
+        "unreachable_or_addresses",
+        list_column(relays.iter().map(|r| r.unreachable_or_addresses.as_ref())),
+        true,
+    );
+    Ok(c.finish()?)
+}
+
+/// Record batch of bridges, one row per bridge
+///
+/// The first column, `published`, is the `bridges_published` time of the snapshot.
+/// The other columns are named and typed as in [`relays_batch`].
+pub fn bridges_batch(
+    bridges: &[BridgeDetails],
+    published: NaiveDateTime,
+) -> Result<RecordBatch, DatasetError> {
+    let mut c = Columns::default();
+    c.push(
+        "published",
+        published_column(published, bridges.len()),
+        false,
+    );
+    c.push(
+        "nickname",
+        strings(bridges.iter().map(|b| Some(b.nickname.as_str()))),
+        false,
+    );
+    c.push(
+        "hashed_fingerprint",
+        strings(bridges.iter().map(|b| Some(b.hashed_fingerprint.as_str()))),
+        false,
+    );
+    c.push(
+        "or_addresses",
+        list_column(bridges.iter().map(|b| Some(&b.or_addresses))),
+        false,
+    );
+    c.push(
+        "last_seen",
+        timestamps(bridges.iter().map(|b| Some(b.last_seen.as_str()))),
+        true,
+    );
+    c.push(
+        "first_seen",
+        timestamps(bridges.iter().map(|b| Some(b.first_seen.as_str()))),
+        true,
+    );
+    c.push(
+        "running",
+        booleans(bridges.iter().map(|b| Some(b.running))),
+        false,
+    );
+    c.push(
+        "flags",
+        list_column(bridges.iter().map(|b| b.flags.as_ref())),
+        true,
+    );
+    c.push(
+        "last_restarted",
+        timestamps(bridges.iter().map(|b| b.last_restarted.as_deref())),
+        true,
+    );
+    c.push(
+        "advertised_bandwidth",
+        integers(bridges.iter().map(|b| b.advertised_bandwidth)),
+        true,
+    );
+    c.push(
+        "overload_general_timestamp",
+        overload_timestamps(bridges.iter().map(|b| b.overload_general_timestamp)),
+        true,
+    );
+    c.push(
+        "contact",
+        strings(bridges.iter().map(|b| b.contact.as_deref())),
+        true,
+    );
+    c.push(
+        "platform",
+        strings(bridges.iter().map(|b| b.platform.as_deref())),
+        true,
+    );
+    c.push(
+        "version",
+        strings(bridges.iter().map(|b| b.version.as_deref())),
+        true,
+    );
+    c.push(
+        "recommended_version",
+        booleans(bridges.iter().map(|b| b.recommended_version)),
+        true,
+    );
+    c.push(
+        "version_status",
+        strings(bridges.iter().map(|b| b.version_status.as_deref())),
+        true,
+    );
+    c.push(
+        "transports",
+        list_column(bridges.iter().map(|b| b.transports.as_ref())),
+        true,
+    );
+    c.push(
+        "blocklist",
+        list_column(bridges.iter().map(|b| b.blocklist.as_ref())),
+        true,
+    );
+    c.push(
+        "bridgedb_distributor",
+        strings(bridges.iter().map(|b| b.bridgedb_distributor.as_deref())),
+        true,
+    );
+    Ok(c.finish()?)
+}
+
+/// `overload_general_timestamp` is in milliseconds since the epoch
+fn overload_timestamps(values: impl Iterator<Item = Option<u64>>) -> ArrayRef {
+    let millis = values.map(|value| value.and_then(|v| i64::try_from(v).ok()));
+    Arc::new(
+        millis
+            .collect::<TimestampMillisecondArray>()
+            .with_timezone(UTC),
+    )
+}
+
+/// Record batch of graph history data points, see [`SeriesPoint`]
+///
+/// The columns are `fingerprint`, `series`, `period`, `timestamp` as a UTC timestamp
+/// and `value`, with gaps as nulls.
+pub fn series_batch(points: &[SeriesPoint]) -> Result<RecordBatch, DatasetError> {
+    let mut c = Columns::default();
+    c.push(
+        "fingerprint",
+        strings(points.iter().map(|p| Some(p.fingerprint.as_str()))),
+        false,
+    );
+    c.push(
+        "series",
+        strings(points.iter().map(|p| Some(p.series.as_str()))),
+        false,
+    );
+    c.push(
+        "period",
+        strings(points.iter().map(|p| Some(p.period))),
+        false,
+    );
+    let timestamps = points
+        .iter()
+        .map(|p| seconds(p.timestamp))
+        .collect::<Vec<_>>();
+    c.push(
+        "timestamp",
+        Arc::new(TimestampSecondArray::from(timestamps).with_timezone(UTC)),
+        false,
+    );
+    c.push("value", floats(points.iter().map(|p| p.value)), true);
+    Ok(c.finish()?)
+}
+
+/// Schema of [`relays_batch`]
+pub fn relays_schema() -> SchemaRef {
+    relays_batch(&[], NaiveDateTime::default())
+        .expect("empty batch is valid")
+        .schema()
+}
+
+/// Schema of [`bridges_batch`]
+pub fn bridges_schema() -> SchemaRef {
+    bridges_batch(&[], NaiveDateTime::default())
+        .expect("empty batch is valid")
+        .schema()
+}
+
+/// Schema of [`series_batch`]
+pub fn series_schema() -> SchemaRef {
+    series_batch(&[]).expect("empty batch is valid").schema()
+}
+
+/// Default Parquet writer properties, with zstd compression
+pub fn writer_properties() -> WriterProperties {
+    WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build()
+}
+
+/// Write a record batch as a Parquet file to `out` and return `out`
+pub fn write_parquet<W: Write + Send>(
+    out: W,
+    batch: &RecordBatch,
+    properties: WriterProperties,
+) -> Result<W, DatasetError> {
+    let mut writer = ArrowWriter::try_new(out, batch.schema(), Some(properties))?;
+    writer.write(batch)?;
+    Ok(writer.into_inner()?)
+}
+
+/// Directory of Parquet files partitioned by table and date
+///
+/// Every append writes one file per table to
+/// `<root>/<table>/date=<YYYY-MM-DD>/part-<YYYYMMDD>T<hhmmss>.parquet`, named after the
+/// publication time of the document. Appending the same snapshot again replaces its
+/// file, so a daily job can be retried. Files are written under a temporary name and
+/// renamed when complete.
+///
+/// # Examples
+///
+/// ```no_run
+/// use onionoo::{Client, QueryParameters, endpoints};
+/// use onionoo::arrow::Dataset;
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new();
+/// let dataset = Dataset::new("onionoo-data");
+///
+/// let details = endpoints::details(&client, QueryParameters::new()).await?;
+/// dataset.append_details(&details)?;
+///
+/// let bandwidth = endpoints::bandwidth(&client, QueryParameters::new()).await?;
+/// dataset.append_history("bandwidth", &bandwidth)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    root: PathBuf,
+    properties: WriterProperties,
+}
+
+impl Dataset {
+    /// Dataset in the directory `root`, which is created on the first append
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            properties: writer_properties(),
+        }
+    }
+
+    /// Parquet writer properties (default [`writer_properties`])
+    pub fn properties(mut self, properties: WriterProperties) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// Root directory of the dataset
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Append a details snapshot to the `relays` and `bridges` tables
+    ///
+    /// Returns the paths of the files written. Tables without rows are skipped.
+    pub fn append_details(&self, details: &DetailsResponse) -> Result<Vec<PathBuf>, DatasetError> {
+        let mut paths = Vec::new();
+        if !details.relays.is_empty() {
+            let published = parse_published(&details.relays_published)?;
+            let batch = relays_batch(&details.relays, published)?;
+            paths.push(self.append("relays", published, &batch)?);
+        }
+        if !details.bridges.is_empty() {
+            let published = parse_published(&details.bridges_published)?;
+            let batch = bridges_batch(&details.bridges, published)?;
+            paths.push(self.append("bridges", published, &batch)?);
+        }
+        Ok(paths)
+    }
+
+    /// Append the graph histories of a document to `table`
+    ///
+    /// The file is partitioned by the `relays_published` time of the document.
+    pub fn append_history<T: Series, U: Series>(
+        &self,
+        table: &str,
+        document: &OnionooResponse<T, U>,
+    ) -> Result<PathBuf, DatasetError> {
+        let published = parse_published(&document.relays_published)?;
+        let batch = series_batch(&document.series())?;
+        self.append(table, published, &batch)
+    }
+
+    /// Path of the file of `table` for a snapshot published at `published`
+    pub fn path(&self, table: &str, published: NaiveDateTime) -> PathBuf {
+        self.root
+            .join(table)
+            .join(published.format("date=%Y-%m-%d").to_string())
+            .join(published.format("part-%Y%m%dT%H%M%S.parquet").to_string())
+    }
+
+    /// Write `batch` as the file of `table` for a snapshot published at `published`
+    pub fn append(
+        &self,
+        table: &str,
+        published: NaiveDateTime,
+        batch: &RecordBatch,
+    ) -> Result<PathBuf, DatasetError> {
+        let path = self.path(table, published);
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let partial = path.with_extension("parquet.tmp");
+        let result = File::create(&partial)
+            .map_err(DatasetError::from)
+            .and_then(|file| write_parquet(file, batch, self.properties.clone()))
+            .and_then(|file| Ok(file.sync_all()?));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UptimeResponse;
+    use arrow_array::RecordBatchReader;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::TimestampSecondType;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn details() -> DetailsResponse {
+        serde_json::from_str(include_str!("../mock/fixtures/details.json")).unwrap()
+    }
+
+    #[test]
+    fn test_relays_batch() {
+        let details = details();
+        let published = parse_published(&details.relays_published).unwrap();
+        let batch = relays_batch(&details.relays, published).unwrap();
+        assert_eq!(batch.num_rows(), details.relays.len());
+        assert_eq!(batch.schema(), relays_schema());
+
+        // Every field of the model has a column, after `published`
+        let relay = serde_json::to_value(&details.relays[0]).unwrap();
+        let schema = batch.schema();
+        assert_eq!(schema.fields().len(), relay.as_object().unwrap().len() + 1);
+        for key in relay.as_object().unwrap().keys() {
+            assert!(schema.field_with_name(key).is_ok(), "{}", key);
+        }
+
+        let first_seen = batch
+            .column_by_name("first_seen")
+            .unwrap()
+            .as_primitive::<TimestampSecondType>();
+        assert_eq!(
+            first_seen.value_as_datetime(0),
+            parse_timestamp(&details.relays[0].first_seen)
+        );
+        assert_eq!(
+            schema.field_with_name("first_seen").unwrap().data_type(),
+            &DataType::Timestamp(arrow_schema::TimeUnit::Second, Some(UTC.into()))
+        );
+
+        let flags = batch.column_by_name("flags").unwrap().as_list::<i32>();
+        let expected = details.relays[1].flags.clone().unwrap_or_default();
+        let values = flags.value(1);
+        let values = values.as_string::<i32>();
+        assert_eq!(values.iter().flatten().collect::<Vec<_>>(), expected);
+
+        let summary = batch.column_by_name("exit_policy_summary").unwrap();
+        assert_eq!(
+            summary.is_null(0),
+            details.relays[0].exit_policy_summary.is_none()
+        );
+    }
+
+    #[test]
+    fn test_dataset() {
+        let root = std::env::temp_dir().join(format!("onionoo-dataset-{}", std::process::id()));
+        let dataset = Dataset::new(&root);
+
+        let details = details();
+        let paths = dataset.append_details(&details).unwrap();
+        assert_eq!(paths.len(), 2);
+        // Appending the same snapshot replaces its files
+        assert_eq!(dataset.append_details(&details).unwrap(), paths);
+        let published = parse_published(&details.relays_published).unwrap();
+        assert_eq!(paths[0], dataset.path("relays", published));
+        assert!(
+            paths[0]
+                .to_string_lossy()
+                .contains(&published.format("relays/date=%Y-%m-%d/").to_string())
+        );
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&paths[1]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(reader.schema(), bridges_schema());
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, details.bridges.len());
+
+        let uptime: UptimeResponse =
+            serde_json::from_str(include_str!("../mock/fixtures/uptime.json")).unwrap();
+        let path = dataset.append_history("uptime", &uptime).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(reader.schema(), series_schema());
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, uptime.series().len());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
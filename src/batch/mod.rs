@@ -0,0 +1,209 @@
+//! Concurrent Batch Queries
+//!
+//! The Onionoo `lookup` parameter accepts a single fingerprint, and a query cannot
+//! combine alternatives. This module fans out one request per fingerprint or per
+//! [`QueryParameters`], runs a bounded number of them concurrently, and merges the
+//! documents into one, removing relays and bridges that were returned more than once.
+
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use tokio::task::JoinSet;
+
+use crate::client::{Client, ClientError};
+use crate::endpoints::Endpoint;
+use crate::models::{Fingerprinted, OnionooResponse};
+use crate::parameters::QueryParameters;
+
+/// Number of concurrent requests used by [`Batch::new`]
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Merged result of a batch of requests
+#[derive(Debug)]
+pub struct BatchResponse<T, U> {
+    /// Merged document, or `None` if every request failed
+    ///
+    /// The header fields, such as `relays_published`, are those of the first successful
+    /// request. Skipped and truncated counts are summed over all requests.
+    pub response: Option<OnionooResponse<T, U>>,
+    /// Failed requests by input index, in input order
+    pub failures: Vec<(usize, ClientError)>,
+}
+
+impl<T, U> BatchResponse<T, U> {
+    /// Whether every request succeeded
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Batch of requests to one endpoint
+///
+/// # Examples
+///
+/// ```no_run
+/// use onionoo::{Client, QueryParameters, endpoints::Endpoint};
+/// use onionoo::models::{BridgeDetails, RelayDetails};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new();
+/// let fingerprints = [
+///     "9695DFC35FFEB861329B9F1AB04C46397020CE31",
+///     "847B1F850344D7876491A54892F904934E4EB85D",
+/// ];
+///
+/// let batch = client
+///     .batch(Endpoint::Details)
+///     .concurrency(4)
+///     .lookup::<RelayDetails, BridgeDetails, _>(fingerprints, QueryParameters::new())
+///     .await;
+/// for (index, error) in &batch.failures {
+///     eprintln!("{}: {}", fingerprints[*index], error);
+/// }
+/// if let Some(response) = batch.response {
+///     println!("Found {} relays", response.relays.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Batch<'a> {
+    client: &'a Client,
+    endpoint: Endpoint,
+    concurrency: usize,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(client: &'a Client, endpoint: Endpoint) -> Self {
+        Self {
+            client,
+            endpoint,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Maximum number of requests in flight; zero is treated as one
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Look up each fingerprint, adding it to `params` as the `lookup` parameter
+    pub async fn lookup<T, U, I>(
+        self,
+        fingerprints: I,
+        params: QueryParameters,
+    ) -> BatchResponse<T, U>
+    where
+        T: DeserializeOwned + Fingerprinted + Send + 'static,
+        U: DeserializeOwned + Fingerprinted + Send + 'static,
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let queries = fingerprints
+            .into_iter()
+            .map(|fingerprint| params.clone().lookup(fingerprint))
+            .collect();
+        self.queries(queries).await
+    }
+
+    /// Run each query; relays and bridges matching any of them are returned
+    pub async fn queries<T, U>(self, queries: Vec<QueryParameters>) -> BatchResponse<T, U>
+    where
+        T: DeserializeOwned + Fingerprinted + Send + 'static,
+        U: DeserializeOwned + Fingerprinted + Send + 'static,
+    {
+        let mut pending = queries.into_iter().enumerate();
+        let mut tasks = JoinSet::new();
+        let mut results = Vec::new();
+
+        loop {
+            while tasks.len() < self.concurrency {
+                let Some((index, params)) = pending.next() else {
+                    break;
+                };
+                let client = self.client.clone();
+                let endpoint = self.endpoint;
+                tasks.spawn(async move {
+                    let result = client
+                        .get_endpoint::<OnionooResponse<T, U>>(endpoint, params)
+                        .await;
+                    (index, result)
+                });
+            }
+            match tasks.join_next().await {
+                Some(Ok(result)) => results.push(result),
+                Some(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+                None => break,
+            }
+        }
+
+        results.sort_by_key(|(index, _)| *index);
+        let mut responses = Vec::new();
+        let mut failures = Vec::new();
+        for (index, result) in results {
+            match result {
+                Ok(response) => responses.push(response),
+                Err(e) => failures.push((index, e)),
+            }
+        }
+
+        BatchResponse {
+            response: merge(responses),
+            failures,
+        }
+    }
+}
+
+impl Client {
+    /// Start a batch of requests to an endpoint
+    pub fn batch(&self, endpoint: Endpoint) -> Batch<'_> {
+        Batch::new(self, endpoint)
+    }
+}
+
+/// Merges documents in order, keeping the first object for each fingerprint
+///
+/// Objects without a fingerprint are always kept.
+pub fn merge<T, U>(responses: Vec<OnionooResponse<T, U>>) -> Option<OnionooResponse<T, U>>
+where
+    T: Fingerprinted,
+    U: Fingerprinted,
+{
+    let mut responses = responses.into_iter();
+    let mut merged = responses.next()?;
+
+    let mut relays = HashSet::new();
+    let mut bridges = HashSet::new();
+    merged.relays.retain(|relay| is_new(&mut relays, relay));
+    merged.bridges.retain(|bridge| is_new(&mut bridges, bridge));
+
+    let sum = |a: Option<u64>, b: Option<u64>| match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+    };
+    for response in responses {
+        merged.relays.extend(
+            response
+                .relays
+                .into_iter()
+                .filter(|relay| is_new(&mut relays, relay)),
+        );
+        merged.bridges.extend(
+            response
+                .bridges
+                .into_iter()
+                .filter(|bridge| is_new(&mut bridges, bridge)),
+        );
+        merged.relays_skipped = sum(merged.relays_skipped, response.relays_skipped);
+        merged.relays_truncated = sum(merged.relays_truncated, response.relays_truncated);
+        merged.bridges_skipped = sum(merged.bridges_skipped, response.bridges_skipped);
+        merged.bridges_truncated = sum(merged.bridges_truncated, response.bridges_truncated);
+    }
+
+    Some(merged)
+}
+
+fn is_new<E: Fingerprinted>(seen: &mut HashSet<String>, entry: &E) -> bool {
+    entry
+        .fingerprint()
+        .is_none_or(|fingerprint| seen.insert(fingerprint.to_uppercase()))
+}
@@ -0,0 +1,454 @@
+//! Onionoo command-line client
+//!
+//! Exposes every endpoint in [`onionoo::endpoints::Endpoint`] as a subcommand and every
+//! parameter in [`onionoo::parameters::all::ALL_PARAMS`] as a flag, and can serve a
+//! stored snapshot with [`onionoo::mirror::MirrorServer`]. Built with the `cli` cargo
+//! feature.
+
+mod output;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use onionoo::{
+    Client, QueryParameters,
+    endpoints::{BASE_URL, Endpoint},
+    mirror::MirrorServer,
+    parameters::{
+        AsList, BoolValue, CountryCode, Date, DaysRange, TypeValue,
+        helpers::{fields_list, order_list, version_list},
+    },
+};
+use serde_json::Value;
+
+/// Query the Onionoo Tor network status protocol
+#[derive(Debug, Parser)]
+#[command(name = "onionoo", version)]
+struct Cli {
+    /// Base URL of the Onionoo instance, e.g. a local mirror
+    #[arg(long, global = true, default_value = BASE_URL)]
+    base_url: String,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = output::Format::Json)]
+    format: output::Format,
+
+    /// Refuse to send queries with validation warnings, e.g. limit=0
+    #[arg(long, global = true)]
+    strict: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Fetch a summary document
+    Summary(QueryArgs),
+    /// Fetch a details document
+    Details(QueryArgs),
+    /// Fetch a bandwidth document
+    Bandwidth(QueryArgs),
+    /// Fetch a weights document (relays only)
+    Weights(QueryArgs),
+    /// Fetch a clients document (bridges only)
+    Clients(QueryArgs),
+    /// Fetch an uptime document
+    Uptime(QueryArgs),
+    /// Serve a directory of stored documents as an Onionoo mirror
+    Serve(ServeArgs),
+}
+
+impl Command {
+    /// Endpoint and query arguments of a fetch subcommand
+    fn query(&self) -> Option<(Endpoint, &QueryArgs)> {
+        match self {
+            Command::Summary(args) => Some((Endpoint::Summary, args)),
+            Command::Details(args) => Some((Endpoint::Details, args)),
+            Command::Bandwidth(args) => Some((Endpoint::Bandwidth, args)),
+            Command::Weights(args) => Some((Endpoint::Weights, args)),
+            Command::Clients(args) => Some((Endpoint::Clients, args)),
+            Command::Uptime(args) => Some((Endpoint::Uptime, args)),
+            Command::Serve(_) => None,
+        }
+    }
+}
+
+/// Arguments of the `serve` subcommand
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// Directory containing summary.json, details.json, bandwidth.json, weights.json,
+    /// clients.json and/or uptime.json
+    #[arg(long)]
+    dir: PathBuf,
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TypeArg {
+    Relay,
+    Bridge,
+}
+
+impl From<TypeArg> for TypeValue {
+    fn from(value: TypeArg) -> Self {
+        match value {
+            TypeArg::Relay => TypeValue::Relay,
+            TypeArg::Bridge => TypeValue::Bridge,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BoolArg {
+    True,
+    False,
+}
+
+impl From<BoolArg> for BoolValue {
+    fn from(value: BoolArg) -> Self {
+        match value {
+            BoolArg::True => BoolValue::True,
+            BoolArg::False => BoolValue::False,
+        }
+    }
+}
+
+/// Query parameters shared by all endpoint subcommands
+#[derive(Debug, Default, Args)]
+struct QueryArgs {
+    /// Return only relay or only bridge documents
+    #[arg(long = "type", value_name = "TYPE", value_enum)]
+    type_param: Option<TypeArg>,
+    /// Return only running or only non-running relays and/or bridges
+    #[arg(long, value_enum)]
+    running: Option<BoolArg>,
+    /// Search nicknames, fingerprints, IP addresses or qualified search terms
+    #[arg(long)]
+    search: Option<String>,
+    /// Return only the relay or bridge with the given (hashed) fingerprint
+    #[arg(long)]
+    lookup: Option<String>,
+    /// Two-letter country code
+    #[arg(long, value_parser = CountryCode::from_str)]
+    country: Option<CountryCode>,
+    /// Comma-separated AS numbers, with or without the "AS" prefix
+    #[arg(long = "as", value_name = "AS", value_parser = AsList::from_str)]
+    as_param: Option<AsList>,
+    /// (Part of) the AS name
+    #[arg(long)]
+    as_name: Option<String>,
+    /// Relay flag assigned by the directory authorities
+    #[arg(long)]
+    flag: Option<String>,
+    /// Range of days ago the relay or bridge was first seen, e.g. "7-14", "30-", "-90"
+    #[arg(long, allow_hyphen_values = true)]
+    first_seen_days: Option<DaysRange>,
+    /// Range of days ago the relay or bridge was last seen
+    #[arg(long, allow_hyphen_values = true)]
+    last_seen_days: Option<DaysRange>,
+    /// First seen after the given date (yyyy-MM-dd)
+    #[arg(long)]
+    first_seen_since: Option<Date>,
+    /// Last seen after the given date (yyyy-MM-dd)
+    #[arg(long)]
+    last_seen_since: Option<Date>,
+    /// (Part of) the contact line
+    #[arg(long)]
+    contact: Option<String>,
+    /// Fingerprint of a relay whose effective family should be returned
+    #[arg(long)]
+    family: Option<String>,
+    /// Tor versions as a comma-separated list or ".." range
+    #[arg(long)]
+    version: Option<String>,
+    /// Operating system prefix
+    #[arg(long)]
+    os: Option<String>,
+    /// Host name suffix
+    #[arg(long)]
+    host_name: Option<String>,
+    /// Return only relays and bridges running a recommended (or not recommended) version
+    #[arg(long, value_enum)]
+    recommended_version: Option<BoolArg>,
+    /// Comma-separated list of fields to include
+    #[arg(long)]
+    fields: Option<String>,
+    /// Comma-separated sort keys, prefixed with "-" for descending order
+    #[arg(long, allow_hyphen_values = true)]
+    order: Option<String>,
+    /// Skip the given number of relays and/or bridges
+    #[arg(long)]
+    offset: Option<u32>,
+    /// Limit the result to the given number of relays and/or bridges
+    #[arg(long)]
+    limit: Option<u32>,
+    /// Fetch every page of the result, `--page-size` documents at a time
+    #[arg(long)]
+    all: bool,
+    /// Number of relays and/or bridges requested per page with `--all`
+    #[arg(long, default_value_t = 500, requires = "all")]
+    page_size: u32,
+}
+
+impl QueryArgs {
+    fn to_params(&self, offset: Option<u32>, limit: Option<u32>) -> QueryParameters {
+        let mut params = QueryParameters::new();
+
+        if let Some(value) = self.type_param {
+            params = params.type_param(value.into());
+        }
+        if let Some(value) = self.running {
+            params = params.running(value.into());
+        }
+        if let Some(ref value) = self.search {
+            params = params.search(value);
+        }
+        if let Some(ref value) = self.lookup {
+            params = params.lookup(value);
+        }
+        if let Some(ref value) = self.country {
+            params = params.country(value.clone());
+        }
+        if let Some(ref value) = self.as_param {
+            params = params.as_param(value.clone());
+        }
+        if let Some(ref value) = self.as_name {
+            params = params.as_name(value);
+        }
+        if let Some(ref value) = self.flag {
+            params = params.flag(value);
+        }
+        if let Some(ref value) = self.first_seen_days {
+            params = params.first_seen_days(value.clone());
+        }
+        if let Some(ref value) = self.last_seen_days {
+            params = params.last_seen_days(value.clone());
+        }
+        if let Some(ref value) = self.first_seen_since {
+            params = params.first_seen_since(value.clone());
+        }
+        if let Some(ref value) = self.last_seen_since {
+            params = params.last_seen_since(value.clone());
+        }
+        if let Some(ref value) = self.contact {
+            params = params.contact(value);
+        }
+        if let Some(ref value) = self.family {
+            params = params.family(value);
+        }
+        if let Some(ref value) = self.version {
+            params = params.version(version_list(value));
+        }
+        if let Some(ref value) = self.os {
+            params = params.os(value);
+        }
+        if let Some(ref value) = self.host_name {
+            params = params.host_name(value);
+        }
+        if let Some(value) = self.recommended_version {
+            params = params.recommended_version(value.into());
+        }
+        if let Some(ref value) = self.fields {
+            params = params.fields(fields_list(value));
+        }
+        if let Some(ref value) = self.order {
+            params = params.order(order_list(value));
+        }
+        if let Some(value) = offset {
+            params = params.offset(value);
+        }
+        if let Some(value) = limit {
+            params = params.limit(value);
+        }
+
+        params
+    }
+}
+
+/// Number of relays and bridges contained in a document
+fn entry_count(document: &Value) -> usize {
+    ["relays", "bridges"]
+        .iter()
+        .filter_map(|key| document.get(key).and_then(Value::as_array))
+        .map(Vec::len)
+        .sum()
+}
+
+/// Appends the relays and bridges of `page` to `document`
+fn merge_page(document: &mut Value, page: Value) {
+    for key in ["relays", "bridges"] {
+        if let (Some(entries), Some(Value::Array(more))) = (
+            document.get_mut(key).and_then(Value::as_array_mut),
+            page.get(key).cloned(),
+        ) {
+            entries.extend(more);
+        }
+    }
+}
+
+async fn fetch(
+    client: &Client,
+    endpoint: Endpoint,
+    args: &QueryArgs,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    if !args.all {
+        return Ok(client
+            .get_endpoint(endpoint, args.to_params(args.offset, args.limit))
+            .await?);
+    }
+
+    let page_size = args.page_size.max(1);
+    let mut offset = args.offset.unwrap_or(0);
+    let mut document: Option<Value> = None;
+    let mut fetched = 0;
+
+    loop {
+        let remaining = args.limit.map(|limit| limit.saturating_sub(fetched));
+        let limit = remaining.map_or(page_size, |r| r.min(page_size));
+        if limit == 0 {
+            break;
+        }
+
+        let page: Value = client
+            .get_endpoint(endpoint, args.to_params(Some(offset), Some(limit)))
+            .await?;
+        let count = entry_count(&page) as u32;
+        fetched += count;
+        offset += count;
+
+        match document {
+            Some(ref mut document) => merge_page(document, page),
+            None => document = Some(page),
+        }
+
+        if count < limit {
+            break;
+        }
+    }
+
+    Ok(document.unwrap_or(Value::Null))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    let Some((endpoint, args)) = cli.command.query() else {
+        if let Command::Serve(args) = cli.command {
+            let mut server = MirrorServer::bind(&args.dir, &args.listen).await?;
+            eprintln!("Serving {} at {}", args.dir.display(), server.base_url());
+            server.wait().await;
+        }
+        return Ok(());
+    };
+
+    let client = Client::new_with_base_url(cli.base_url).strict(cli.strict);
+
+    let document = fetch(&client, endpoint, args).await?;
+
+    let stdout = std::io::stdout();
+    output::write(&mut stdout.lock(), cli.format, &document)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use onionoo::{endpoints::helpers, parameters::all::ALL_PARAMS};
+
+    #[test]
+    fn test_every_endpoint_has_a_subcommand() {
+        let command = Cli::command();
+        for endpoint in helpers::all_endpoints() {
+            assert!(
+                command.find_subcommand(endpoint.name()).is_some(),
+                "missing subcommand for {}",
+                endpoint.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_parameter_has_a_flag() {
+        let command = Cli::command();
+        let summary = command.find_subcommand("summary").unwrap();
+        for param in ALL_PARAMS {
+            let flag = param.replace('_', "-");
+            assert!(
+                summary
+                    .get_arguments()
+                    .any(|arg| arg.get_long() == Some(flag.as_str())),
+                "missing flag --{}",
+                flag
+            );
+        }
+    }
+
+    #[test]
+    fn test_flags_to_query_string() {
+        let cli = Cli::try_parse_from([
+            "onionoo",
+            "details",
+            "--type",
+            "relay",
+            "--running",
+            "true",
+            "--as",
+            "3",
+            "--order",
+            "-consensus_weight",
+            "--limit",
+            "5",
+        ])
+        .unwrap();
+        let (endpoint, args) = cli.command.query().unwrap();
+        assert_eq!(
+            args.to_params(args.offset, args.limit).to_query_string(),
+            "?as=AS3&limit=5&order=-consensus_weight&running=true&type=relay"
+        );
+        assert_eq!(endpoint.path(), "/details");
+    }
+
+    #[test]
+    fn test_serve_arguments() {
+        let cli = Cli::try_parse_from(["onionoo", "serve", "--dir", "snapshot"]).unwrap();
+        assert!(cli.command.query().is_none());
+        let Command::Serve(args) = cli.command else {
+            panic!("expected serve subcommand");
+        };
+        assert_eq!(args.dir, PathBuf::from("snapshot"));
+        assert_eq!(args.listen, "127.0.0.1:8080");
+    }
+
+    #[test]
+    fn test_invalid_days_range_is_rejected() {
+        let cli = Cli::try_parse_from(["onionoo", "summary", "--first-seen-days", "7"]).unwrap();
+        let (_, args) = cli.command.query().unwrap();
+        assert_eq!(
+            args.to_params(None, None).to_query_string(),
+            "?first_seen_days=7-7"
+        );
+        assert!(Cli::try_parse_from(["onionoo", "summary", "--first-seen-days", "14-7"]).is_err());
+        assert!(
+            Cli::try_parse_from(["onionoo", "summary", "--last-seen-since", "yesterday"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["onionoo", "summary", "--country", "usa"]).is_err());
+        assert!(Cli::try_parse_from(["onionoo", "summary", "--as", "ASX"]).is_err());
+    }
+
+    #[test]
+    fn test_merge_page() {
+        let mut document = serde_json::json!({"relays": [1], "bridges": []});
+        merge_page(
+            &mut document,
+            serde_json::json!({"relays": [2], "bridges": [3]}),
+        );
+        assert_eq!(entry_count(&document), 3);
+    }
+}
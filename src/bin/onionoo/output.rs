@@ -0,0 +1,165 @@
+//! Output formatting for the command-line client
+//!
+//! Documents are printed as JSON, or flattened into one row per relay and bridge for
+//! table and CSV output.
+
+use clap::ValueEnum;
+use onionoo::tabular::{self, ListEncoding};
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Maximum width of a table cell before it is truncated
+const MAX_CELL_WIDTH: usize = 48;
+
+/// Output formats supported by the command-line client
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Pretty-printed JSON document
+    Json,
+    /// Aligned plain-text table
+    Table,
+    /// Comma-separated values with a header row, lists as JSON arrays
+    Csv,
+}
+
+/// Write a document to `out` in the given format
+pub fn write<W: Write>(out: &mut W, format: Format, document: &Value) -> io::Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, document)?;
+            writeln!(out)
+        }
+        Format::Table => write_table(out, document),
+        Format::Csv => write_csv(out, document),
+    }
+}
+
+/// Flatten the relays and bridges of a document into a header and rows
+///
+/// The first column is the object kind (`relay` or `bridge`), followed by the union of
+/// all top-level keys in sorted order.
+fn rows(document: &Value, lists: ListEncoding) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut entries = Vec::new();
+    for (key, kind) in [("relays", "relay"), ("bridges", "bridge")] {
+        if let Some(list) = document.get(key).and_then(Value::as_array) {
+            entries.extend(list.iter().filter_map(Value::as_object).map(|o| (kind, o)));
+        }
+    }
+
+    let mut columns: Vec<String> = entries
+        .iter()
+        .flat_map(|(_, object)| object.keys().cloned())
+        .collect();
+    columns.sort();
+    columns.dedup();
+
+    let rows = entries
+        .iter()
+        .map(|(kind, object)| {
+            std::iter::once(kind.to_string())
+                .chain(columns.iter().map(|c| {
+                    object
+                        .get(c)
+                        .map(|value| tabular::cell_text(value, lists))
+                        .unwrap_or_default()
+                }))
+                .collect()
+        })
+        .collect();
+
+    columns.insert(0, "type".to_string());
+    (columns, rows)
+}
+
+/// Tables join lists of scalars with spaces for readability; CSV output uses the
+/// default encoding of [`tabular::TableWriter`]
+fn write_table<W: Write>(out: &mut W, document: &Value) -> io::Result<()> {
+    let (columns, rows) = rows(document, ListEncoding::Delimited(' '));
+    let truncate = |s: &str| -> String {
+        if s.chars().count() > MAX_CELL_WIDTH {
+            let mut short: String = s.chars().take(MAX_CELL_WIDTH - 1).collect();
+            short.push('…');
+            short
+        } else {
+            s.to_string()
+        }
+    };
+
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|c| truncate(c)).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(name.len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |out: &mut W, cells: &[String]| -> io::Result<()> {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<width$}", c, width = w))
+            .collect();
+        writeln!(out, "{}", padded.join("  ").trim_end())
+    };
+
+    line(out, &columns)?;
+    line(
+        out,
+        &widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>(),
+    )?;
+    for row in &rows {
+        line(out, row)?;
+    }
+    Ok(())
+}
+
+fn write_csv<W: Write>(out: &mut W, document: &Value) -> io::Result<()> {
+    let (columns, rows) = rows(document, ListEncoding::default());
+    tabular::write_record(out, &columns)?;
+    for row in &rows {
+        tabular::write_record(out, row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> Value {
+        serde_json::json!({
+            "version": "8.0",
+            "relays": [{"n": "alpha", "f": "AAAA", "a": ["10.0.0.1", "10.0.0.2"], "r": true}],
+            "bridges": [{"n": "beta, the bridge", "h": "BBBB", "r": false}]
+        })
+    }
+
+    #[test]
+    fn test_csv_output() {
+        let mut out = Vec::new();
+        write(&mut out, Format::Csv, &document()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "type,a,f,h,n,r\nrelay,\"[\"\"10.0.0.1\"\",\"\"10.0.0.2\"\"]\",AAAA,,alpha,true\nbridge,,,BBBB,\"beta, the bridge\",false\n"
+        );
+    }
+
+    #[test]
+    fn test_table_output() {
+        let mut out = Vec::new();
+        write(&mut out, Format::Table, &document()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("type    a"));
+        assert!(lines[2].starts_with("relay   10.0.0.1 10.0.0.2"));
+    }
+}
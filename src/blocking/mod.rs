@@ -0,0 +1,289 @@
+//! Blocking Onionoo API Client
+//!
+//! This module provides a synchronous counterpart of [`crate::Client`] and the
+//! [`crate::endpoints`] functions for code that does not run an async runtime. It is
+//! available with the `blocking` feature and is built on `reqwest::blocking`; URL
+//! construction, parameter validation and error mapping are shared with the async
+//! client.
+//!
+//! Like `reqwest::blocking`, the client must not be created or used from within an
+//! async runtime.
+
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::Instant;
+
+use reqwest::blocking::{Client as HttpClient, RequestBuilder};
+use serde::de::DeserializeOwned;
+
+use crate::client::{
+    ClientError, RequestContext, StreamedBody, WithMetadata, decode, new_request,
+    parse_with_metadata, request_url, status_error, validate,
+};
+use crate::endpoints::{self, BASE_URL, Endpoint, OnionooEndpoint};
+use crate::models::{self, DocumentHeader};
+use crate::parameters::QueryParameters;
+use crate::stream::Entry;
+use crate::transport::{Response, header_pairs};
+
+/// Size of the buffer used to read streamed bodies
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Blocking client for the Onionoo API
+///
+/// # Examples
+///
+/// ```no_run
+/// use onionoo::{QueryParameters, blocking, selection::TypeValue};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = blocking::Client::new();
+/// let params = QueryParameters::new()
+///     .type_param(TypeValue::Relay)
+///     .limit(10);
+///
+/// let response = blocking::summary(&client, params)?;
+/// println!("Found {} relays", response.relays.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    /// Internal HTTP client for making requests
+    http_client: HttpClient,
+    /// Base URL for the Onionoo API
+    base_url: Arc<str>,
+    /// Whether validation warnings also reject requests
+    strict: bool,
+}
+
+impl Client {
+    /// Create a new blocking Onionoo API client
+    pub fn new() -> Self {
+        Self::new_with_base_url(BASE_URL)
+    }
+
+    /// Create a new blocking client with a custom base URL
+    pub fn new_with_base_url(base_url: impl Into<Arc<str>>) -> Self {
+        Self {
+            http_client: HttpClient::new(),
+            base_url: base_url.into(),
+            strict: false,
+        }
+    }
+
+    /// Reject requests with validation warnings, not only errors
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Make a generic GET request to an endpoint path, e.g. `/summary`
+    ///
+    /// See [`crate::Client::get`].
+    pub fn get<T>(&self, endpoint: &str, params: QueryParameters) -> Result<T, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        self.get_with_metadata(endpoint, params)
+            .map(|response| response.document)
+    }
+
+    /// Make a generic GET request and return the document with the response metadata
+    ///
+    /// See [`crate::Client::get_with_metadata`].
+    pub fn get_with_metadata<T>(
+        &self,
+        endpoint: &str,
+        params: QueryParameters,
+    ) -> Result<WithMetadata<T>, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        let url = request_url(&self.base_url, endpoint, &params)?;
+        let context = RequestContext {
+            url: url.clone(),
+            endpoint: endpoint.to_string(),
+            query: params,
+        };
+        let started = Instant::now();
+        self.send(&url)
+            .and_then(|response| parse_with_metadata(response, started.elapsed()))
+            .map_err(|e| e.with_context(context))
+    }
+
+    fn send(&self, url: &str) -> Result<Response, ClientError> {
+        let response = self.request(url).send()?;
+        let status = response.status().as_u16();
+        let headers = header_pairs(response.headers());
+        let body = response.bytes()?.to_vec();
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// Make a GET request to an endpoint after validating the parameters
+    ///
+    /// See [`crate::Client::get_endpoint`].
+    pub fn get_endpoint<T>(
+        &self,
+        endpoint: Endpoint,
+        params: QueryParameters,
+    ) -> Result<T, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        validate(endpoint, &params, self.strict)?;
+        self.get(endpoint.path(), params)
+    }
+
+    /// Make a GET request to a typed endpoint
+    ///
+    /// See [`crate::Client::fetch`].
+    pub fn fetch<E: OnionooEndpoint>(
+        &self,
+        params: QueryParameters,
+    ) -> Result<E::Response, ClientError> {
+        self.get_endpoint(E::ENDPOINT, params)
+    }
+
+    /// Make a GET request to a typed endpoint and return the response metadata too
+    ///
+    /// See [`crate::Client::fetch_with_metadata`].
+    pub fn fetch_with_metadata<E: OnionooEndpoint>(
+        &self,
+        params: QueryParameters,
+    ) -> Result<WithMetadata<E::Response>, ClientError> {
+        validate(E::ENDPOINT, &params, self.strict)?;
+        self.get_with_metadata(E::PATH, params)
+    }
+
+    /// Stream the relays and bridges of a typed endpoint's document one at a time
+    ///
+    /// See [`crate::Client::stream`].
+    pub fn stream<E, F>(
+        &self,
+        params: QueryParameters,
+        mut on_entry: F,
+    ) -> Result<WithMetadata<DocumentHeader>, ClientError>
+    where
+        E: OnionooEndpoint,
+        F: FnMut(Entry<E::Relay, E::Bridge>),
+    {
+        validate(E::ENDPOINT, &params, self.strict)?;
+        let url = request_url(&self.base_url, E::PATH, &params)?;
+        let context = RequestContext {
+            url: url.clone(),
+            endpoint: E::PATH.to_string(),
+            query: params,
+        };
+        let started = Instant::now();
+        let result = (|| {
+            let mut response = self.request(&url).send()?;
+            let status = response.status().as_u16();
+            let headers = header_pairs(response.headers());
+            if !response.status().is_success() {
+                let body = response.bytes()?.to_vec();
+                let response = Response {
+                    status,
+                    headers,
+                    body,
+                };
+                return Err(status_error(&decode(response)?.0));
+            }
+            let mut body = StreamedBody::new(&headers)?;
+            let mut chunk = vec![0; CHUNK_SIZE];
+            loop {
+                let read = match response.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(ClientError::transport(e)),
+                };
+                body.feed(chunk[..read].to_vec(), &mut on_entry)?;
+            }
+            body.finish(&mut on_entry, status, headers, started.elapsed())
+        })();
+        result.map_err(|e| e.with_context(context))
+    }
+
+    /// GET request for a URL, accepting the encodings enabled by cargo features
+    fn request(&self, url: &str) -> RequestBuilder {
+        new_request(url.to_string())
+            .headers
+            .into_iter()
+            .fold(self.http_client.get(url), |builder, (name, value)| {
+                builder.header(name, value)
+            })
+    }
+
+    /// Get the base URL being used by this client
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Whether the client is in strict validation mode
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Check if the client is configured to use the default Onionoo URL
+    pub fn is_default(&self) -> bool {
+        &*self.base_url == BASE_URL
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a summary document, see [`crate::endpoints::summary`]
+pub fn summary(
+    client: &Client,
+    params: QueryParameters,
+) -> Result<models::SummaryResponse, ClientError> {
+    client.fetch::<endpoints::Summary>(params)
+}
+
+/// Returns a details document, see [`crate::endpoints::details`]
+pub fn details(
+    client: &Client,
+    params: QueryParameters,
+) -> Result<models::DetailsResponse, ClientError> {
+    client.fetch::<endpoints::Details>(params)
+}
+
+/// Returns a bandwidth document, see [`crate::endpoints::bandwidth`]
+pub fn bandwidth(
+    client: &Client,
+    params: QueryParameters,
+) -> Result<models::BandwidthResponse, ClientError> {
+    client.fetch::<endpoints::Bandwidth>(params)
+}
+
+/// Returns a weights document, see [`crate::endpoints::weights`]
+pub fn weights(
+    client: &Client,
+    params: QueryParameters,
+) -> Result<models::WeightsResponse, ClientError> {
+    client.fetch::<endpoints::Weights>(params)
+}
+
+/// Returns a clients document, see [`crate::endpoints::clients`]
+pub fn clients(
+    client: &Client,
+    params: QueryParameters,
+) -> Result<models::ClientsResponse, ClientError> {
+    client.fetch::<endpoints::Clients>(params)
+}
+
+/// Returns an uptime document, see [`crate::endpoints::uptime`]
+pub fn uptime(
+    client: &Client,
+    params: QueryParameters,
+) -> Result<models::UptimeResponse, ClientError> {
+    client.fetch::<endpoints::Uptime>(params)
+}
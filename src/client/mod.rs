@@ -3,43 +3,377 @@
 //! This module provides the main client for making HTTP requests to the Onionoo API.
 //! The client handles request construction, response processing, and error handling.
 
-use reqwest::Client as HttpClient;
+use chrono::{DateTime, Utc};
 use serde::de::DeserializeOwned;
 use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::parameters::QueryParameters;
+use crate::encoding::{ContentEncoding, Decoder};
+use crate::endpoints::{Endpoint, OnionooEndpoint};
+use crate::limits::{LimitStats, Limiter, RateLimit};
+use crate::metrics::{MetricsHook, Observer};
+use crate::models::DocumentHeader;
+use crate::parameters::{QueryParameters, ValidationReport};
+use crate::stream::{DocumentParser, Entry};
+use crate::transport::{Request, ReqwestTransport, Response, Transport, find_header};
+
+/// Maximum length of the body excerpt kept in [`ClientError::Deserialization`]
+pub const BODY_SNIPPET_LEN: usize = 200;
+
+/// Metadata of a successful response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseMetadata {
+    /// HTTP status code
+    pub status: u16,
+    /// Time the document was last modified, from the `Last-Modified` header
+    pub last_modified: Option<DateTime<Utc>>,
+    /// Value of the `Cache-Control` header
+    pub cache_control: Option<String>,
+    /// Value of the `Content-Encoding` header
+    pub content_encoding: Option<String>,
+    /// Time from sending the request until the whole body was received
+    pub elapsed: Duration,
+    /// Time the request waited for the client's rate limit or a free slot before it
+    /// was sent, see [`limits`](crate::limits)
+    pub queued: Duration,
+    /// Size of the decoded body in bytes
+    pub body_size: usize,
+    /// Size of the body as received, if it was compressed
+    pub compressed_size: Option<usize>,
+    /// All response headers (name, value)
+    pub headers: Vec<(String, String)>,
+}
+
+impl ResponseMetadata {
+    pub(crate) fn new(
+        status: u16,
+        headers: Vec<(String, String)>,
+        body_size: usize,
+        compressed_size: Option<usize>,
+        elapsed: Duration,
+    ) -> Self {
+        let mut metadata = Self {
+            status,
+            last_modified: None,
+            cache_control: None,
+            content_encoding: None,
+            elapsed,
+            queued: Duration::ZERO,
+            body_size,
+            compressed_size,
+            headers,
+        };
+        metadata.last_modified = metadata
+            .header("last-modified")
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .map(|time| time.with_timezone(&Utc));
+        metadata.cache_control = metadata.header("cache-control").map(str::to_string);
+        metadata.content_encoding = metadata.header("content-encoding").map(str::to_string);
+        metadata
+    }
+
+    /// Value of the first header with the given name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The `max-age` directive of the `Cache-Control` header
+    pub fn max_age(&self) -> Option<Duration> {
+        self.cache_control
+            .as_deref()?
+            .split(',')
+            .find_map(|directive| directive.trim().strip_prefix("max-age="))
+            .and_then(|seconds| seconds.trim_matches('"').parse().ok())
+            .map(Duration::from_secs)
+    }
+
+    /// Whether the response was served by an HTTP cache
+    ///
+    /// This is the case if a cache in front of the server added a non-zero `Age`
+    /// header, or an `X-Cache` header reporting a hit.
+    pub fn is_cache_hit(&self) -> bool {
+        self.header("age")
+            .and_then(|age| age.trim().parse::<u64>().ok())
+            .is_some_and(|age| age > 0)
+            || self
+                .header("x-cache")
+                .is_some_and(|value| value.to_ascii_lowercase().contains("hit"))
+    }
+}
+
+/// Deserialized document together with the metadata of its response
+#[derive(Debug, Clone)]
+pub struct WithMetadata<T> {
+    /// The deserialized document
+    pub document: T,
+    /// Status, caching headers, timing and size of the response
+    pub metadata: ResponseMetadata,
+}
+
+/// Request that an error occurred for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    /// Absolute URL including the query string
+    pub url: String,
+    /// Endpoint path, e.g. `/summary`
+    pub endpoint: String,
+    /// Query parameters of the request
+    pub query: QueryParameters,
+}
+
+/// Broad class of a [`ClientError`], e.g. for labelling error metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorKind {
+    /// The request timed out, or the server answered 408 or 504
+    Timeout,
+    /// The request could not be sent or the response not received
+    Connection,
+    /// The server answered 429 Too Many Requests
+    RateLimited,
+    /// The server answered another error status
+    Status,
+    /// The document did not match the expected type
+    Deserialization,
+    /// The compressed body could not be decoded
+    Decoding,
+    /// The URL or query parameters were rejected before sending
+    InvalidRequest,
+    /// Any other error
+    Other,
+}
+
+impl ErrorKind {
+    /// Label for the kind, e.g. `rate_limited`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Connection => "connection",
+            ErrorKind::RateLimited => "rate_limited",
+            ErrorKind::Status => "status",
+            ErrorKind::Deserialization => "deserialization",
+            ErrorKind::Decoding => "decoding",
+            ErrorKind::InvalidRequest => "invalid_request",
+            ErrorKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 /// Custom error type for API client operations
+///
+/// Errors for a request that was sent carry a [`RequestContext`]; the classifier
+/// methods such as [`is_retryable`](Self::is_retryable) tell transient failures from
+/// permanent ones without matching on variants.
 #[derive(Debug)]
 pub enum ClientError {
     /// HTTP request failed (network issues, DNS, etc.)
-    Request(reqwest::Error),
+    Request {
+        error: reqwest::Error,
+        context: Option<Box<RequestContext>>,
+    },
+    /// A custom transport failed to get a response
+    Transport {
+        error: Box<dyn std::error::Error + Send + Sync>,
+        context: Option<Box<RequestContext>>,
+    },
     /// HTTP response returned an error status code
-    StatusCode(u16, String),
+    StatusCode {
+        status: u16,
+        /// Response body, decoded lossily as UTF-8
+        body: String,
+        /// Response headers (name, value)
+        headers: Vec<(String, String)>,
+        context: Option<Box<RequestContext>>,
+    },
     /// JSON deserialization failed
-    Deserialization(String),
+    Deserialization {
+        /// Error message from `serde_json`
+        message: String,
+        /// Path of the value that failed, e.g. `relays[3].or_addresses`
+        path: String,
+        /// Line of the error in the body, starting at 1, or 0 if unknown
+        line: usize,
+        /// Column of the error in the body, starting at 1, or 0 if unknown
+        column: usize,
+        /// Excerpt of the body around the error, at most [`BODY_SNIPPET_LEN`] characters
+        snippet: String,
+        context: Option<Box<RequestContext>>,
+    },
+    /// The compressed response body could not be decoded
+    Decoding {
+        message: String,
+        context: Option<Box<RequestContext>>,
+    },
     /// Invalid URL construction
     UrlConstruction(String),
+    /// Query parameters failed validation and the request was not sent
+    InvalidParameters(ValidationReport),
     /// Generic client error with message
     Other(String),
 }
 
+impl ClientError {
+    /// Wrap an error from a custom transport
+    pub fn transport<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> Self {
+        ClientError::Transport {
+            error: error.into(),
+            context: None,
+        }
+    }
+
+    /// Request the error occurred for, if it was sent
+    pub fn context(&self) -> Option<&RequestContext> {
+        match self {
+            ClientError::Request { context, .. }
+            | ClientError::Transport { context, .. }
+            | ClientError::StatusCode { context, .. }
+            | ClientError::Deserialization { context, .. }
+            | ClientError::Decoding { context, .. } => context.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// URL of the request the error occurred for
+    pub fn url(&self) -> Option<&str> {
+        self.context().map(|context| context.url.as_str())
+    }
+
+    /// HTTP status of an error response
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::StatusCode { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the request timed out, or the server answered 408 or 504
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            ClientError::Request { error, .. } => error.is_timeout(),
+            ClientError::Transport { error, .. } => error_chain(error.as_ref()).any(|e| {
+                e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+            }),
+            ClientError::StatusCode { status, .. } => matches!(status, 408 | 504),
+            _ => false,
+        }
+    }
+
+    /// Broad class of the error
+    pub fn kind(&self) -> ErrorKind {
+        if self.is_timeout() {
+            return ErrorKind::Timeout;
+        }
+        match self {
+            ClientError::Request { .. } | ClientError::Transport { .. } => ErrorKind::Connection,
+            ClientError::StatusCode { status: 429, .. } => ErrorKind::RateLimited,
+            ClientError::StatusCode { .. } => ErrorKind::Status,
+            ClientError::Deserialization { .. } => ErrorKind::Deserialization,
+            ClientError::Decoding { .. } => ErrorKind::Decoding,
+            ClientError::UrlConstruction(_) | ClientError::InvalidParameters(_) => {
+                ErrorKind::InvalidRequest
+            }
+            ClientError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the server answered 404 Not Found
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404)
+    }
+
+    /// Whether the server answered 429 Too Many Requests
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(429)
+    }
+
+    /// Whether sending the same request again may succeed
+    ///
+    /// This is the case for connection failures, timeouts, rate limiting and the
+    /// server errors 500, 502, 503 and 504. Error responses to invalid requests,
+    /// malformed documents and parameters rejected by validation are permanent.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Request { error, .. } => {
+                error.is_timeout() || error.is_connect() || error.is_request() || error.is_body()
+            }
+            ClientError::Transport { .. } => true,
+            ClientError::StatusCode { status, .. } => {
+                matches!(status, 408 | 429 | 500 | 502 | 503 | 504)
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay requested by the server's `Retry-After` header, if given in seconds
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ClientError::StatusCode { headers, .. } => headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("retry-after"))
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .map(Duration::from_secs),
+            _ => None,
+        }
+    }
+
+    /// Attach the request to errors that occurred while sending or parsing it
+    pub(crate) fn with_context(mut self, request: RequestContext) -> Self {
+        if let ClientError::Request { context, .. }
+        | ClientError::Transport { context, .. }
+        | ClientError::StatusCode { context, .. }
+        | ClientError::Deserialization { context, .. }
+        | ClientError::Decoding { context, .. } = &mut self
+        {
+            *context = Some(Box::new(request));
+        }
+        self
+    }
+}
+
+fn error_chain<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(error), |e| e.source())
+}
+
 impl fmt::Display for ClientError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ClientError::Request(e) => write!(f, "HTTP request failed: {}", e),
-            ClientError::StatusCode(code, msg) => write!(f, "HTTP {}: {}", code, msg),
-            ClientError::Deserialization(msg) => write!(f, "JSON deserialization failed: {}", msg),
-            ClientError::UrlConstruction(msg) => write!(f, "URL construction error: {}", msg),
-            ClientError::Other(msg) => write!(f, "Client error: {}", msg),
+            ClientError::Request { error, .. } => write!(f, "HTTP request failed: {}", error)?,
+            ClientError::Transport { error, .. } => write!(f, "Transport failed: {}", error)?,
+            ClientError::StatusCode { status, body, .. } => write!(f, "HTTP {}: {}", status, body)?,
+            ClientError::Deserialization { message, path, .. } => {
+                write!(f, "JSON deserialization failed at {}: {}", path, message)?
+            }
+            ClientError::Decoding { message, .. } => {
+                write!(f, "Response decoding failed: {}", message)?
+            }
+            ClientError::UrlConstruction(msg) => write!(f, "URL construction error: {}", msg)?,
+            ClientError::InvalidParameters(report) => {
+                write!(f, "Invalid query parameters: {}", report)?
+            }
+            ClientError::Other(msg) => write!(f, "Client error: {}", msg)?,
         }
+        if let Some(url) = self.url() {
+            write!(f, " (GET {})", url)?;
+        }
+        Ok(())
     }
 }
 
 impl std::error::Error for ClientError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            ClientError::Request(e) => Some(e),
+            ClientError::Request { error, .. } => Some(error),
+            ClientError::Transport { error, .. } => Some(error.as_ref()),
+            ClientError::InvalidParameters(report) => Some(report),
             _ => None,
         }
     }
@@ -47,23 +381,35 @@ impl std::error::Error for ClientError {
 
 impl From<reqwest::Error> for ClientError {
     fn from(error: reqwest::Error) -> Self {
-        ClientError::Request(error)
+        ClientError::Request {
+            error,
+            context: None,
+        }
     }
 }
 
 impl From<serde_json::Error> for ClientError {
     fn from(error: serde_json::Error) -> Self {
-        ClientError::Deserialization(error.to_string())
+        deserialization_error(error, ".".to_string(), &[])
     }
 }
 
 /// Main client for the Onionoo API
-#[derive(Debug)]
+///
+/// Cloning a client is cheap: clones share the transport, and with it the connection
+/// pool, as well as the rate limit and concurrency cap.
+#[derive(Debug, Clone)]
 pub struct Client {
-    /// Internal HTTP client for making requests
-    http_client: HttpClient,
+    /// Transport used to send requests
+    transport: Arc<dyn Transport>,
+    /// Rate limit and concurrency cap shared by all clones
+    limiter: Arc<Limiter>,
+    /// Receiver of request metrics
+    metrics: Option<Arc<dyn MetricsHook>>,
     /// Base URL for the Onionoo API
-    base_url: &'static str,
+    base_url: Arc<str>,
+    /// Whether validation warnings also reject requests
+    strict: bool,
 }
 
 impl Client {
@@ -76,10 +422,7 @@ impl Client {
     /// let client = Client::new();
     /// ```
     pub fn new() -> Self {
-        Self {
-            http_client: HttpClient::new(),
-            base_url: "https://onionoo.torproject.org",
-        }
+        Self::new_with_base_url("https://onionoo.torproject.org")
     }
 
     /// Create a new client with a custom base URL
@@ -92,10 +435,145 @@ impl Client {
     /// use onionoo::Client;
     /// let client = Client::new_with_base_url("https://custom-onionoo.example.com");
     /// ```
-    pub fn new_with_base_url(base_url: &'static str) -> Self {
+    pub fn new_with_base_url(base_url: impl Into<Arc<str>>) -> Self {
         Self {
-            http_client: HttpClient::new(),
-            base_url,
+            transport: Arc::new(ReqwestTransport::new()),
+            limiter: Arc::default(),
+            metrics: None,
+            base_url: base_url.into(),
+            strict: false,
+        }
+    }
+
+    /// Send requests through a custom transport instead of `reqwest`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::{Client, transport::FixtureTransport};
+    /// let transport = FixtureTransport::new().respond("/summary", 200, "{}");
+    /// let client = Client::new().with_transport(transport);
+    /// ```
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Reject requests with validation warnings, not only errors
+    ///
+    /// See [`QueryParameters::validate`] for the checks applied by
+    /// [`get_endpoint`](Self::get_endpoint).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::Client;
+    /// let client = Client::new().strict(true);
+    /// ```
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Limit the rate at which requests are sent
+    ///
+    /// Requests over the limit wait until the token bucket has refilled. The limit is
+    /// shared by all clones of the returned client, together with the
+    /// [`max_in_flight`](Self::max_in_flight) cap, but not with clones made earlier.
+    /// Waiting requires a Tokio runtime with the time driver enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, limits::RateLimit};
+    /// let client = Client::new().rate_limit(RateLimit::per_second(2).burst(4));
+    /// ```
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.limiter = Arc::new(Limiter::new(Some(rate_limit), self.limiter.max_in_flight()));
+        self
+    }
+
+    /// Limit the number of requests in flight at once
+    ///
+    /// Further requests wait for a running one to finish. Like
+    /// [`rate_limit`](Self::rate_limit), the cap is shared by all clones of the
+    /// returned client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_in_flight` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::Client;
+    /// let client = Client::new().max_in_flight(4);
+    /// ```
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        assert!(max_in_flight > 0, "max_in_flight must be at least 1");
+        self.limiter = Arc::new(Limiter::new(self.limiter.rate_limit(), Some(max_in_flight)));
+        self
+    }
+
+    /// Current state of the rate limit and concurrency cap
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::Client;
+    /// let client = Client::new().max_in_flight(4);
+    /// let stats = client.limit_stats();
+    /// if stats.is_saturated() {
+    ///     println!("{} requests waiting", stats.waiting);
+    /// }
+    /// ```
+    pub fn limit_stats(&self) -> LimitStats {
+        self.limiter.stats()
+    }
+
+    /// Report every request to a metrics hook, see [`metrics`](crate::metrics)
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, metrics::Recorder};
+    /// let recorder = Recorder::new();
+    /// let client = Client::new().with_metrics(recorder.clone());
+    /// ```
+    pub fn with_metrics<M: MetricsHook + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Report that the request that failed with `error` is about to be retried
+    ///
+    /// The client does not retry requests itself; retry loops call this so retries
+    /// show up in the metrics hook and, with the `tracing` feature, as an event.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, QueryParameters, endpoints};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new();
+    /// let response = match endpoints::summary(&client, QueryParameters::new()).await {
+    ///     Err(e) if e.is_retryable() => {
+    ///         client.report_retry(&e);
+    ///         endpoints::summary(&client, QueryParameters::new()).await?
+    ///     }
+    ///     result => result?,
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn report_retry(&self, error: &ClientError) {
+        let endpoint = error
+            .context()
+            .map_or("", |context| context.endpoint.as_str());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(endpoint, %error, "retrying request");
+        if let Some(metrics) = &self.metrics {
+            metrics.on_retry(endpoint, error);
         }
     }
 
@@ -135,40 +613,95 @@ impl Client {
     where
         T: DeserializeOwned,
     {
-        let endpoint = if endpoint.starts_with('/') {
-            endpoint
-        } else {
-            return Err(ClientError::UrlConstruction(
-                "Endpoint must start with '/'".to_string(),
-            ));
-        };
-
-        let mut url = format!("{}{}", self.base_url, endpoint);
-
-        let query_string = params.to_query_string();
-        if !query_string.is_empty() {
-            url.push_str(&query_string);
-        }
-
-        let response = self.http_client.get(&url).send().await?;
-
-        if !response.status().is_success() {
-            let status_code = response.status().as_u16();
-            let error_message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ClientError::StatusCode(status_code, error_message));
-        }
+        self.get_with_metadata(endpoint, params)
+            .await
+            .map(|response| response.document)
+    }
 
-        let response_text = response.text().await?;
-        let deserialized: T = serde_json::from_str(&response_text)?;
+    /// Make a generic GET request and return the document with the response metadata
+    ///
+    /// Like [`get`](Self::get), but also returns the status, caching headers, elapsed
+    /// time and body size of the response, e.g. for freshness checks.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, QueryParameters, models::SummaryResponse};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new();
+    /// let response = client
+    ///     .get_with_metadata::<SummaryResponse>("/summary", QueryParameters::new())
+    ///     .await?;
+    /// println!(
+    ///     "{} bytes in {:?}, last modified {:?}",
+    ///     response.metadata.body_size,
+    ///     response.metadata.elapsed,
+    ///     response.metadata.last_modified
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_with_metadata<T>(
+        &self,
+        endpoint: &str,
+        params: QueryParameters,
+    ) -> Result<WithMetadata<T>, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        self.request(None, endpoint, params).await
+    }
 
-        Ok(deserialized)
+    /// Validates the parameters against `validate_for`, if given, and sends the request
+    ///
+    /// Every outcome, including invalid parameters, is reported to the metrics hook.
+    async fn request<T>(
+        &self,
+        validate_for: Option<Endpoint>,
+        endpoint: &str,
+        params: QueryParameters,
+    ) -> Result<WithMetadata<T>, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        let observer = Observer::start(self.metrics.as_deref(), endpoint, &params);
+        let result = observer
+            .instrument(async {
+                if let Some(validate_for) = validate_for {
+                    validate(validate_for, &params, self.strict)?;
+                }
+                let url = request_url(&self.base_url, endpoint, &params)?;
+                let context = RequestContext {
+                    url: url.clone(),
+                    endpoint: endpoint.to_string(),
+                    query: params,
+                };
+                let permit = self.limiter.acquire().await;
+                let started = Instant::now();
+                self.transport
+                    .send(new_request(url))
+                    .await
+                    .and_then(|response| parse_with_metadata(response, started.elapsed()))
+                    .map(|mut response: WithMetadata<T>| {
+                        response.metadata.queued = permit.queued();
+                        response
+                    })
+                    .map_err(|e| e.with_context(context))
+            })
+            .await;
+        observer.finish(&result);
+        result
     }
 
     /// Make a GET request to a specific endpoint using the Endpoint enum
     ///
+    /// The parameters are validated against the endpoint first. Requests with
+    /// validation errors, or in strict mode with any issues, fail with
+    /// [`ClientError::InvalidParameters`] without being sent.
+    ///
+    /// The response type is not checked against the endpoint; use
+    /// [`fetch`](Self::fetch) for that.
+    ///
     /// # Type Parameters
     ///
     /// * `T` - The type to deserialize the response into. Must implement `serde::de::DeserializeOwned`.
@@ -201,23 +734,144 @@ impl Client {
     /// ```
     pub async fn get_endpoint<T>(
         &self,
-        endpoint: crate::endpoints::Endpoint,
+        endpoint: Endpoint,
         params: QueryParameters,
     ) -> Result<T, ClientError>
     where
         T: DeserializeOwned,
     {
-        self.get(endpoint.path(), params).await
+        self.request(Some(endpoint), endpoint.path(), params)
+            .await
+            .map(|response| response.document)
+    }
+
+    /// Make a GET request to a typed endpoint
+    ///
+    /// The response is deserialized into the endpoint's document type, see
+    /// [`OnionooEndpoint`]. Parameters are validated like in
+    /// [`get_endpoint`](Self::get_endpoint).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, QueryParameters, endpoints::Details, selection::TypeValue};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new();
+    /// let params = QueryParameters::new()
+    ///     .type_param(TypeValue::Relay)
+    ///     .limit(10);
+    ///
+    /// let response = client.fetch::<Details>(params).await?;
+    /// println!("Found {} relays", response.relays.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch<E: OnionooEndpoint>(
+        &self,
+        params: QueryParameters,
+    ) -> Result<E::Response, ClientError> {
+        self.get_endpoint(E::ENDPOINT, params).await
+    }
+
+    /// Make a GET request to a typed endpoint and return the response metadata too
+    ///
+    /// See [`fetch`](Self::fetch) and [`get_with_metadata`](Self::get_with_metadata).
+    pub async fn fetch_with_metadata<E: OnionooEndpoint>(
+        &self,
+        params: QueryParameters,
+    ) -> Result<WithMetadata<E::Response>, ClientError> {
+        self.request(Some(E::ENDPOINT), E::PATH, params).await
+    }
+
+    /// Stream the relays and bridges of a typed endpoint's document one at a time
+    ///
+    /// The body is parsed as it arrives and each relay or bridge is passed to
+    /// `on_entry` as soon as it is complete, so a full document never has to be held
+    /// in memory, see [`stream`](crate::stream). The remaining top-level fields are
+    /// returned with the response metadata once the document has been read.
+    /// Parameters are validated like in [`get_endpoint`](Self::get_endpoint).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, QueryParameters, endpoints::Details, stream::Entry};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new();
+    /// let mut bandwidth = 0;
+    ///
+    /// let response = client
+    ///     .stream::<Details, _>(QueryParameters::new(), |entry| {
+    ///         if let Entry::Relay(relay) = entry {
+    ///             bandwidth += relay.observed_bandwidth.unwrap_or(0);
+    ///         }
+    ///     })
+    ///     .await?;
+    /// println!(
+    ///     "{} bytes/s observed as of {}",
+    ///     bandwidth, response.document.relays_published
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stream<E, F>(
+        &self,
+        params: QueryParameters,
+        mut on_entry: F,
+    ) -> Result<WithMetadata<DocumentHeader>, ClientError>
+    where
+        E: OnionooEndpoint,
+        F: FnMut(Entry<E::Relay, E::Bridge>),
+    {
+        let observer = Observer::start(self.metrics.as_deref(), E::PATH, &params);
+        let result = async {
+            validate(E::ENDPOINT, &params, self.strict)?;
+            let url = request_url(&self.base_url, E::PATH, &params)?;
+            let context = RequestContext {
+                url: url.clone(),
+                endpoint: E::PATH.to_string(),
+                query: params,
+            };
+            async {
+                let permit = self.limiter.acquire().await;
+                let started = Instant::now();
+                let mut response = self.transport.send_streaming(new_request(url)).await?;
+                if !(200..300).contains(&response.status) {
+                    return Err(status_error(&decode(response.collect().await?)?.0));
+                }
+                let mut body = StreamedBody::new(&response.headers)?;
+                while let Some(chunk) = response.body.next_chunk().await? {
+                    body.feed(chunk, &mut on_entry)?;
+                }
+                let mut response = body.finish(
+                    &mut on_entry,
+                    response.status,
+                    response.headers,
+                    started.elapsed(),
+                )?;
+                response.metadata.queued = permit.queued();
+                Ok(response)
+            }
+            .await
+            .map_err(|e: ClientError| e.with_context(context))
+        };
+        let result = observer.instrument(result).await;
+        observer.finish(&result);
+        result
     }
 
     /// Get the base URL being used by this client
-    pub fn base_url(&self) -> &'static str {
-        self.base_url
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Whether the client is in strict validation mode
+    pub fn is_strict(&self) -> bool {
+        self.strict
     }
 
     /// Check if the client is configured to use the default Onionoo URL
     pub fn is_default(&self) -> bool {
-        self.base_url == "https://onionoo.torproject.org"
+        &*self.base_url == "https://onionoo.torproject.org"
     }
 }
 
@@ -227,13 +881,238 @@ impl Default for Client {
     }
 }
 
+/// Builds the URL for an endpoint path and query parameters
+pub(crate) fn request_url(
+    base_url: &str,
+    endpoint: &str,
+    params: &QueryParameters,
+) -> Result<String, ClientError> {
+    if !endpoint.starts_with('/') {
+        return Err(ClientError::UrlConstruction(
+            "Endpoint must start with '/'".to_string(),
+        ));
+    }
+    Ok(format!(
+        "{}{}{}",
+        base_url,
+        endpoint,
+        params.to_query_string()
+    ))
+}
+
+/// Rejects parameters with validation errors, or any issues in strict mode
+pub(crate) fn validate(
+    endpoint: Endpoint,
+    params: &QueryParameters,
+    strict: bool,
+) -> Result<(), ClientError> {
+    params
+        .validate(&endpoint)
+        .check(strict)
+        .map_err(ClientError::InvalidParameters)
+}
+
+/// Maps an error status to [`ClientError::StatusCode`] and deserializes the body otherwise
+pub(crate) fn parse_response<T>(response: &Response) -> Result<T, ClientError>
+where
+    T: DeserializeOwned,
+{
+    if !response.is_success() {
+        return Err(status_error(response));
+    }
+    let mut deserializer = serde_json::Deserializer::from_slice(&response.body);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        deserialization_error(e.into_inner(), path, &response.body)
+    })?;
+    deserializer
+        .end()
+        .map_err(|e| deserialization_error(e, ".".to_string(), &response.body))?;
+    Ok(value)
+}
+
+pub(crate) fn status_error(response: &Response) -> ClientError {
+    ClientError::StatusCode {
+        status: response.status,
+        body: String::from_utf8_lossy(&response.body).into_owned(),
+        headers: response.headers.clone(),
+        context: None,
+    }
+}
+
+/// Deserializes a response like [`parse_response`] and records its metadata
+///
+/// Compressed bodies are decoded first.
+pub(crate) fn parse_with_metadata<T>(
+    response: Response,
+    elapsed: Duration,
+) -> Result<WithMetadata<T>, ClientError>
+where
+    T: DeserializeOwned,
+{
+    let (response, compressed_size) = decode(response)?;
+    Ok(WithMetadata {
+        document: parse_response(&response)?,
+        metadata: ResponseMetadata::new(
+            response.status,
+            response.headers,
+            response.body.len(),
+            compressed_size,
+            elapsed,
+        ),
+    })
+}
+
+/// Request for a URL, accepting the encodings enabled by cargo features
+pub(crate) fn new_request(url: String) -> Request {
+    match ContentEncoding::accept_encoding() {
+        Some(accepted) => Request::new(url).header("Accept-Encoding", accepted),
+        None => Request::new(url),
+    }
+}
+
+fn decoding_error(message: String) -> ClientError {
+    ClientError::Decoding {
+        message,
+        context: None,
+    }
+}
+
+/// Decodes a compressed body and returns the response with its compressed size
+pub(crate) fn decode(response: Response) -> Result<(Response, Option<usize>), ClientError> {
+    let mut decoder =
+        Decoder::new(response.header_value("content-encoding")).map_err(decoding_error)?;
+    if decoder.is_identity() {
+        return Ok((response, None));
+    }
+    let compressed_size = response.body.len();
+    let mut body = decoder
+        .decode(response.body)
+        .map_err(|e| decoding_error(e.to_string()))?;
+    body.extend(
+        decoder
+            .finish()
+            .map_err(|e| decoding_error(e.to_string()))?,
+    );
+    let response = Response {
+        status: response.status,
+        headers: response.headers,
+        body,
+    };
+    Ok((response, Some(compressed_size)))
+}
+
+/// Streamed body being decoded and parsed, with its byte counts
+pub(crate) struct StreamedBody<T, U> {
+    decoder: Decoder,
+    parser: DocumentParser<T, U>,
+    body_size: usize,
+    compressed_size: usize,
+}
+
+impl<T, U> StreamedBody<T, U>
+where
+    T: DeserializeOwned,
+    U: DeserializeOwned,
+{
+    pub fn new(headers: &[(String, String)]) -> Result<Self, ClientError> {
+        Ok(Self {
+            decoder: Decoder::new(find_header(headers, "content-encoding"))
+                .map_err(decoding_error)?,
+            parser: DocumentParser::new(),
+            body_size: 0,
+            compressed_size: 0,
+        })
+    }
+
+    /// Decodes and parses the next chunk as received
+    pub fn feed<F>(&mut self, chunk: Vec<u8>, on_entry: &mut F) -> Result<(), ClientError>
+    where
+        F: FnMut(Entry<T, U>),
+    {
+        self.compressed_size += chunk.len();
+        let chunk = self
+            .decoder
+            .decode(chunk)
+            .map_err(|e| decoding_error(e.to_string()))?;
+        self.body_size += chunk.len();
+        self.parser.feed(&chunk, on_entry)
+    }
+
+    /// Parses the rest of the decoded body and checks that the document was complete
+    pub fn finish<F>(
+        mut self,
+        on_entry: &mut F,
+        status: u16,
+        headers: Vec<(String, String)>,
+        elapsed: Duration,
+    ) -> Result<WithMetadata<DocumentHeader>, ClientError>
+    where
+        F: FnMut(Entry<T, U>),
+    {
+        let compressed = !self.decoder.is_identity();
+        let rest = self
+            .decoder
+            .finish()
+            .map_err(|e| decoding_error(e.to_string()))?;
+        self.body_size += rest.len();
+        self.parser.feed(&rest, on_entry)?;
+        Ok(WithMetadata {
+            document: self.parser.finish()?,
+            metadata: ResponseMetadata::new(
+                status,
+                headers,
+                self.body_size,
+                compressed.then_some(self.compressed_size),
+                elapsed,
+            ),
+        })
+    }
+}
+
+fn deserialization_error(error: serde_json::Error, path: String, body: &[u8]) -> ClientError {
+    ClientError::Deserialization {
+        message: error.to_string(),
+        path,
+        line: error.line(),
+        column: error.column(),
+        snippet: body_snippet(body, error.line(), error.column()),
+        context: None,
+    }
+}
+
+/// Excerpt of the body centred on a line and column, marking cut ends with `…`
+pub(crate) fn body_snippet(body: &[u8], line: usize, column: usize) -> String {
+    let offset = body
+        .split(|&b| b == b'\n')
+        .take(line.saturating_sub(1))
+        .map(|l| l.len() + 1)
+        .sum::<usize>()
+        + column.saturating_sub(1);
+    let offset = offset.min(body.len());
+    let start = offset.saturating_sub(BODY_SNIPPET_LEN / 2);
+    // Four bytes per character is the UTF-8 worst case
+    let end = body.len().min(start + BODY_SNIPPET_LEN * 4);
+    let text = String::from_utf8_lossy(&body[start..end]);
+    let text = text.trim_start_matches('\u{FFFD}');
+    let mut snippet: String = text.chars().take(BODY_SNIPPET_LEN).collect();
+    let cut_end = start + snippet.len() < body.len();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if cut_end {
+        snippet.push('…');
+    }
+    snippet
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parameters::{
-        QueryParameters,
-        selection::{BoolValue, TypeValue},
-    };
+    use crate::endpoints::{Details, Summary, Uptime};
+    use crate::models::SummaryResponse;
+    use crate::parameters::{QueryParameters, selection::TypeValue};
+    use crate::transport::FixtureTransport;
 
     #[test]
     fn test_client_default_creation() {
@@ -248,20 +1127,273 @@ mod tests {
         let client = Client::new_with_base_url(custom_url);
         assert_eq!(client.base_url(), custom_url);
         assert!(!client.is_default());
+
+        let owned = Client::new_with_base_url(format!("http://127.0.0.1:{}", 9030));
+        assert_eq!(owned.base_url(), "http://127.0.0.1:9030");
+        assert_eq!(owned.clone().base_url(), owned.base_url());
     }
 
-    #[test]
-    fn test_url_construction() {
-        let client = Client::new();
+    const SUMMARY: &str = r#"{
+        "version": "8.0",
+        "relays_published": "2024-01-01 00:00:00",
+        "relays": [{"n": "moria1", "f": "9695DFC35FFEB861329B9F1AB04C46397020CE31", "a": ["128.31.0.39"], "r": true}],
+        "bridges_published": "2024-01-01 00:00:00",
+        "bridges": []
+    }"#;
+
+    #[tokio::test]
+    async fn test_url_construction() {
+        let transport = FixtureTransport::new().respond("/summary", 200, SUMMARY);
+        let client = Client::new_with_base_url("https://onionoo.example.com")
+            .with_transport(transport.clone());
         let params = QueryParameters::new()
             .type_param(TypeValue::Relay)
             .limit(10);
 
-        // The actual URL construction happens in the get() method
-        // We can test that invalid endpoints are rejected
-        // Note: This test would need to be async in a real scenario
-        // For now, we just test the constructor methods work
-        assert_eq!(client.base_url(), "https://onionoo.torproject.org");
+        let response: SummaryResponse = client.get("/summary", params.clone()).await.unwrap();
+        assert_eq!(response.relays[0].n, "moria1");
+        let response = client
+            .fetch::<Summary>(QueryParameters::new())
+            .await
+            .unwrap();
+        assert_eq!(response.relays.len(), 1);
+
+        let result: Result<SummaryResponse, _> = client.get("summary", params).await;
+        assert!(matches!(result, Err(ClientError::UrlConstruction(_))));
+
+        let urls: Vec<_> = transport.requests().into_iter().map(|r| r.url).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://onionoo.example.com/summary?limit=10&type=relay",
+                "https://onionoo.example.com/summary",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_error_mapping() {
+        let transport = FixtureTransport::new()
+            .respond("/summary?limit=1", 400, "Invalid parameter")
+            .respond("/summary", 200, "{\"version\":")
+            .respond("/details", 200, SUMMARY);
+        let client = Client::new().with_transport(transport);
+
+        let result = client
+            .fetch::<Summary>(QueryParameters::new().limit(1))
+            .await;
+        assert!(matches!(
+            result,
+            Err(ClientError::StatusCode { status: 400, ref body, .. }) if body == "Invalid parameter"
+        ));
+        let result = client.fetch::<Summary>(QueryParameters::new()).await;
+        assert!(matches!(result, Err(ClientError::Deserialization { .. })));
+        let result = client.fetch::<Details>(QueryParameters::new()).await;
+        assert!(matches!(result, Err(ClientError::Deserialization { .. })));
+        let result = client.fetch::<Uptime>(QueryParameters::new()).await;
+        assert!(matches!(
+            result,
+            Err(ClientError::StatusCode { status: 404, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_error_context() {
+        let body = SUMMARY.replace(r#""r": true"#, r#""r": "yes""#);
+        let transport = FixtureTransport::new()
+            .respond("/summary", 200, body)
+            .respond_with(
+                "/details",
+                Response::new(429, "Slow down").header("Retry-After", "30"),
+            );
+        let client =
+            Client::new_with_base_url("https://onionoo.example.com").with_transport(transport);
+
+        let error = client
+            .fetch::<Summary>(QueryParameters::new().search("moria"))
+            .await
+            .unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(
+            context.url,
+            "https://onionoo.example.com/summary?search=moria"
+        );
+        assert_eq!(context.endpoint, "/summary");
+        assert_eq!(context.query, QueryParameters::new().search("moria"));
+        let ClientError::Deserialization {
+            path,
+            line,
+            snippet,
+            ..
+        } = &error
+        else {
+            panic!("unexpected error: {error}");
+        };
+        assert_eq!(path, "relays[0].r");
+        assert_eq!(*line, 4);
+        assert!(snippet.contains(r#""r": "yes""#));
+        assert!(!error.is_retryable());
+        assert!(
+            error
+                .to_string()
+                .ends_with("(GET https://onionoo.example.com/summary?search=moria)")
+        );
+
+        let error = client
+            .fetch::<Details>(QueryParameters::new())
+            .await
+            .unwrap_err();
+        assert!(error.is_rate_limited());
+        assert!(error.is_retryable());
+        assert!(!error.is_not_found());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(30)));
+
+        let error = client
+            .fetch::<Uptime>(QueryParameters::new())
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found());
+        assert!(!error.is_retryable());
+        assert_eq!(error.url(), Some("https://onionoo.example.com/uptime"));
+
+        let error = client
+            .fetch::<Summary>(QueryParameters::new().as_param("ASX"))
+            .await
+            .unwrap_err();
+        assert!(error.context().is_none());
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_response_metadata() {
+        let transport = FixtureTransport::new().respond_with(
+            "/summary",
+            Response::new(200, SUMMARY)
+                .header("Last-Modified", "Mon, 01 Jan 2024 01:00:00 GMT")
+                .header("Cache-Control", "public, max-age=300")
+                .header("Content-Type", "application/json"),
+        );
+        let client = Client::new().with_transport(transport);
+
+        let response = client
+            .fetch_with_metadata::<Summary>(QueryParameters::new())
+            .await
+            .unwrap();
+        assert_eq!(response.document.relays[0].n, "moria1");
+        let metadata = response.metadata;
+        assert_eq!(metadata.status, 200);
+        assert_eq!(
+            metadata.last_modified.unwrap().to_rfc3339(),
+            "2024-01-01T01:00:00+00:00"
+        );
+        assert_eq!(
+            metadata.cache_control.as_deref(),
+            Some("public, max-age=300")
+        );
+        assert_eq!(metadata.max_age(), Some(Duration::from_secs(300)));
+        assert_eq!(metadata.content_encoding, None);
+        assert_eq!(metadata.body_size, SUMMARY.len());
+        assert_eq!(metadata.header("content-type"), Some("application/json"));
+
+        let result = client
+            .fetch_with_metadata::<Summary>(QueryParameters::new().as_param("ASX"))
+            .await;
+        assert!(matches!(result, Err(ClientError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let transport = FixtureTransport::new().respond("/summary", 200, SUMMARY);
+        let client = Client::new().with_transport(transport);
+
+        let mut nicknames = Vec::new();
+        let response = client
+            .stream::<Summary, _>(QueryParameters::new(), |entry| {
+                if let Entry::Relay(relay) = entry {
+                    nicknames.push(relay.n);
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(nicknames, ["moria1"]);
+        assert_eq!(response.document.version, "8.0");
+        assert_eq!(response.metadata.body_size, SUMMARY.len());
+
+        let error = client
+            .stream::<Uptime, _>(QueryParameters::new(), |_| {})
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(error.context().unwrap().endpoint, "/uptime");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_compressed_response() {
+        let compressed =
+            crate::encoding::encode(ContentEncoding::Gzip, SUMMARY.as_bytes()).unwrap();
+        let transport = FixtureTransport::new()
+            .respond_with(
+                "/summary",
+                Response::new(200, compressed.clone()).header("Content-Encoding", "gzip"),
+            )
+            .respond_with(
+                "/details",
+                Response::new(200, SUMMARY).header("Content-Encoding", "gzip"),
+            );
+        let client = Client::new().with_transport(transport.clone());
+
+        let response = client
+            .fetch_with_metadata::<Summary>(QueryParameters::new())
+            .await
+            .unwrap();
+        assert_eq!(response.document.relays[0].n, "moria1");
+        assert_eq!(response.metadata.body_size, SUMMARY.len());
+        assert_eq!(response.metadata.compressed_size, Some(compressed.len()));
+        let accepted = transport.requests()[0]
+            .headers
+            .iter()
+            .find(|(name, _)| name == "Accept-Encoding")
+            .map(|(_, value)| value.clone());
+        assert_eq!(accepted, ContentEncoding::accept_encoding());
+
+        let error = client
+            .fetch::<Details>(QueryParameters::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ClientError::Decoding { .. }), "{error}");
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let status = |status| ClientError::StatusCode {
+            status,
+            body: String::new(),
+            headers: Vec::new(),
+            context: None,
+        };
+        assert!(status(503).is_retryable());
+        assert!(status(504).is_timeout());
+        assert!(!status(400).is_retryable());
+        assert!(!status(503).is_timeout());
+
+        let timeout = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let error = ClientError::transport(timeout);
+        assert!(error.is_timeout());
+        assert!(error.is_retryable());
+        assert!(!ClientError::transport("refused").is_timeout());
+    }
+
+    #[test]
+    fn test_body_snippet() {
+        assert_eq!(body_snippet(b"{\"a\": x}", 1, 7), "{\"a\": x}");
+
+        let body = format!("[{}x{}]", "1,".repeat(200), ",1".repeat(200));
+        let snippet = body_snippet(body.as_bytes(), 1, 402);
+        assert_eq!(snippet.chars().count(), BODY_SNIPPET_LEN + 2);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert_eq!(snippet.chars().nth(BODY_SNIPPET_LEN / 2 + 1), Some('x'));
     }
 
     #[test]
@@ -269,7 +1401,12 @@ mod tests {
         let error = ClientError::Other("test error".to_string());
         assert_eq!(error.to_string(), "Client error: test error");
 
-        let error = ClientError::StatusCode(404, "Not Found".to_string());
+        let error = ClientError::StatusCode {
+            status: 404,
+            body: "Not Found".to_string(),
+            headers: Vec::new(),
+            context: None,
+        };
         assert_eq!(error.to_string(), "HTTP 404: Not Found");
     }
 }
@@ -0,0 +1,316 @@
+//! Response Compression
+//!
+//! Onionoo serves compressed documents, and a full details document shrinks about
+//! tenfold. The client advertises the encodings enabled by the `gzip`, `deflate` and
+//! `brotli` cargo features in the `Accept-Encoding` header and decodes responses
+//! itself, chunk by chunk, so the compressed and uncompressed sizes can both be
+//! reported in [`ResponseMetadata`](crate::client::ResponseMetadata) and streamed
+//! documents are decoded as they arrive.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Content coding of a response body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    /// `gzip`, with the `gzip` feature
+    Gzip,
+    /// `deflate` (zlib), with the `deflate` feature
+    Deflate,
+    /// `br`, with the `brotli` feature
+    Brotli,
+}
+
+impl ContentEncoding {
+    /// All encodings, in order of preference
+    pub const ALL: [ContentEncoding; 3] = [
+        ContentEncoding::Brotli,
+        ContentEncoding::Gzip,
+        ContentEncoding::Deflate,
+    ];
+
+    /// Token used in the `Content-Encoding` and `Accept-Encoding` headers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Brotli => "br",
+        }
+    }
+
+    /// Whether the cargo feature for decoding this encoding is enabled
+    pub fn is_supported(&self) -> bool {
+        match self {
+            ContentEncoding::Gzip => cfg!(feature = "gzip"),
+            ContentEncoding::Deflate => cfg!(feature = "deflate"),
+            ContentEncoding::Brotli => cfg!(feature = "brotli"),
+        }
+    }
+
+    /// Encodings that can be decoded, in order of preference
+    pub fn supported() -> Vec<ContentEncoding> {
+        Self::ALL.into_iter().filter(Self::is_supported).collect()
+    }
+
+    /// Value of the `Accept-Encoding` header, or `None` if no encoding is supported
+    pub fn accept_encoding() -> Option<String> {
+        let supported = Self::supported();
+        (!supported.is_empty()).then(|| {
+            supported
+                .iter()
+                .map(ContentEncoding::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+}
+
+impl fmt::Display for ContentEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ContentEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Ok(ContentEncoding::Gzip),
+            "deflate" => Ok(ContentEncoding::Deflate),
+            "br" => Ok(ContentEncoding::Brotli),
+            other => Err(format!("unknown content encoding '{}'", other)),
+        }
+    }
+}
+
+/// Incremental decoder for a response body
+pub(crate) enum Decoder {
+    Identity,
+    #[cfg(feature = "gzip")]
+    Gzip(Box<flate2::write::GzDecoder<Vec<u8>>>),
+    #[cfg(feature = "deflate")]
+    Deflate(Inflate),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+impl Decoder {
+    /// Decoder for the value of a `Content-Encoding` header
+    pub fn new(content_encoding: Option<&str>) -> Result<Self, String> {
+        let encoding = match content_encoding.map(str::trim) {
+            None | Some("") => return Ok(Decoder::Identity),
+            Some(value) if value.eq_ignore_ascii_case("identity") => {
+                return Ok(Decoder::Identity);
+            }
+            Some(value) => value.parse::<ContentEncoding>()?,
+        };
+        match encoding {
+            #[cfg(feature = "gzip")]
+            ContentEncoding::Gzip => Ok(Decoder::Gzip(Box::new(flate2::write::GzDecoder::new(
+                Vec::new(),
+            )))),
+            #[cfg(feature = "deflate")]
+            ContentEncoding::Deflate => Ok(Decoder::Deflate(Inflate::default())),
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => Ok(Decoder::Brotli(Box::new(
+                brotli::DecompressorWriter::new(Vec::new(), 4096),
+            ))),
+            #[allow(unreachable_patterns)]
+            encoding => Err(format!(
+                "content encoding '{}' requires the '{}' feature",
+                encoding,
+                feature_name(encoding)
+            )),
+        }
+    }
+
+    /// Whether the body is decoded at all
+    pub fn is_identity(&self) -> bool {
+        matches!(self, Decoder::Identity)
+    }
+
+    /// Decodes the next chunk of the body and returns the output available so far
+    pub fn decode(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>> {
+        #[cfg(any(feature = "gzip", feature = "brotli"))]
+        use std::io::Write;
+
+        match self {
+            Decoder::Identity => Ok(chunk),
+            #[cfg(feature = "gzip")]
+            Decoder::Gzip(decoder) => {
+                decoder.write_all(&chunk)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            #[cfg(feature = "deflate")]
+            Decoder::Deflate(decoder) => decoder.decode(&chunk),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(decoder) => {
+                decoder.write_all(&chunk)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// Checks that the body was complete and returns the remaining output
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Decoder::Identity => Ok(Vec::new()),
+            #[cfg(feature = "gzip")]
+            Decoder::Gzip(decoder) => decoder.finish(),
+            #[cfg(feature = "deflate")]
+            Decoder::Deflate(decoder) => decoder.finish(),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(mut decoder) => {
+                decoder.close()?;
+                decoder.into_inner().map_err(|_| {
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated brotli stream")
+                })
+            }
+        }
+    }
+}
+
+/// Incremental zlib decoder that detects truncated streams
+#[cfg(feature = "deflate")]
+pub(crate) struct Inflate {
+    decompress: flate2::Decompress,
+    done: bool,
+}
+
+#[cfg(feature = "deflate")]
+impl Default for Inflate {
+    fn default() -> Self {
+        Self {
+            decompress: flate2::Decompress::new(true),
+            done: false,
+        }
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Inflate {
+    fn decode(&mut self, mut input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() * 4);
+        // Continue while input is left, or output is pending because the buffer filled up
+        while !self.done && (!input.is_empty() || output.len() == output.capacity()) {
+            output.reserve(output.capacity().max(1024));
+            let (total_in, total_out) = (self.decompress.total_in(), self.decompress.total_out());
+            let status = self
+                .decompress
+                .decompress_vec(input, &mut output, flate2::FlushDecompress::None)
+                .map_err(io::Error::other)?;
+            let consumed = (self.decompress.total_in() - total_in) as usize;
+            input = &input[consumed..];
+            self.done = status == flate2::Status::StreamEnd;
+            if consumed == 0 && self.decompress.total_out() == total_out {
+                break;
+            }
+        }
+        Ok(output)
+    }
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        if !self.done {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated deflate stream",
+            ));
+        }
+        Ok(Vec::new())
+    }
+}
+
+fn feature_name(encoding: ContentEncoding) -> &'static str {
+    match encoding {
+        ContentEncoding::Gzip => "gzip",
+        ContentEncoding::Deflate => "deflate",
+        ContentEncoding::Brotli => "brotli",
+    }
+}
+
+/// Compresses a body, for the local servers
+///
+/// Returns `None` if the encoding's feature is disabled.
+#[cfg(any(feature = "test-util", feature = "mirror", feature = "metrics-server"))]
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "deflate", feature = "brotli")),
+    allow(unused_variables)
+)]
+pub(crate) fn encode(encoding: ContentEncoding, body: &[u8]) -> Option<Vec<u8>> {
+    #[cfg(any(feature = "gzip", feature = "deflate", feature = "brotli"))]
+    use std::io::Write;
+
+    match encoding {
+        #[cfg(feature = "gzip")]
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body).ok()?;
+            encoder.finish().ok()
+        }
+        #[cfg(feature = "deflate")]
+        ContentEncoding::Deflate => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body).ok()?;
+            encoder.finish().ok()
+        }
+        #[cfg(feature = "brotli")]
+        ContentEncoding::Brotli => {
+            let mut encoded = Vec::new();
+            {
+                let mut encoder = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+                encoder.write_all(body).ok()?;
+            }
+            Some(encoded)
+        }
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_encoding() {
+        assert_eq!("GZIP".parse(), Ok(ContentEncoding::Gzip));
+        assert_eq!("br".parse(), Ok(ContentEncoding::Brotli));
+        assert!("zstd".parse::<ContentEncoding>().is_err());
+
+        let accepted = ContentEncoding::accept_encoding().unwrap_or_default();
+        for encoding in ContentEncoding::ALL {
+            assert_eq!(
+                accepted.split(", ").any(|e| e == encoding.as_str()),
+                encoding.is_supported()
+            );
+        }
+        assert!(Decoder::new(Some("identity")).unwrap().is_identity());
+        assert!(Decoder::new(Some("zstd")).is_err());
+    }
+
+    #[cfg(all(feature = "gzip", feature = "deflate", feature = "brotli"))]
+    #[test]
+    fn test_round_trip_in_chunks() {
+        let body = br#"{"relays":[{"nickname":"moria1"}]}"#.repeat(100);
+        for encoding in ContentEncoding::ALL {
+            let encoded = encode(encoding, &body).unwrap();
+            assert!(encoded.len() < body.len());
+
+            let mut decoder = Decoder::new(Some(encoding.as_str())).unwrap();
+            let mut decoded = Vec::new();
+            for chunk in encoded.chunks(7) {
+                decoded.extend(decoder.decode(chunk.to_vec()).unwrap());
+            }
+            decoded.extend(decoder.finish().unwrap());
+            assert_eq!(decoded, body, "{}", encoding);
+
+            let mut decoder = Decoder::new(Some(encoding.as_str())).unwrap();
+            let _ = decoder.decode(encoded[..encoded.len() / 2].to_vec());
+            assert!(decoder.finish().is_err(), "{}", encoding);
+        }
+    }
+}
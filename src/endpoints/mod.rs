@@ -2,6 +2,8 @@
 //!
 //! This module provides easy-to-use asynchronous functions for each API endpoint.
 
+use serde::de::DeserializeOwned;
+
 use crate::{
     client::{Client, ClientError},
     models,
@@ -12,6 +14,7 @@ use crate::{
 pub const BASE_URL: &str = "https://onionoo.torproject.org";
 
 /// Available API endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Endpoint {
     /// Summary endpoint - returns a summary document
     Summary,
@@ -39,7 +42,7 @@ impl Endpoint {
         }
     }
 
-    pub fn path(&self) -> &'static str {
+    pub const fn path(&self) -> &'static str {
         match self {
             Endpoint::Summary => "/summary",
             Endpoint::Details => "/details",
@@ -117,6 +120,10 @@ pub const ENDPOINT_DESCRIPTIONS: &[(&str, &str)] = &[
 ];
 
 /// Response data types for each endpoint
+///
+/// Superseded by the marker types implementing [`OnionooEndpoint`], whose
+/// [`Response`](OnionooEndpoint::Response) is the document type itself.
+#[deprecated(note = "use the `OnionooEndpoint` marker types, e.g. `endpoints::Details`")]
 pub enum ResponseType {
     /// SummaryResponse type
     Summary,
@@ -132,7 +139,9 @@ pub enum ResponseType {
     Uptime,
 }
 
+#[allow(deprecated)]
 impl Endpoint {
+    #[deprecated(note = "use `<E as OnionooEndpoint>::Response` instead")]
     pub fn response_type(&self) -> ResponseType {
         match self {
             Endpoint::Summary => ResponseType::Summary,
@@ -145,6 +154,84 @@ impl Endpoint {
     }
 }
 
+// ==================== TYPED ENDPOINTS ====================
+
+/// Endpoint paired with the type of its documents
+///
+/// Each endpoint has a marker type implementing this trait, so that
+/// [`Client::fetch`] checks at compile time that the response is deserialized into the
+/// document type of the requested endpoint.
+///
+/// # Examples
+///
+/// ```no_run
+/// use onionoo::{Client, QueryParameters, endpoints::Weights};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new();
+/// let response = client.fetch::<Weights>(QueryParameters::new().limit(10)).await?;
+/// println!("Found {} relays", response.relays.len());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Pairing an endpoint with the wrong document type does not compile:
+///
+/// ```compile_fail
+/// use onionoo::{Client, QueryParameters, endpoints::Weights, models::SummaryResponse};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new();
+/// let response: SummaryResponse = client.fetch::<Weights>(QueryParameters::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait OnionooEndpoint {
+    /// The endpoint requested
+    const ENDPOINT: Endpoint;
+    /// Path of the endpoint, e.g. `/details`
+    const PATH: &'static str = Self::ENDPOINT.path();
+    /// Type of the relay objects in the document
+    type Relay: DeserializeOwned;
+    /// Type of the bridge objects in the document
+    type Bridge: DeserializeOwned;
+    /// Document type returned by the endpoint
+    type Response: DeserializeOwned;
+}
+
+macro_rules! typed_endpoints {
+    ($($(#[$meta:meta])* $name:ident => $endpoint:ident, $relay:ty, $bridge:ty;)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub struct $name;
+
+            impl OnionooEndpoint for $name {
+                const ENDPOINT: Endpoint = Endpoint::$endpoint;
+                type Relay = $relay;
+                type Bridge = $bridge;
+                type Response = models::OnionooResponse<$relay, $bridge>;
+            }
+        )*
+    };
+}
+
+typed_endpoints! {
+    /// Summary endpoint returning a [`models::SummaryResponse`]
+    Summary => Summary, models::RelaySummary, models::BridgeSummary;
+    /// Details endpoint returning a [`models::DetailsResponse`]
+    Details => Details, models::RelayDetails, models::BridgeDetails;
+    /// Details endpoint returning a [`models::PartialDetailsResponse`], for use with the
+    /// `fields` parameter
+    PartialDetails => Details, models::PartialRelayDetails, models::PartialBridgeDetails;
+    /// Bandwidth endpoint returning a [`models::BandwidthResponse`]
+    Bandwidth => Bandwidth, models::RelayBandwidth, models::BridgeBandwidth;
+    /// Weights endpoint returning a [`models::WeightsResponse`]
+    Weights => Weights, models::RelayWeights, ();
+    /// Clients endpoint returning a [`models::ClientsResponse`]
+    Clients => Clients, (), models::BridgeClients;
+    /// Uptime endpoint returning a [`models::UptimeResponse`]
+    Uptime => Uptime, models::RelayUptime, models::BridgeUptime;
+}
+
 // ==================== ASYNC ENDPOINT FUNCTIONS ====================
 
 /// Returns a summary document containing short summaries of relays with nicknames,
@@ -169,7 +256,7 @@ pub async fn summary(
     client: &Client,
     params: QueryParameters,
 ) -> Result<models::SummaryResponse, ClientError> {
-    client.get("/summary", params).await
+    client.fetch::<Summary>(params).await
 }
 
 /// Returns a details document based on network statuses published by the Tor directories,
@@ -194,7 +281,7 @@ pub async fn details(
     client: &Client,
     params: QueryParameters,
 ) -> Result<models::DetailsResponse, ClientError> {
-    client.get("/details", params).await
+    client.fetch::<Details>(params).await
 }
 
 /// Returns a bandwidth document containing aggregate statistics of a relay's or bridge's
@@ -219,7 +306,7 @@ pub async fn bandwidth(
     client: &Client,
     params: QueryParameters,
 ) -> Result<models::BandwidthResponse, ClientError> {
-    client.get("/bandwidth", params).await
+    client.fetch::<Bandwidth>(params).await
 }
 
 /// Returns a weights document containing aggregate statistics of a relay's probability
@@ -244,7 +331,7 @@ pub async fn weights(
     client: &Client,
     params: QueryParameters,
 ) -> Result<models::WeightsResponse, ClientError> {
-    client.get("/weights", params).await
+    client.fetch::<Weights>(params).await
 }
 
 /// Returns a clients document containing estimates of the average number of clients
@@ -268,7 +355,7 @@ pub async fn clients(
     client: &Client,
     params: QueryParameters,
 ) -> Result<models::ClientsResponse, ClientError> {
-    client.get("/clients", params).await
+    client.fetch::<Clients>(params).await
 }
 
 /// Returns an uptime document containing fractional uptimes of relays and bridges.
@@ -292,7 +379,7 @@ pub async fn uptime(
     client: &Client,
     params: QueryParameters,
 ) -> Result<models::UptimeResponse, ClientError> {
-    client.get("/uptime", params).await
+    client.fetch::<Uptime>(params).await
 }
 
 /// Helper functions for working with endpoints
@@ -0,0 +1,525 @@
+//! Prometheus Exporter
+//!
+//! This module turns relay documents into the Prometheus text exposition format, so
+//! relay operators can chart their relays in Grafana without glue code. Each relay of
+//! a details document becomes one sample per gauge, labelled with its fingerprint,
+//! nickname, country, AS and flags. The latest data points of the bandwidth, weights
+//! and uptime documents of the same relays are added when they are given.
+//!
+//! With the `metrics-server` feature, [`MetricsServer`] serves the exposition on
+//! `/metrics` for Prometheus to scrape.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::client::{Client, ClientError};
+use crate::endpoints;
+use crate::models::{
+    BandwidthResponse, DetailsResponse, RelayBandwidth, RelayDetails, RelayUptime, RelayWeights,
+    UptimeResponse, WeightsResponse,
+};
+use crate::parameters::{QueryParameters, TypeValue};
+use crate::utils::parse_timestamp;
+
+/// `Content-Type` of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Documents describing the same set of relays
+#[derive(Debug, Clone)]
+pub struct RelayDocuments {
+    pub details: DetailsResponse,
+    pub bandwidth: Option<BandwidthResponse>,
+    pub weights: Option<WeightsResponse>,
+    pub uptime: Option<UptimeResponse>,
+}
+
+impl RelayDocuments {
+    pub fn new(details: DetailsResponse) -> Self {
+        Self {
+            details,
+            bandwidth: None,
+            weights: None,
+            uptime: None,
+        }
+    }
+
+    /// Add the bandwidth document, for read and written bytes and overload flags
+    pub fn bandwidth(mut self, bandwidth: BandwidthResponse) -> Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Add the weights document, used for probabilities missing from the details
+    pub fn weights(mut self, weights: WeightsResponse) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Add the uptime document, for the uptime fraction
+    pub fn uptime(mut self, uptime: UptimeResponse) -> Self {
+        self.uptime = Some(uptime);
+        self
+    }
+
+    /// Fetch all four documents with the same parameters
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use onionoo::{Client, QueryParameters, exporter::{Exporter, RelayDocuments}};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new();
+    /// let params = QueryParameters::new().contact("ops@example.com");
+    /// let documents = RelayDocuments::fetch(&client, params).await?;
+    /// print!("{}", Exporter::new().render(&documents));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch(client: &Client, params: QueryParameters) -> Result<Self, ClientError> {
+        let relays = params.type_param(TypeValue::Relay);
+        Ok(Self::new(endpoints::details(client, relays.clone()).await?)
+            .bandwidth(endpoints::bandwidth(client, relays.clone()).await?)
+            .weights(endpoints::weights(client, relays.clone()).await?)
+            .uptime(endpoints::uptime(client, relays).await?))
+    }
+}
+
+/// Documents of one relay
+struct Relay<'a> {
+    details: &'a RelayDetails,
+    bandwidth: Option<&'a RelayBandwidth>,
+    weights: Option<&'a RelayWeights>,
+    uptime: Option<&'a RelayUptime>,
+}
+
+/// Gauge with one sample per relay
+struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: fn(&Relay<'_>) -> Option<f64>,
+}
+
+fn flag(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+const GAUGES: &[Gauge] = &[
+    Gauge {
+        name: "relay_running",
+        help: "Whether the relay is listed as running in the last consensus",
+        value: |relay| Some(flag(relay.details.running)),
+    },
+    Gauge {
+        name: "relay_consensus_weight",
+        help: "Weight assigned to the relay by the directory authorities",
+        value: |relay| Some(relay.details.consensus_weight as f64),
+    },
+    Gauge {
+        name: "relay_consensus_weight_fraction",
+        help: "Fraction of the relay's consensus weight of the sum of all consensus weights",
+        value: |relay| {
+            relay
+                .details
+                .consensus_weight_fraction
+                .or_else(|| relay.weights?.consensus_weight_fraction.as_ref()?.latest())
+        },
+    },
+    Gauge {
+        name: "relay_guard_probability",
+        help: "Probability of the relay to be selected for the guard position",
+        value: |relay| {
+            relay
+                .details
+                .guard_probability
+                .or_else(|| relay.weights?.guard_probability.as_ref()?.latest())
+        },
+    },
+    Gauge {
+        name: "relay_middle_probability",
+        help: "Probability of the relay to be selected for the middle position",
+        value: |relay| {
+            relay
+                .details
+                .middle_probability
+                .or_else(|| relay.weights?.middle_probability.as_ref()?.latest())
+        },
+    },
+    Gauge {
+        name: "relay_exit_probability",
+        help: "Probability of the relay to be selected for the exit position",
+        value: |relay| {
+            relay
+                .details
+                .exit_probability
+                .or_else(|| relay.weights?.exit_probability.as_ref()?.latest())
+        },
+    },
+    Gauge {
+        name: "relay_advertised_bandwidth_bytes_per_second",
+        help: "Bandwidth the relay is willing and capable to provide",
+        value: |relay| relay.details.advertised_bandwidth.map(|bw| bw as f64),
+    },
+    Gauge {
+        name: "relay_read_bytes_per_second",
+        help: "Latest average of bytes read by the relay",
+        value: |relay| relay.bandwidth?.read_history.as_ref()?.latest(),
+    },
+    Gauge {
+        name: "relay_written_bytes_per_second",
+        help: "Latest average of bytes written by the relay",
+        value: |relay| relay.bandwidth?.write_history.as_ref()?.latest(),
+    },
+    Gauge {
+        name: "relay_uptime_fraction",
+        help: "Latest fraction of time the relay was running",
+        value: |relay| relay.uptime?.uptime.as_ref()?.latest(),
+    },
+    Gauge {
+        name: "relay_overload_general",
+        help: "Whether the relay reported general overload",
+        value: |relay| Some(flag(relay.details.overload_general_timestamp.is_some())),
+    },
+    Gauge {
+        name: "relay_overload_ratelimits",
+        help: "Whether the relay reported hitting its rate limits",
+        value: |relay| Some(flag(relay.bandwidth?.overload_ratelimits.is_some())),
+    },
+    Gauge {
+        name: "relay_overload_fd_exhausted",
+        help: "Whether the relay reported running out of file descriptors",
+        value: |relay| Some(flag(relay.bandwidth?.overload_fd_exhausted.is_some())),
+    },
+];
+
+/// Renders [`RelayDocuments`] in the Prometheus text exposition format
+///
+/// Metric names start with `onionoo_` unless another [`prefix`](Self::prefix) is
+/// set. Relays missing a value, e.g. because the document it comes from was not given,
+/// have no sample for that gauge.
+///
+/// # Examples
+///
+/// ```
+/// use onionoo::exporter::{Exporter, RelayDocuments};
+/// use onionoo::models::DetailsResponse;
+///
+/// let details: DetailsResponse = serde_json::from_str(r#"{
+///     "version": "8.0",
+///     "relays_published": "2024-01-01 00:00:00",
+///     "bridges_published": "2024-01-01 00:00:00",
+///     "relays": [{
+///         "nickname": "moria1",
+///         "fingerprint": "9695DFC35FFEB861329B9F1AB04C46397020CE31",
+///         "or_addresses": ["128.31.0.34:9101"],
+///         "last_seen": "2024-01-01 00:00:00",
+///         "last_changed_address_or_port": "2024-01-01 00:00:00",
+///         "first_seen": "2024-01-01 00:00:00",
+///         "running": true,
+///         "consensus_weight": 20
+///     }],
+///     "bridges": []
+/// }"#)?;
+///
+/// let text = Exporter::new().render(&RelayDocuments::new(details));
+/// assert!(text.contains(r#"onionoo_relay_consensus_weight{fingerprint="9695DFC35FFEB861329B9F1AB04C46397020CE31",nickname="moria1",country="",as="",flags=""} 20"#));
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    prefix: String,
+}
+
+impl Default for Exporter {
+    fn default() -> Self {
+        Self {
+            prefix: "onionoo_".to_string(),
+        }
+    }
+}
+
+impl Exporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prefix of all metric names, including any separator (default `onionoo_`)
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Render the documents as text exposition
+    pub fn render(&self, documents: &RelayDocuments) -> String {
+        fn by_fingerprint<T>(entries: &[T], fingerprint: fn(&T) -> &str) -> HashMap<String, &T> {
+            entries
+                .iter()
+                .map(|entry| (fingerprint(entry).to_uppercase(), entry))
+                .collect()
+        }
+        let bandwidth = documents
+            .bandwidth
+            .as_ref()
+            .map(|doc| by_fingerprint(&doc.relays, |relay| &relay.fingerprint));
+        let weights = documents
+            .weights
+            .as_ref()
+            .map(|doc| by_fingerprint(&doc.relays, |relay| &relay.fingerprint));
+        let uptime = documents
+            .uptime
+            .as_ref()
+            .map(|doc| by_fingerprint(&doc.relays, |relay| &relay.fingerprint));
+
+        let relays: Vec<(String, Relay<'_>)> = documents
+            .details
+            .relays
+            .iter()
+            .map(|details| {
+                let key = details.fingerprint.to_uppercase();
+                let relay = Relay {
+                    details,
+                    bandwidth: bandwidth.as_ref().and_then(|m| m.get(&key)).copied(),
+                    weights: weights.as_ref().and_then(|m| m.get(&key)).copied(),
+                    uptime: uptime.as_ref().and_then(|m| m.get(&key)).copied(),
+                };
+                (labels(details), relay)
+            })
+            .collect();
+
+        let mut output = String::new();
+        if let Some(published) = parse_timestamp(&documents.details.relays_published) {
+            self.header(
+                &mut output,
+                "relays_published_timestamp_seconds",
+                "Time the relays in the details document were published",
+            );
+            let _ = writeln!(
+                output,
+                "{}relays_published_timestamp_seconds {}",
+                self.prefix,
+                published.and_utc().timestamp()
+            );
+        }
+        for gauge in GAUGES {
+            let samples: Vec<(&str, f64)> = relays
+                .iter()
+                .filter_map(|(labels, relay)| Some((labels.as_str(), (gauge.value)(relay)?)))
+                .collect();
+            if samples.is_empty() {
+                continue;
+            }
+            self.header(&mut output, gauge.name, gauge.help);
+            for (labels, value) in samples {
+                let _ = writeln!(
+                    output,
+                    "{}{}{{{}}} {}",
+                    self.prefix, gauge.name, labels, value
+                );
+            }
+        }
+        output
+    }
+
+    fn header(&self, output: &mut String, name: &str, help: &str) {
+        let _ = writeln!(output, "# HELP {}{} {}", self.prefix, name, help);
+        let _ = writeln!(output, "# TYPE {}{} gauge", self.prefix, name);
+    }
+}
+
+/// Label set of a relay, without the braces
+fn labels(relay: &RelayDetails) -> String {
+    let country = relay.country.as_ref().map(|c| c.to_string());
+    let as_number = relay.as_number.as_ref().map(|a| a.to_string());
+    let flags = relay.flags.as_deref().unwrap_or_default().join(",");
+    [
+        ("fingerprint", relay.fingerprint.as_str()),
+        ("nickname", relay.nickname.as_str()),
+        ("country", country.as_deref().unwrap_or_default()),
+        ("as", as_number.as_deref().unwrap_or_default()),
+        ("flags", flags.as_str()),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Escapes a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(feature = "metrics-server")]
+pub use server::MetricsServer;
+
+#[cfg(feature = "metrics-server")]
+mod server {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, RwLock};
+
+    use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+    use tokio::task::JoinHandle;
+
+    use super::CONTENT_TYPE;
+    use crate::encoding::ContentEncoding;
+    use crate::serve;
+
+    /// HTTP server answering `GET /metrics` with the last exposition it was given
+    ///
+    /// Until the first [`update`](Self::update), scrapes are answered with
+    /// `503 Service Unavailable`. The server stops when the handle is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use onionoo::{Client, QueryParameters};
+    /// use onionoo::exporter::{Exporter, MetricsServer, RelayDocuments};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let server = MetricsServer::bind("127.0.0.1:9100").await?;
+    /// let client = Client::new();
+    /// loop {
+    ///     let params = QueryParameters::new().contact("ops@example.com");
+    ///     match RelayDocuments::fetch(&client, params).await {
+    ///         Ok(documents) => server.update(Exporter::new().render(&documents)),
+    ///         Err(e) => eprintln!("Refresh failed: {}", e),
+    ///     }
+    ///     tokio::time::sleep(Duration::from_secs(3600)).await;
+    /// }
+    /// # }
+    /// ```
+    pub struct MetricsServer {
+        address: SocketAddr,
+        exposition: Arc<RwLock<Option<Arc<String>>>>,
+        task: JoinHandle<()>,
+    }
+
+    impl MetricsServer {
+        /// Start serving on `address`
+        ///
+        /// Use port 0 to bind to a free port.
+        pub async fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+            let listener = TcpListener::bind(address).await?;
+            let address = listener.local_addr()?;
+            let exposition = Arc::new(RwLock::new(None));
+
+            let task_exposition = Arc::clone(&exposition);
+            let task = tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let exposition = task_exposition.read().unwrap().clone();
+                    tokio::spawn(async move {
+                        let _ = handle(stream, exposition).await;
+                    });
+                }
+            });
+
+            Ok(Self {
+                address,
+                exposition,
+                task,
+            })
+        }
+
+        /// Address the server is listening on
+        pub fn local_addr(&self) -> SocketAddr {
+            self.address
+        }
+
+        /// URL of the metrics, e.g. `http://127.0.0.1:9100/metrics`
+        pub fn url(&self) -> String {
+            format!("http://{}/metrics", self.address)
+        }
+
+        /// Replace the exposition served to later scrapes
+        pub fn update(&self, exposition: String) {
+            *self.exposition.write().unwrap() = Some(Arc::new(exposition));
+        }
+
+        /// Serve until the listener fails
+        pub async fn wait(&mut self) {
+            let _ = (&mut self.task).await;
+        }
+    }
+
+    impl Drop for MetricsServer {
+        fn drop(&mut self) {
+            self.task.abort();
+        }
+    }
+
+    async fn handle(mut stream: TcpStream, exposition: Option<Arc<String>>) -> io::Result<()> {
+        let Some(request) = serve::read_request(&mut stream).await? else {
+            return Ok(());
+        };
+        let path = request.target.split('?').next().unwrap_or_default();
+        let (status, body) = match (request.method.as_str(), path, &exposition) {
+            ("GET", "/metrics", Some(exposition)) => (200, exposition.as_str()),
+            ("GET", "/metrics", None) => (503, ""),
+            ("GET", _, _) => (404, ""),
+            _ => (405, ""),
+        };
+        let encoding = request.negotiate(&ContentEncoding::supported());
+        serve::write_typed_response(&mut stream, status, CONTENT_TYPE, body, encoding).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> RelayDocuments {
+        fn parse<T: serde::de::DeserializeOwned>(json: &str) -> T {
+            serde_json::from_str(json).unwrap()
+        }
+        let mut details: DetailsResponse = parse(include_str!("../mock/fixtures/details.json"));
+        details.relays[0].nickname = "quote\"d".to_string();
+        RelayDocuments::new(details)
+            .bandwidth(parse(include_str!("../mock/fixtures/bandwidth.json")))
+            .weights(parse(include_str!("../mock/fixtures/weights.json")))
+            .uptime(parse(include_str!("../mock/fixtures/uptime.json")))
+    }
+
+    #[test]
+    fn test_render() {
+        let documents = documents();
+        let text = Exporter::new().render(&documents);
+        let relay = &documents.details.relays[1];
+
+        for gauge in GAUGES {
+            let help = format!("# HELP onionoo_{} ", gauge.name);
+            assert_eq!(text.matches(&help).count(), 1, "{}", gauge.name);
+        }
+        let labels = format!(
+            "fingerprint=\"{}\",nickname=\"bravo\",country=\"de\",as=\"AS24940\",flags=\"Exit,Fast,Running,Stable,Valid\"",
+            relay.fingerprint
+        );
+        assert!(text.contains(&format!(
+            "onionoo_relay_consensus_weight{{{}}} {}\n",
+            labels, relay.consensus_weight
+        )));
+        assert!(text.contains("nickname=\"quote\\\"d\""));
+
+        let bandwidth = &documents.bandwidth.as_ref().unwrap().relays[1];
+        let read = bandwidth.read_history.as_ref().unwrap().latest().unwrap();
+        assert!(text.contains(&format!(
+            "onionoo_relay_read_bytes_per_second{{{}}} {}\n",
+            labels, read
+        )));
+        let samples = text
+            .lines()
+            .filter(|line| line.starts_with("onionoo_relay_uptime_fraction{"))
+            .count();
+        assert_eq!(samples, documents.details.relays.len());
+
+        // Gauges from documents that were not given are left out
+        let details_only = RelayDocuments::new(documents.details.clone());
+        let text = Exporter::new().prefix("tor_").render(&details_only);
+        assert!(text.contains("# TYPE tor_relay_running gauge"));
+        assert!(!text.contains("read_bytes_per_second"));
+        assert!(!text.contains("onionoo_"));
+    }
+}
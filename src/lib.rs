@@ -2,10 +2,30 @@
 //!
 //! A Rust wrapper for the Onionoo Tor network status protocol, providing a convenient and type-safe interface to query information about Tor relays and bridges.
 
+pub mod alerts;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
+pub mod encoding;
 pub mod endpoints;
+pub mod exporter;
+pub mod limits;
+pub mod metrics;
+#[cfg(feature = "mirror")]
+pub mod mirror;
+#[cfg(feature = "test-util")]
+pub mod mock;
 pub mod models;
 pub mod parameters;
+pub mod query;
+#[cfg(any(feature = "test-util", feature = "mirror", feature = "metrics-server"))]
+mod serve;
+pub mod stream;
+pub mod tabular;
+pub mod transport;
 pub mod utils;
 
 // Re-export commonly used types for convenience
@@ -0,0 +1,346 @@
+//! Client-Side Rate Limiting
+//!
+//! The Onionoo operators ask heavy users to spread their requests out. A
+//! [`Client`](crate::Client) can be given a token-bucket [`RateLimit`] and a cap on
+//! the number of requests in flight with
+//! [`Client::rate_limit`](crate::Client::rate_limit) and
+//! [`Client::max_in_flight`](crate::Client::max_in_flight). The limits are shared by
+//! all clones of the client, so tasks fanning out requests through their own clone
+//! are limited together.
+//!
+//! Requests over the limits wait before they are sent. How long a request waited is
+//! reported in [`ResponseMetadata::queued`](crate::client::ResponseMetadata::queued),
+//! and [`Client::limit_stats`](crate::Client::limit_stats) returns the current
+//! number of requests in flight and waiting.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+/// Token-bucket rate limit
+///
+/// The bucket holds up to [`burst`](Self::burst) tokens and refills at `requests`
+/// tokens per `per`. Every request takes one token, and waits for the bucket to refill
+/// if it is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use onionoo::limits::RateLimit;
+///
+/// let limit = RateLimit::per_second(2).burst(5);
+/// assert_eq!(limit.interval(), Duration::from_millis(500));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    requests: u32,
+    per: Duration,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// Allow `requests` requests per `per`, one at a time
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` or `per` is zero.
+    pub fn new(requests: u32, per: Duration) -> Self {
+        assert!(requests > 0, "rate limit must allow at least one request");
+        assert!(!per.is_zero(), "rate limit period must not be zero");
+        Self {
+            requests,
+            per,
+            burst: 1,
+        }
+    }
+
+    /// Allow `requests` requests per second
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// Allow `requests` requests per minute
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Allow up to `burst` requests at once after an idle period (default 1)
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Time for one token to be refilled
+    pub fn interval(&self) -> Duration {
+        self.per / self.requests
+    }
+
+    /// Size of the bucket
+    pub fn burst_size(&self) -> u32 {
+        self.burst
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests per {:?} (burst {})",
+            self.requests, self.per, self.burst
+        )
+    }
+}
+
+/// Snapshot of a client's limits and the requests they hold back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitStats {
+    /// Configured rate limit
+    pub rate_limit: Option<RateLimit>,
+    /// Configured maximum number of requests in flight
+    pub max_in_flight: Option<usize>,
+    /// Requests being sent or received
+    pub in_flight: usize,
+    /// Requests waiting for the rate limit or a free slot
+    pub waiting: usize,
+    /// Tokens left in the rate-limit bucket
+    pub available_tokens: Option<u32>,
+    /// Requests that had to wait before they were sent
+    pub throttled: u64,
+    /// Total time requests spent waiting
+    pub total_wait: Duration,
+}
+
+impl LimitStats {
+    /// Whether requests are currently being held back
+    pub fn is_saturated(&self) -> bool {
+        self.waiting > 0
+    }
+}
+
+struct Bucket {
+    /// Tokens left, negative if requests have reserved tokens not yet refilled
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Shared state of a client's limits
+pub(crate) struct Limiter {
+    rate_limit: Option<RateLimit>,
+    bucket: Mutex<Bucket>,
+    max_in_flight: Option<usize>,
+    semaphore: Option<Semaphore>,
+    in_flight: AtomicUsize,
+    waiting: AtomicUsize,
+    throttled: AtomicU64,
+    waited_micros: AtomicU64,
+}
+
+impl Limiter {
+    pub fn new(rate_limit: Option<RateLimit>, max_in_flight: Option<usize>) -> Self {
+        Self {
+            rate_limit,
+            bucket: Mutex::new(Bucket {
+                tokens: rate_limit.map_or(0.0, |limit| limit.burst as f64),
+                refilled: Instant::now(),
+            }),
+            max_in_flight,
+            semaphore: max_in_flight.map(Semaphore::new),
+            in_flight: AtomicUsize::new(0),
+            waiting: AtomicUsize::new(0),
+            throttled: AtomicU64::new(0),
+            waited_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+    }
+
+    pub fn max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    /// Waits until a request may be sent
+    ///
+    /// The request counts as in flight until the returned permit is dropped.
+    pub async fn acquire(&self) -> Permit<'_> {
+        let started = Instant::now();
+        let waiting = Counter::enter(&self.waiting);
+        let mut throttled = false;
+        let slot = match &self.semaphore {
+            Some(semaphore) => Some(match semaphore.try_acquire() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    throttled = true;
+                    semaphore
+                        .acquire()
+                        .await
+                        .expect("limiter semaphore is never closed")
+                }
+            }),
+            None => None,
+        };
+        if let Some(delay) = self.reserve() {
+            throttled = true;
+            tokio::time::sleep(delay).await;
+        }
+        drop(waiting);
+
+        let queued = if throttled {
+            let queued = started.elapsed();
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            self.waited_micros
+                .fetch_add(queued.as_micros() as u64, Ordering::Relaxed);
+            queued
+        } else {
+            Duration::ZERO
+        };
+        Permit {
+            _slot: slot,
+            _in_flight: Counter::enter(&self.in_flight),
+            queued,
+        }
+    }
+
+    /// Takes a token and returns how long to wait for it, if the bucket is empty
+    fn reserve(&self) -> Option<Duration> {
+        let limit = self.rate_limit?;
+        let interval = limit.interval();
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        bucket.tokens = refilled(&bucket, limit, now);
+        bucket.refilled = now;
+        bucket.tokens -= 1.0;
+        (bucket.tokens < 0.0).then(|| interval.mul_f64(-bucket.tokens))
+    }
+
+    pub fn stats(&self) -> LimitStats {
+        let available_tokens = self.rate_limit.map(|limit| {
+            let bucket = self.bucket.lock().unwrap();
+            refilled(&bucket, limit, Instant::now()).max(0.0) as u32
+        });
+        LimitStats {
+            rate_limit: self.rate_limit,
+            max_in_flight: self.max_in_flight,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            waiting: self.waiting.load(Ordering::Relaxed),
+            available_tokens,
+            throttled: self.throttled.load(Ordering::Relaxed),
+            total_wait: Duration::from_micros(self.waited_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl fmt::Debug for Limiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Limiter")
+            .field("rate_limit", &self.rate_limit)
+            .field("max_in_flight", &self.max_in_flight)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+fn refilled(bucket: &Bucket, limit: RateLimit, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.refilled);
+    (bucket.tokens + elapsed.as_secs_f64() / limit.interval().as_secs_f64()).min(limit.burst as f64)
+}
+
+/// Permission to send one request
+pub(crate) struct Permit<'a> {
+    _slot: Option<SemaphorePermit<'a>>,
+    _in_flight: Counter<'a>,
+    queued: Duration,
+}
+
+impl Permit<'_> {
+    /// Time spent waiting for the permit
+    pub fn queued(&self) -> Duration {
+        self.queued
+    }
+}
+
+/// Increments a counter until dropped, so cancelled requests are not counted
+struct Counter<'a>(&'a AtomicUsize);
+
+impl<'a> Counter<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Counter<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket() {
+        let limiter = Limiter::new(Some(RateLimit::per_second(50).burst(2)), None);
+        let started = Instant::now();
+        for _ in 0..2 {
+            assert_eq!(limiter.acquire().await.queued(), Duration::ZERO);
+        }
+        assert_eq!(limiter.stats().available_tokens, Some(0));
+
+        // The next two requests wait 20ms each for a token
+        for _ in 0..2 {
+            assert_eq!(limiter.acquire().await.queued(), Duration::from_millis(20));
+        }
+        assert_eq!(started.elapsed(), Duration::from_millis(40));
+
+        let stats = limiter.stats();
+        assert_eq!(stats.throttled, 2);
+        assert_eq!(stats.total_wait, Duration::from_millis(40));
+        assert_eq!((stats.in_flight, stats.waiting), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        let limiter = Limiter::new(None, Some(1));
+        let first = limiter.acquire().await;
+        assert_eq!(limiter.stats().in_flight, 1);
+
+        let second = limiter.acquire();
+        tokio::pin!(second);
+        // Polling once registers the request as waiting
+        assert!(poll_once(second.as_mut()).await.is_none());
+        assert!(limiter.stats().is_saturated());
+
+        drop(first);
+        let second = second.await;
+        assert!(second.queued() > Duration::ZERO);
+        assert_eq!(limiter.stats().waiting, 0);
+        assert_eq!(limiter.stats().throttled, 1);
+    }
+
+    async fn poll_once<F: std::future::Future + Unpin>(future: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            output = future => Some(output),
+            _ = std::future::ready(()) => None,
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_rate_limit() {
+        RateLimit::per_second(0);
+    }
+}
@@ -0,0 +1,380 @@
+//! Request Instrumentation
+//!
+//! Every request made by [`Client::get_with_metadata`](crate::Client::get_with_metadata),
+//! and so by all endpoint functions, and by [`Client::stream`](crate::Client::stream)
+//! is reported to the client's [`MetricsHook`], if one is set with
+//! [`Client::with_metrics`](crate::Client::with_metrics). The hook receives counters
+//! for requests, errors by [`ErrorKind`], retries and cache hits, and the latency of
+//! each request, so it can feed any metrics system. [`Recorder`] is a ready-made hook
+//! keeping the totals in memory.
+//!
+//! With the `tracing` feature, each request also runs in a `DEBUG` span named
+//! `onionoo.request` with the fields `endpoint` and `query`, and `status`,
+//! `latency_ms`, `queued_ms`, `body_size` or `error_kind` once it has finished.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client::{ClientError, ErrorKind, ResponseMetadata, WithMetadata};
+use crate::parameters::QueryParameters;
+
+/// Upper bounds of the latency histogram buckets kept by [`Recorder`], in seconds
+pub const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Receiver of request metrics
+///
+/// All methods do nothing by default, so an implementation only overrides the
+/// metrics it records. `endpoint` is the path of the request, e.g. `/details`.
+/// Methods are called on the task making the request and should not block.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use onionoo::{Client, client::ClientError, metrics::MetricsHook};
+///
+/// #[derive(Debug, Default)]
+/// struct ErrorCounter(AtomicU64);
+///
+/// impl MetricsHook for ErrorCounter {
+///     fn on_error(&self, endpoint: &str, error: &ClientError) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///         eprintln!("{} failed ({}): {}", endpoint, error.kind(), error);
+///     }
+/// }
+///
+/// let client = Client::new().with_metrics(ErrorCounter::default());
+/// ```
+pub trait MetricsHook: fmt::Debug + Send + Sync {
+    /// A request was made
+    ///
+    /// Requests whose parameters fail validation are counted too, and then reported
+    /// to [`on_error`](Self::on_error) with [`ErrorKind::InvalidRequest`] without
+    /// being sent.
+    fn on_request(&self, endpoint: &str) {
+        let _ = endpoint;
+    }
+
+    /// A response was received and parsed
+    fn on_response(&self, endpoint: &str, metadata: &ResponseMetadata) {
+        let _ = (endpoint, metadata);
+    }
+
+    /// A request failed, see [`ClientError::kind`]
+    fn on_error(&self, endpoint: &str, error: &ClientError) {
+        let _ = (endpoint, error);
+    }
+
+    /// A request finished, successfully or not, after `latency` including the time
+    /// it waited for the client's [`limits`](crate::limits)
+    fn on_latency(&self, endpoint: &str, latency: Duration) {
+        let _ = (endpoint, latency);
+    }
+
+    /// A failed request is retried, see [`Client::report_retry`](crate::Client::report_retry)
+    fn on_retry(&self, endpoint: &str, error: &ClientError) {
+        let _ = (endpoint, error);
+    }
+
+    /// A response was served by an HTTP cache, see
+    /// [`ResponseMetadata::is_cache_hit`]
+    fn on_cache_hit(&self, endpoint: &str) {
+        let _ = endpoint;
+    }
+}
+
+/// Totals collected by a [`Recorder`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    /// Requests attempted, including those rejected before sending
+    pub requests: u64,
+    /// Failed requests by kind
+    pub errors: BTreeMap<ErrorKind, u64>,
+    /// Retried requests
+    pub retries: u64,
+    /// Responses served by an HTTP cache
+    pub cache_hits: u64,
+    /// Number of requests with a latency up to each of [`LATENCY_BUCKETS`], not
+    /// cumulative
+    pub latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    /// Number of requests slower than the last bucket
+    pub latency_overflow: u64,
+    /// Sum of all latencies
+    pub latency_sum: Duration,
+}
+
+impl MetricsSnapshot {
+    /// Total number of failed requests
+    pub fn error_count(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// Number of finished requests
+    pub fn latency_count(&self) -> u64 {
+        self.latency_buckets.iter().sum::<u64>() + self.latency_overflow
+    }
+}
+
+/// [`MetricsHook`] keeping totals over all endpoints in memory
+///
+/// The recorder can be cloned before it is passed to
+/// [`Client::with_metrics`](crate::Client::with_metrics); clones share the totals.
+///
+/// # Examples
+///
+/// ```
+/// use onionoo::{Client, metrics::Recorder};
+///
+/// let recorder = Recorder::new();
+/// let client = Client::new().with_metrics(recorder.clone());
+/// // ... make requests ...
+/// let snapshot = recorder.snapshot();
+/// println!("{} requests, {} errors", snapshot.requests, snapshot.error_count());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    totals: Arc<Mutex<MetricsSnapshot>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current totals
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.totals.lock().unwrap().clone()
+    }
+}
+
+impl MetricsHook for Recorder {
+    fn on_request(&self, _endpoint: &str) {
+        self.totals.lock().unwrap().requests += 1;
+    }
+
+    fn on_error(&self, _endpoint: &str, error: &ClientError) {
+        *self
+            .totals
+            .lock()
+            .unwrap()
+            .errors
+            .entry(error.kind())
+            .or_default() += 1;
+    }
+
+    fn on_latency(&self, _endpoint: &str, latency: Duration) {
+        let mut totals = self.totals.lock().unwrap();
+        let seconds = latency.as_secs_f64();
+        match LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            Some(bucket) => totals.latency_buckets[bucket] += 1,
+            None => totals.latency_overflow += 1,
+        }
+        totals.latency_sum += latency;
+    }
+
+    fn on_retry(&self, _endpoint: &str, _error: &ClientError) {
+        self.totals.lock().unwrap().retries += 1;
+    }
+
+    fn on_cache_hit(&self, _endpoint: &str) {
+        self.totals.lock().unwrap().cache_hits += 1;
+    }
+}
+
+/// Reports one request to the hook and the tracing span
+pub(crate) struct Observer<'a> {
+    hook: Option<&'a dyn MetricsHook>,
+    endpoint: &'a str,
+    started: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'a> Observer<'a> {
+    pub fn start(
+        hook: Option<&'a dyn MetricsHook>,
+        endpoint: &'a str,
+        params: &QueryParameters,
+    ) -> Self {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "onionoo.request",
+            endpoint,
+            query = params.to_query_string().trim_start_matches('?'),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            queued_ms = tracing::field::Empty,
+            body_size = tracing::field::Empty,
+            error_kind = tracing::field::Empty,
+        );
+        #[cfg(not(feature = "tracing"))]
+        let _ = params;
+        if let Some(hook) = hook {
+            hook.on_request(endpoint);
+        }
+        Self {
+            hook,
+            endpoint,
+            started: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span,
+        }
+    }
+
+    /// Runs the request inside the span
+    pub async fn instrument<F: Future>(&self, request: F) -> F::Output {
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument;
+            request.instrument(self.span.clone()).await
+        }
+        #[cfg(not(feature = "tracing"))]
+        request.await
+    }
+
+    /// Reports the outcome of the request
+    pub fn finish<T>(self, result: &Result<WithMetadata<T>, ClientError>) {
+        let latency = self.started.elapsed();
+        #[cfg(feature = "tracing")]
+        {
+            let _entered = self.span.enter();
+            self.span
+                .record("latency_ms", latency.as_secs_f64() * 1000.0);
+            match result {
+                Ok(response) => {
+                    let metadata = &response.metadata;
+                    self.span.record("status", metadata.status);
+                    self.span
+                        .record("queued_ms", metadata.queued.as_secs_f64() * 1000.0);
+                    self.span.record("body_size", metadata.body_size);
+                    tracing::debug!(cache_hit = metadata.is_cache_hit(), "request finished");
+                }
+                Err(error) => {
+                    if let Some(status) = error.status() {
+                        self.span.record("status", status);
+                    }
+                    self.span.record("error_kind", error.kind().as_str());
+                    tracing::debug!(%error, "request failed");
+                }
+            }
+        }
+        let Some(hook) = self.hook else {
+            return;
+        };
+        match result {
+            Ok(response) => {
+                hook.on_response(self.endpoint, &response.metadata);
+                if response.metadata.is_cache_hit() {
+                    hook.on_cache_hit(self.endpoint);
+                }
+            }
+            Err(error) => hook.on_error(self.endpoint, error),
+        }
+        hook.on_latency(self.endpoint, latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder() {
+        let recorder = Recorder::new();
+        recorder.on_request("/summary");
+        recorder.on_request("/summary");
+        recorder.on_error("/summary", &ClientError::Other("boom".to_string()));
+        recorder.on_latency("/summary", Duration::from_millis(70));
+        recorder.on_latency("/summary", Duration::from_secs(90));
+        recorder.on_cache_hit("/summary");
+
+        let snapshot = recorder.clone().snapshot();
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.errors.get(&ErrorKind::Other), Some(&1));
+        assert_eq!(snapshot.error_count(), 1);
+        assert_eq!(snapshot.cache_hits, 1);
+        assert_eq!(snapshot.latency_buckets[1], 1);
+        assert_eq!(snapshot.latency_overflow, 1);
+        assert_eq!(snapshot.latency_count(), 2);
+        assert_eq!(snapshot.latency_sum, Duration::from_millis(90_070));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_request_span() {
+        use crate::Client;
+        use crate::parameters::QueryParameters;
+        use crate::transport::{FixtureTransport, Response};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+
+        /// Collects the fields recorded on spans and events
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<(String, String)>>>);
+
+        impl Visit for Capture {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                let value = format!("{:?}", value);
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((field.name().to_string(), value));
+            }
+        }
+
+        impl tracing::Subscriber for Capture {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                span.record(&mut self.clone());
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, values: &Record<'_>) {
+                values.record(&mut self.clone());
+            }
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &tracing::Event<'_>) {
+                event.record(&mut self.clone());
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let capture = Capture::default();
+        let client = Client::new().with_transport(
+            FixtureTransport::new().respond_with("/summary", Response::new(200, "{}")),
+        );
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        tracing::subscriber::with_default(capture.clone(), || {
+            runtime.block_on(async {
+                let params = QueryParameters::new().limit(5);
+                let _: serde_json::Value = client.get("/summary", params).await.unwrap();
+                let _ = client
+                    .get::<serde_json::Value>("/details", QueryParameters::new())
+                    .await;
+            })
+        });
+
+        let fields = capture.0.lock().unwrap();
+        let values = |name: &str| -> Vec<&str> {
+            fields
+                .iter()
+                .filter(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+                .collect()
+        };
+        assert_eq!(values("endpoint"), ["\"/summary\"", "\"/details\""]);
+        assert_eq!(values("query")[0], "\"limit=5\"");
+        assert_eq!(values("status"), ["200", "404"]);
+        assert_eq!(values("body_size"), ["2"]);
+        assert_eq!(values("error_kind"), ["\"status\""]);
+        assert_eq!(values("latency_ms").len(), 2);
+    }
+}
@@ -0,0 +1,211 @@
+//! Onionoo Mirror Server
+//!
+//! This module serves previously stored Onionoo documents over HTTP, so that a
+//! [`Client`] can be pointed at a local snapshot instead of the public instance. It is
+//! available with the `mirror` feature.
+//!
+//! A snapshot is a directory containing one document per endpoint, named after the
+//! endpoint, e.g. `details.json` or `summary.json`. Endpoints without a document
+//! respond with `404 Not Found`. All query parameters are evaluated locally with the
+//! semantics of the Onionoo server; days ranges are relative to the snapshot's
+//! `relays_published` time, so results do not change as the snapshot ages. Responses
+//! are compressed with the encodings enabled by cargo features if the client accepts them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::task::JoinHandle;
+
+use crate::client::Client;
+use crate::encoding::ContentEncoding;
+use crate::endpoints::{Endpoint, helpers};
+use crate::serve::{self, Documents};
+
+/// Error type for the mirror server
+#[derive(Debug)]
+pub enum MirrorError {
+    /// Reading the snapshot or binding the listener failed
+    Io(io::Error),
+    /// A stored document is not valid JSON (file, error)
+    Document(PathBuf, serde_json::Error),
+    /// The snapshot directory contains no documents
+    EmptySnapshot(PathBuf),
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirrorError::Io(e) => write!(f, "I/O error: {}", e),
+            MirrorError::Document(path, e) => {
+                write!(f, "Invalid document {}: {}", path.display(), e)
+            }
+            MirrorError::EmptySnapshot(path) => {
+                write!(f, "No Onionoo documents found in {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MirrorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MirrorError::Io(e) => Some(e),
+            MirrorError::Document(_, e) => Some(e),
+            MirrorError::EmptySnapshot(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for MirrorError {
+    fn from(err: io::Error) -> Self {
+        MirrorError::Io(err)
+    }
+}
+
+/// Returns the file name a snapshot stores the endpoint's document under
+pub fn file_name(endpoint: &Endpoint) -> String {
+    format!("{}.json", endpoint.name())
+}
+
+/// Reads the documents of a snapshot directory
+fn load(dir: &Path) -> Result<Documents, MirrorError> {
+    let mut documents = HashMap::new();
+    for endpoint in helpers::all_endpoints() {
+        let path = dir.join(file_name(&endpoint));
+        let document = match std::fs::read_to_string(&path) {
+            Ok(document) => document,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let document: Value = match serde_json::from_str(&document) {
+            Ok(document) => document,
+            Err(e) => return Err(MirrorError::Document(path, e)),
+        };
+        documents.insert(endpoint.path(), document);
+    }
+
+    if documents.is_empty() {
+        return Err(MirrorError::EmptySnapshot(dir.to_path_buf()));
+    }
+    Ok(Documents::new(documents))
+}
+
+/// HTTP server for a snapshot directory
+///
+/// The server stops when the handle is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use onionoo::{QueryParameters, endpoints, mirror::MirrorServer};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let server = MirrorServer::bind("./snapshot", "127.0.0.1:8080").await?;
+///
+/// let params = QueryParameters::new().country("de").limit(10);
+/// let response = endpoints::details(&server.client(), params).await?;
+/// println!("{} relays", response.relays.len());
+/// # Ok(())
+/// # }
+/// ```
+pub struct MirrorServer {
+    dir: PathBuf,
+    address: SocketAddr,
+    base_url: Arc<str>,
+    documents: Arc<RwLock<Arc<Documents>>>,
+    task: JoinHandle<()>,
+}
+
+impl MirrorServer {
+    /// Load the snapshot in `dir` and start serving it on `address`
+    ///
+    /// Use port 0 to bind to a free port.
+    pub async fn bind<P: AsRef<Path>, A: ToSocketAddrs>(
+        dir: P,
+        address: A,
+    ) -> Result<Self, MirrorError> {
+        let dir = dir.as_ref().to_path_buf();
+        let documents = Arc::new(RwLock::new(Arc::new(load(&dir)?)));
+
+        let listener = TcpListener::bind(address).await?;
+        let address = listener.local_addr()?;
+
+        let task_documents = Arc::clone(&documents);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let documents = Arc::clone(&task_documents.read().unwrap());
+                tokio::spawn(async move {
+                    let _ = handle(stream, documents).await;
+                });
+            }
+        });
+
+        let base_url: Arc<str> = format!("http://{}", address).into();
+
+        Ok(Self {
+            dir,
+            address,
+            base_url,
+            documents,
+            task,
+        })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:8080`
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Create a client pointed at this server
+    pub fn client(&self) -> Client {
+        Client::new_with_base_url(Arc::clone(&self.base_url))
+    }
+
+    /// Re-read the snapshot directory
+    ///
+    /// Requests already in progress finish with the previous documents. If the snapshot
+    /// cannot be loaded, the server keeps serving the previous documents.
+    pub fn reload(&self) -> Result<(), MirrorError> {
+        let documents = load(&self.dir)?;
+        *self.documents.write().unwrap() = Arc::new(documents);
+        Ok(())
+    }
+
+    /// Serve until the listener fails
+    pub async fn wait(&mut self) {
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for MirrorServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle(mut stream: TcpStream, documents: Arc<Documents>) -> io::Result<()> {
+    let Some(request) = serve::read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let target = &request.target;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, body) = if request.method == "GET" {
+        documents.render(path, query)
+    } else {
+        (405, String::new())
+    };
+    let encoding = request.negotiate(&ContentEncoding::supported());
+    serve::write_response(&mut stream, status, &body, encoding).await
+}
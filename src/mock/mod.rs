@@ -0,0 +1,255 @@
+//! Mock Onionoo Server
+//!
+//! This module provides a local Onionoo-compatible HTTP server for tests and offline
+//! development. It is available with the `test-util` feature.
+//!
+//! The server serves fixture documents on the six endpoint paths and applies all query
+//! parameters locally, like the snapshot server in `mirror`. Failures such as error
+//! status codes, `304 Not Modified`, slow responses and malformed JSON can be scripted
+//! per endpoint.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::client::Client;
+use crate::encoding::ContentEncoding;
+use crate::endpoints::{Endpoint, helpers};
+use crate::serve::{self, Documents};
+
+/// Fixture documents served by default
+///
+/// The fixtures describe four relays and two bridges with consistent fingerprints
+/// across all six documents.
+pub mod fixtures {
+    use crate::endpoints::Endpoint;
+
+    pub const SUMMARY: &str = include_str!("fixtures/summary.json");
+    pub const DETAILS: &str = include_str!("fixtures/details.json");
+    pub const BANDWIDTH: &str = include_str!("fixtures/bandwidth.json");
+    pub const WEIGHTS: &str = include_str!("fixtures/weights.json");
+    pub const CLIENTS: &str = include_str!("fixtures/clients.json");
+    pub const UPTIME: &str = include_str!("fixtures/uptime.json");
+
+    /// Returns the default fixture document for an endpoint
+    pub fn for_endpoint(endpoint: &Endpoint) -> &'static str {
+        match endpoint {
+            Endpoint::Summary => SUMMARY,
+            Endpoint::Details => DETAILS,
+            Endpoint::Bandwidth => BANDWIDTH,
+            Endpoint::Weights => WEIGHTS,
+            Endpoint::Clients => CLIENTS,
+            Endpoint::Uptime => UPTIME,
+        }
+    }
+}
+
+/// Scripted failure returned instead of (or before) the fixture document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Respond with the given HTTP status code and an empty body
+    Status(u16),
+    /// Respond with `304 Not Modified`
+    NotModified,
+    /// Wait for the given duration, then respond normally
+    Delay(Duration),
+    /// Respond with `200 OK` and a truncated JSON document
+    MalformedJson,
+}
+
+/// State shared between the server handle and its connection tasks
+struct State {
+    documents: Documents,
+    encodings: Vec<ContentEncoding>,
+    faults: Mutex<HashMap<&'static str, VecDeque<Fault>>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl State {
+    fn next_fault(&self, path: &str) -> Option<Fault> {
+        self.faults
+            .lock()
+            .unwrap()
+            .get_mut(path)
+            .and_then(VecDeque::pop_front)
+    }
+}
+
+/// Builder for a [`MockServer`] with custom fixtures
+pub struct MockServerBuilder {
+    fixtures: HashMap<&'static str, String>,
+    encodings: Vec<ContentEncoding>,
+}
+
+impl MockServerBuilder {
+    /// Serve the given document on the endpoint's path instead of the default fixture
+    ///
+    /// The document must be valid JSON; [`start`](Self::start) fails otherwise.
+    pub fn fixture<T: Into<String>>(mut self, endpoint: Endpoint, document: T) -> Self {
+        self.fixtures.insert(endpoint.path(), document.into());
+        self
+    }
+
+    /// Stop serving the endpoint's path, so that requests return `404 Not Found`
+    pub fn without(mut self, endpoint: Endpoint) -> Self {
+        self.fixtures.remove(endpoint.path());
+        self
+    }
+
+    /// Compress responses with the first of these encodings that the client accepts
+    ///
+    /// By default responses are not compressed. Encodings whose cargo feature is
+    /// disabled are skipped.
+    pub fn encodings<I: IntoIterator<Item = ContentEncoding>>(mut self, encodings: I) -> Self {
+        self.encodings = encodings.into_iter().collect();
+        self
+    }
+
+    /// Bind to a free local port and start serving
+    pub async fn start(self) -> io::Result<MockServer> {
+        let documents = self
+            .fixtures
+            .into_iter()
+            .map(|(path, document)| Ok((path, serde_json::from_str(&document)?)))
+            .collect::<io::Result<_>>()?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(State {
+            documents: Documents::new(documents),
+            encodings: self.encodings,
+            faults: Mutex::default(),
+            requests: Mutex::default(),
+        });
+
+        let task_state = Arc::clone(&state);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = Arc::clone(&task_state);
+                tokio::spawn(async move {
+                    let _ = handle(stream, state).await;
+                });
+            }
+        });
+
+        let base_url: Arc<str> = format!("http://{}", address).into();
+
+        Ok(MockServer {
+            base_url,
+            state,
+            task,
+        })
+    }
+}
+
+/// Local Onionoo-compatible HTTP server
+///
+/// The server stops when the handle is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use onionoo::{QueryParameters, endpoints, mock::MockServer, selection::TypeValue};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let server = MockServer::start().await?;
+/// let client = server.client();
+///
+/// let params = QueryParameters::new().type_param(TypeValue::Relay).limit(2);
+/// let response = endpoints::summary(&client, params).await?;
+/// assert_eq!(response.relays.len(), 2);
+/// assert!(response.bridges.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub struct MockServer {
+    base_url: Arc<str>,
+    state: Arc<State>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start a server with the default fixtures on all six endpoints
+    pub async fn start() -> io::Result<Self> {
+        Self::builder().start().await
+    }
+
+    /// Create a builder preloaded with the default fixtures
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder {
+            fixtures: helpers::all_endpoints()
+                .iter()
+                .map(|e| (e.path(), fixtures::for_endpoint(e).to_string()))
+                .collect(),
+            encodings: Vec::new(),
+        }
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:40123`
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Create a client pointed at this server
+    pub fn client(&self) -> Client {
+        Client::new_with_base_url(Arc::clone(&self.base_url))
+    }
+
+    /// Queue a fault for the next request to the endpoint
+    ///
+    /// Faults are consumed in the order they were queued, one per request.
+    pub fn push_fault(&self, endpoint: Endpoint, fault: Fault) {
+        self.state
+            .faults
+            .lock()
+            .unwrap()
+            .entry(endpoint.path())
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// Request targets (path and query string) received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle(mut stream: TcpStream, state: Arc<State>) -> io::Result<()> {
+    let Some(request) = serve::read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let (method, target) = (&request.method, &request.target);
+    state.requests.lock().unwrap().push(target.clone());
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, body) = match state.next_fault(path) {
+        Some(Fault::Status(code)) => (code, String::new()),
+        Some(Fault::NotModified) => (304, String::new()),
+        Some(Fault::MalformedJson) => (200, r#"{"version":"8.0","relays":[{"n":"#.to_string()),
+        Some(Fault::Delay(duration)) => {
+            tokio::time::sleep(duration).await;
+            respond(&state, method, path, query)
+        }
+        None => respond(&state, method, path, query),
+    };
+
+    let encoding = request.negotiate(&state.encodings);
+    serve::write_response(&mut stream, status, &body, encoding).await
+}
+
+fn respond(state: &State, method: &str, path: &str, query: &str) -> (u16, String) {
+    if method != "GET" {
+        return (405, String::new());
+    }
+    state.documents.render(path, query)
+}
@@ -3,10 +3,16 @@
 //! This module contains all the Rust struct definitions for deserializing
 //! data from the Onionoo API endpoints based on the protocol specification.
 
+use chrono::{NaiveDateTime, TimeDelta};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parameters::{AsNumber, CountryCode, ParameterError, fields::Field};
+use crate::utils::parse_timestamp;
 
 /// Base response structure shared by all Onionoo endpoints
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OnionooResponse<T, U> {
     pub version: String,
     #[serde(rename = "next_major_version_scheduled")]
@@ -29,222 +35,345 @@ pub struct OnionooResponse<T, U> {
     pub bridges_truncated: Option<u64>,
 }
 
+/// Header fields of a document, without its relays and bridges
+///
+/// Returned when the relays and bridges are streamed one at a time, see
+/// [`Client::stream`](crate::Client::stream).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DocumentHeader {
+    pub version: String,
+    pub next_major_version_scheduled: Option<String>,
+    pub build_revision: Option<String>,
+    pub relays_published: String,
+    pub relays_skipped: Option<u64>,
+    pub relays_truncated: Option<u64>,
+    pub bridges_published: String,
+    pub bridges_skipped: Option<u64>,
+    pub bridges_truncated: Option<u64>,
+}
+
+/// Defines the relay and bridge objects of a document type and its field enum
+///
+/// Each field names the variant of the field enum it corresponds to, followed by the
+/// name used in documents in parentheses if it differs from the Rust field name. A bare
+/// variant is added to the enum; a path such as `DetailsField::Nickname` refers to a
+/// variant added by an earlier object. Objects given a second name after a `/` also
+/// get a partial variant of the struct, in which every field is optional.
+macro_rules! document_objects {
+    // Field enum, collecting the variants added by each field
+    (@enum $meta:tt $enum:ident [$($variants:tt)*]
+        $(#[$attr:meta])* $field:ident : $ty:ty => $path:ident :: $variant:ident,
+        $($rest:tt)*
+    ) => {
+        document_objects!(@enum $meta $enum [$($variants)*] $($rest)*);
+    };
+    (@enum $meta:tt $enum:ident [$($variants:tt)*]
+        $(#[$attr:meta])* $field:ident : $ty:ty => $variant:ident $(($name:literal))?,
+        $($rest:tt)*
+    ) => {
+        document_objects!(@enum $meta $enum [$($variants)* [$variant $field $($name)?]] $($rest)*);
+    };
+    (@enum [$($meta:tt)*] $enum:ident [$([$variant:ident $field:ident $($name:literal)?])*]) => {
+        $($meta)*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $enum {
+            $($variant,)*
+        }
+
+        impl Field for $enum {
+            const ALL: &'static [Self] = &[$($enum::$variant,)*];
+
+            fn as_str(&self) -> &'static str {
+                match self {
+                    $($enum::$variant => document_objects!(@name $field $($name)?),)*
+                }
+            }
+        }
+
+        impl fmt::Display for $enum {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        /// Parses a field name, ignoring case as the Onionoo server does
+        impl FromStr for $enum {
+            type Err = ParameterError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .find(|field| field.as_str().eq_ignore_ascii_case(s.trim()))
+                    .copied()
+                    .ok_or_else(|| {
+                        ParameterError::InvalidValue("fields".to_string(), s.to_string())
+                    })
+            }
+        }
+    };
+    (@name $field:ident $name:literal) => { $name };
+    (@name $field:ident) => { stringify!($field) };
+
+    // Object structs, collecting the fields of the full and the partial struct
+    (@object $head:tt [$($full:tt)*] [$($part:tt)*] [$($fields:tt)*]
+        $(#[$attr:meta])* $field:ident : Option<$ty:ty> => $($variant:ident)::+
+        $(($name:literal))?, $($rest:tt)*
+    ) => {
+        document_objects!(@object $head
+            [$($full)* $(#[$attr])* $(#[serde(rename = $name)])? pub $field: Option<$ty>,]
+            [$($part)* $(#[$attr])* $(#[serde(rename = $name)])? pub $field: Option<$ty>,]
+            [$($fields)* [$($variant)::+]]
+            $($rest)*);
+    };
+    (@object $head:tt [$($full:tt)*] [$($part:tt)*] [$($fields:tt)*]
+        $(#[$attr:meta])* $field:ident : $ty:ty => $($variant:ident)::+
+        $(($name:literal))?, $($rest:tt)*
+    ) => {
+        document_objects!(@object $head
+            [$($full)* $(#[$attr])* $(#[serde(rename = $name)])? pub $field: $ty,]
+            [$($part)* $(#[$attr])* $(#[serde(rename = $name)])? pub $field: Option<$ty>,]
+            [$($fields)* [$($variant)::+]]
+            $($rest)*);
+    };
+    (@object [$enum:ident [$($meta:tt)*] $name:ident []]
+        [$($full:tt)*] $part:tt [$([$($variant:tt)*])*]
+    ) => {
+        $($meta)*
+        #[derive(Debug, Clone, Deserialize, Serialize)]
+        pub struct $name {
+            $($full)*
+        }
+
+        impl $name {
+            /// Fields of the object, in document order
+            pub const FIELDS: &'static [$enum] =
+                &[$(document_objects!(@variant $enum $($variant)*),)*];
+        }
+    };
+    (@object [$enum:ident $meta:tt $name:ident [$partial:ident]]
+        $full:tt [$($part:tt)*] $fields:tt
+    ) => {
+        document_objects!(@object [$enum $meta $name []] $full [] $fields);
+
+        #[doc = concat!("[`", stringify!($name), "`] with only the requested fields")]
+        ///
+        /// Every field is optional, so that documents requested with the `fields`
+        /// parameter can be deserialized.
+        #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+        pub struct $partial {
+            $($part)*
+        }
+
+        impl $partial {
+            /// Fields of the object, in document order
+            pub const FIELDS: &'static [$enum] = $name::FIELDS;
+        }
+    };
+    (@variant $enum:ident $path:ident :: $variant:ident) => { $path::$variant };
+    (@variant $enum:ident $variant:ident) => { $enum::$variant };
+
+    (
+        $(#[$enum_meta:meta])*
+        enum $enum:ident;
+        $(
+            $(#[$meta:meta])*
+            struct $name:ident $(/ $partial:ident)? { $($body:tt)* }
+        )+
+    ) => {
+        document_objects!(@enum [$(#[$enum_meta])*] $enum [] $($($body)*)+);
+        $(
+            document_objects!(
+                @object [$enum [$(#[$meta])*] $name [$($partial)?]] [] [] [] $($body)*
+            );
+        )+
+    };
+}
+
 // ==================== SUMMARY DOCUMENTS ====================
 /// Summary document response type
 pub type SummaryResponse = OnionooResponse<RelaySummary, BridgeSummary>;
 
-/// Relay summary object (Summary endpoint)
-/// Contains short summaries with nicknames, fingerprints, IP addresses, and running information
-#[derive(Debug, Deserialize, Serialize)]
-pub struct RelaySummary {
-    /// Relay nickname consisting of 1–19 alphanumerical characters
-    pub n: String,
-    /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
-    pub f: String,
-    /// Array of IPv4 or IPv6 addresses where the relay accepts onion-routing connections
-    pub a: Vec<String>,
-    /// Boolean field saying whether this relay was listed as running in the last relay network status consensus
-    pub r: bool,
-}
+document_objects! {
+    /// Fields of summary documents (`n`, `f` and `a` for relays, `n`, `h` and `r` for bridges)
+    enum SummaryField;
 
-/// Bridge summary object (Summary endpoint)
-/// Contains bridges with hashed fingerprints and running information
-#[derive(Debug, Deserialize, Serialize)]
-pub struct BridgeSummary {
-    /// Bridge nickname consisting of 1–19 alphanumerical characters
-    pub n: String,
-    /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
-    pub h: String,
-    /// Boolean field saying whether this bridge was successfully tested by bridgestrap
-    pub r: bool,
+    /// Relay summary object (Summary endpoint)
+    /// Contains short summaries with nicknames, fingerprints, IP addresses, and running information
+    struct RelaySummary {
+        /// Relay nickname consisting of 1–19 alphanumerical characters
+        n: String => Nickname,
+        /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
+        f: String => Fingerprint,
+        /// Array of IPv4 or IPv6 addresses where the relay accepts onion-routing connections
+        a: Vec<String> => Addresses,
+        /// Boolean field saying whether this relay was listed as running in the last relay network status consensus
+        r: bool => Running,
+    }
+
+    /// Bridge summary object (Summary endpoint)
+    /// Contains bridges with hashed fingerprints and running information
+    struct BridgeSummary {
+        /// Bridge nickname consisting of 1–19 alphanumerical characters
+        n: String => SummaryField::Nickname,
+        /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
+        h: String => HashedFingerprint,
+        /// Boolean field saying whether this bridge was successfully tested by bridgestrap
+        r: bool => SummaryField::Running,
+    }
 }
 
 // ==================== DETAILS DOCUMENTS ====================
 /// Details document response type
 pub type DetailsResponse = OnionooResponse<RelayDetails, BridgeDetails>;
 
-/// Relay details object (Details endpoint)
-/// Based on network statuses, server descriptors, and TorDNSEL data
-#[derive(Debug, Deserialize, Serialize)]
-pub struct RelayDetails {
-    /// Relay nickname consisting of 1–19 alphanumerical characters
-    pub nickname: String,
-    /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// Array of IPv4 or IPv6 addresses and TCP ports where the relay accepts onion-routing connections
-    #[serde(rename = "or_addresses")]
-    pub or_addresses: Vec<String>,
-    /// Array of IPv4 addresses that the relay used to exit to the Internet in the past 24 hours
-    #[serde(rename = "exit_addresses")]
-    pub exit_addresses: Option<Vec<String>>,
-    /// IPv4 address and TCP port where the relay accepts directory connections
-    #[serde(rename = "dir_address")]
-    pub dir_address: Option<String>,
-    /// UTC timestamp when this relay was last seen in a network status consensus
-    #[serde(rename = "last_seen")]
-    pub last_seen: String,
-    /// UTC timestamp when this relay last stopped announcing an IPv4 or IPv6 address or TCP port
-    #[serde(rename = "last_changed_address_or_port")]
-    pub last_changed_address_or_port: String,
-    /// UTC timestamp when this relay was first seen in a network status consensus
-    #[serde(rename = "first_seen")]
-    pub first_seen: String,
-    /// Boolean field saying whether this relay was listed as running in the last relay network status consensus
-    pub running: bool,
-    /// Boolean field saying whether this relay indicated that it is hibernating
-    pub hibernating: Option<bool>,
-    /// Array of relay flags that the directory authorities assigned to this relay
-    pub flags: Option<Vec<String>>,
-    /// Two-letter lower-case country code as found in a GeoIP database
-    pub country: Option<String>,
-    /// Country name as found in a GeoIP database
-    #[serde(rename = "country_name")]
-    pub country_name: Option<String>,
-    /// Region name as found in a GeoIP database
-    #[serde(rename = "region_name")]
-    pub region_name: Option<String>,
-    /// City name as found in a GeoIP database
-    #[serde(rename = "city_name")]
-    pub city_name: Option<String>,
-    /// Latitude as found in a GeoIP database
-    pub latitude: Option<f64>,
-    /// Longitude as found in a GeoIP database
-    pub longitude: Option<f64>,
-    /// AS number as found in an AS database
-    #[serde(rename = "as")]
-    pub as_number: Option<String>,
-    /// AS name as found in an AS database
-    #[serde(rename = "as_name")]
-    pub as_name: Option<String>,
-    /// Weight assigned to this relay by the directory authorities
-    #[serde(rename = "consensus_weight")]
-    pub consensus_weight: u64,
-    /// Host names with verified A records
-    #[serde(rename = "verified_host_names")]
-    pub verified_host_names: Option<Vec<String>>,
-    /// Host names without verified A records
-    #[serde(rename = "unverified_host_names")]
-    pub unverified_host_names: Option<Vec<String>>,
-    /// UTC timestamp when the relay was last (re-)started
-    #[serde(rename = "last_restarted")]
-    pub last_restarted: Option<String>,
-    /// Average bandwidth in bytes per second that this relay is willing to sustain over long periods
-    #[serde(rename = "bandwidth_rate")]
-    pub bandwidth_rate: Option<u64>,
-    /// Bandwidth in bytes per second that this relay is willing to sustain in very short intervals
-    #[serde(rename = "bandwidth_burst")]
-    pub bandwidth_burst: Option<u64>,
-    /// Bandwidth estimate in bytes per second of the capacity this relay can handle
-    #[serde(rename = "observed_bandwidth")]
-    pub observed_bandwidth: Option<u64>,
-    /// Bandwidth in bytes per second that this relay is willing and capable to provide
-    #[serde(rename = "advertised_bandwidth")]
-    pub advertised_bandwidth: Option<u64>,
-    /// Timestamp indicating relay has reached an overloaded state
-    #[serde(rename = "overload_general_timestamp")]
-    pub overload_general_timestamp: Option<u64>,
-    /// Array of exit-policy lines
-    #[serde(rename = "exit_policy")]
-    pub exit_policy: Option<Vec<String>>,
-    /// Summary version of the relay's exit policy
-    #[serde(rename = "exit_policy_summary")]
-    pub exit_policy_summary: Option<ExitPolicySummary>,
-    /// Summary version of the relay's IPv6 exit policy
-    #[serde(rename = "exit_policy_v6_summary")]
-    pub exit_policy_v6_summary: Option<ExitPolicySummary>,
-    /// Contact address of the relay operator
-    pub contact: Option<String>,
-    /// Platform string containing operating system and Tor version details
-    pub platform: Option<String>,
-    /// Tor software version without leading "Tor"
-    pub version: Option<String>,
-    /// Boolean field saying whether the Tor software version of this relay is recommended
-    #[serde(rename = "recommended_version")]
-    pub recommended_version: Option<bool>,
-    /// Status of the Tor software version of this relay
-    #[serde(rename = "version_status")]
-    pub version_status: Option<String>,
-    /// Array of fingerprints of relays that are in an effective, mutual family relationship
-    #[serde(rename = "effective_family")]
-    pub effective_family: Option<Vec<String>>,
-    /// Array of fingerprints of relays that are not in an effective, mutual family relationship
-    #[serde(rename = "alleged_family")]
-    pub alleged_family: Option<Vec<String>>,
-    /// Array of fingerprints of relays that can be reached by following effective family relationships
-    #[serde(rename = "indirect_family")]
-    pub indirect_family: Option<Vec<String>>,
-    /// Fraction of this relay's consensus weight compared to the sum of all consensus weights
-    #[serde(rename = "consensus_weight_fraction")]
-    pub consensus_weight_fraction: Option<f64>,
-    /// Probability of this relay to be selected for the guard position
-    #[serde(rename = "guard_probability")]
-    pub guard_probability: Option<f64>,
-    /// Probability of this relay to be selected for the middle position
-    #[serde(rename = "middle_probability")]
-    pub middle_probability: Option<f64>,
-    /// Probability of this relay to be selected for the exit position
-    #[serde(rename = "exit_probability")]
-    pub exit_probability: Option<f64>,
-    /// Boolean field saying whether the consensus weight of this relay is based on measurements
-    pub measured: Option<bool>,
-    /// Array of addresses that the relay claims to accept but that are unreachable
-    #[serde(rename = "unreachable_or_addresses")]
-    pub unreachable_or_addresses: Option<Vec<String>>,
-}
+/// Details document response type for requests with the `fields` parameter
+///
+/// Every field of the relay and bridge objects is optional, so that documents
+/// containing only the requested fields can be deserialized.
+pub type PartialDetailsResponse = OnionooResponse<PartialRelayDetails, PartialBridgeDetails>;
 
-/// Bridge details object (Details endpoint)
-#[derive(Debug, Deserialize, Serialize)]
-pub struct BridgeDetails {
-    /// Bridge nickname consisting of 1–19 alphanumerical characters
-    pub nickname: String,
-    /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
-    #[serde(rename = "hashed_fingerprint")]
-    pub hashed_fingerprint: String,
-    /// Array of sanitized IPv4 or IPv6 addresses and TCP ports where the bridge accepts connections
-    #[serde(rename = "or_addresses")]
-    pub or_addresses: Vec<String>,
-    /// UTC timestamp when this bridge was last seen in a bridge network status
-    #[serde(rename = "last_seen")]
-    pub last_seen: String,
-    /// UTC timestamp when this bridge was first seen in a bridge network status
-    #[serde(rename = "first_seen")]
-    pub first_seen: String,
-    /// Boolean field saying whether this bridge was successfully tested by bridgestrap
-    pub running: bool,
-    /// Array of relay flags that the bridge authority assigned to this bridge
-    pub flags: Option<Vec<String>>,
-    /// UTC timestamp when the bridge was last (re-)started
-    #[serde(rename = "last_restarted")]
-    pub last_restarted: Option<String>,
-    /// Bandwidth in bytes per second that this bridge is willing and capable to provide
-    #[serde(rename = "advertised_bandwidth")]
-    pub advertised_bandwidth: Option<u64>,
-    /// Timestamp indicating bridge has reached an overloaded state
-    #[serde(rename = "overload_general_timestamp")]
-    pub overload_general_timestamp: Option<u64>,
-    /// Platform string containing operating system and Tor version details
-    pub platform: Option<String>,
-    /// Tor software version without leading "Tor"
-    pub version: Option<String>,
-    /// Boolean field saying whether the Tor software version of this bridge is recommended
-    #[serde(rename = "recommended_version")]
-    pub recommended_version: Option<bool>,
-    /// Status of the Tor software version of this bridge
-    #[serde(rename = "version_status")]
-    pub version_status: Option<String>,
-    /// Array of (pluggable) transport names supported by this bridge
-    pub transports: Option<Vec<String>>,
-    /// Array of country codes where this bridge is not served because it is believed to be blocked
-    pub blocklist: Option<Vec<String>>,
-    /// BridgeDB distributor that this bridge is currently assigned to
-    #[serde(rename = "bridgedb_distributor")]
-    pub bridgedb_distributor: Option<String>,
-    /// Contact address of the bridge operator
-    pub contact: Option<String>,
+document_objects! {
+    /// Fields of relay and bridge details documents
+    enum DetailsField;
+
+    /// Relay details object (Details endpoint)
+    /// Based on network statuses, server descriptors, and TorDNSEL data
+    struct RelayDetails / PartialRelayDetails {
+        /// Relay nickname consisting of 1–19 alphanumerical characters
+        nickname: String => Nickname,
+        /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => Fingerprint,
+        /// Array of IPv4 or IPv6 addresses and TCP ports where the relay accepts onion-routing connections
+        or_addresses: Vec<String> => OrAddresses,
+        /// Array of IPv4 addresses that the relay used to exit to the Internet in the past 24 hours
+        exit_addresses: Option<Vec<String>> => ExitAddresses,
+        /// IPv4 address and TCP port where the relay accepts directory connections
+        dir_address: Option<String> => DirAddress,
+        /// UTC timestamp when this relay was last seen in a network status consensus
+        last_seen: String => LastSeen,
+        /// UTC timestamp when this relay last stopped announcing an IPv4 or IPv6 address or TCP port
+        last_changed_address_or_port: String => LastChangedAddressOrPort,
+        /// UTC timestamp when this relay was first seen in a network status consensus
+        first_seen: String => FirstSeen,
+        /// Boolean field saying whether this relay was listed as running in the last relay network status consensus
+        running: bool => Running,
+        /// Boolean field saying whether this relay indicated that it is hibernating
+        hibernating: Option<bool> => Hibernating,
+        /// Array of relay flags that the directory authorities assigned to this relay
+        flags: Option<Vec<String>> => Flags,
+        /// Two-letter lower-case country code as found in a GeoIP database
+        country: Option<CountryCode> => Country,
+        /// Country name as found in a GeoIP database
+        country_name: Option<String> => CountryName,
+        /// Region name as found in a GeoIP database
+        region_name: Option<String> => RegionName,
+        /// City name as found in a GeoIP database
+        city_name: Option<String> => CityName,
+        /// Latitude as found in a GeoIP database
+        latitude: Option<f64> => Latitude,
+        /// Longitude as found in a GeoIP database
+        longitude: Option<f64> => Longitude,
+        /// AS number as found in an AS database
+        as_number: Option<AsNumber> => As("as"),
+        /// AS name as found in an AS database
+        as_name: Option<String> => AsName,
+        /// Weight assigned to this relay by the directory authorities
+        consensus_weight: u64 => ConsensusWeight,
+        /// Host names with verified A records
+        verified_host_names: Option<Vec<String>> => VerifiedHostNames,
+        /// Host names without verified A records
+        unverified_host_names: Option<Vec<String>> => UnverifiedHostNames,
+        /// UTC timestamp when the relay was last (re-)started
+        last_restarted: Option<String> => LastRestarted,
+        /// Average bandwidth in bytes per second that this relay is willing to sustain over long periods
+        bandwidth_rate: Option<u64> => BandwidthRate,
+        /// Bandwidth in bytes per second that this relay is willing to sustain in very short intervals
+        bandwidth_burst: Option<u64> => BandwidthBurst,
+        /// Bandwidth estimate in bytes per second of the capacity this relay can handle
+        observed_bandwidth: Option<u64> => ObservedBandwidth,
+        /// Bandwidth in bytes per second that this relay is willing and capable to provide
+        advertised_bandwidth: Option<u64> => AdvertisedBandwidth,
+        /// Timestamp indicating relay has reached an overloaded state
+        overload_general_timestamp: Option<u64> => OverloadGeneralTimestamp,
+        /// Array of exit-policy lines
+        exit_policy: Option<Vec<String>> => ExitPolicy,
+        /// Summary version of the relay's exit policy
+        exit_policy_summary: Option<ExitPolicySummary> => ExitPolicySummary,
+        /// Summary version of the relay's IPv6 exit policy
+        exit_policy_v6_summary: Option<ExitPolicySummary> => ExitPolicyV6Summary,
+        /// Contact address of the relay operator
+        contact: Option<String> => Contact,
+        /// Platform string containing operating system and Tor version details
+        platform: Option<String> => Platform,
+        /// Tor software version without leading "Tor"
+        version: Option<String> => Version,
+        /// Boolean field saying whether the Tor software version of this relay is recommended
+        recommended_version: Option<bool> => RecommendedVersion,
+        /// Status of the Tor software version of this relay
+        version_status: Option<String> => VersionStatus,
+        /// Array of fingerprints of relays that are in an effective, mutual family relationship
+        effective_family: Option<Vec<String>> => EffectiveFamily,
+        /// Array of fingerprints of relays that are not in an effective, mutual family relationship
+        alleged_family: Option<Vec<String>> => AllegedFamily,
+        /// Array of fingerprints of relays that can be reached by following effective family relationships
+        indirect_family: Option<Vec<String>> => IndirectFamily,
+        /// Fraction of this relay's consensus weight compared to the sum of all consensus weights
+        consensus_weight_fraction: Option<f64> => ConsensusWeightFraction,
+        /// Probability of this relay to be selected for the guard position
+        guard_probability: Option<f64> => GuardProbability,
+        /// Probability of this relay to be selected for the middle position
+        middle_probability: Option<f64> => MiddleProbability,
+        /// Probability of this relay to be selected for the exit position
+        exit_probability: Option<f64> => ExitProbability,
+        /// Boolean field saying whether the consensus weight of this relay is based on measurements
+        measured: Option<bool> => Measured,
+        /// Array of addresses that the relay claims to accept but that are unreachable
+        unreachable_or_addresses: Option<Vec<String>> => UnreachableOrAddresses,
+    }
+
+    /// Bridge details object (Details endpoint)
+    struct BridgeDetails / PartialBridgeDetails {
+        /// Bridge nickname consisting of 1–19 alphanumerical characters
+        nickname: String => DetailsField::Nickname,
+        /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
+        hashed_fingerprint: String => HashedFingerprint,
+        /// Array of sanitized IPv4 or IPv6 addresses and TCP ports where the bridge accepts connections
+        or_addresses: Vec<String> => DetailsField::OrAddresses,
+        /// UTC timestamp when this bridge was last seen in a bridge network status
+        last_seen: String => DetailsField::LastSeen,
+        /// UTC timestamp when this bridge was first seen in a bridge network status
+        first_seen: String => DetailsField::FirstSeen,
+        /// Boolean field saying whether this bridge was successfully tested by bridgestrap
+        running: bool => DetailsField::Running,
+        /// Array of relay flags that the bridge authority assigned to this bridge
+        flags: Option<Vec<String>> => DetailsField::Flags,
+        /// UTC timestamp when the bridge was last (re-)started
+        last_restarted: Option<String> => DetailsField::LastRestarted,
+        /// Bandwidth in bytes per second that this bridge is willing and capable to provide
+        advertised_bandwidth: Option<u64> => DetailsField::AdvertisedBandwidth,
+        /// Timestamp indicating bridge has reached an overloaded state
+        overload_general_timestamp: Option<u64> => DetailsField::OverloadGeneralTimestamp,
+        /// Platform string containing operating system and Tor version details
+        platform: Option<String> => DetailsField::Platform,
+        /// Tor software version without leading "Tor"
+        version: Option<String> => DetailsField::Version,
+        /// Boolean field saying whether the Tor software version of this bridge is recommended
+        recommended_version: Option<bool> => DetailsField::RecommendedVersion,
+        /// Status of the Tor software version of this bridge
+        version_status: Option<String> => DetailsField::VersionStatus,
+        /// Array of (pluggable) transport names supported by this bridge
+        transports: Option<Vec<String>> => Transports,
+        /// Array of country codes where this bridge is not served because it is believed to be blocked
+        blocklist: Option<Vec<String>> => Blocklist,
+        /// BridgeDB distributor that this bridge is currently assigned to
+        bridgedb_distributor: Option<String> => BridgedbDistributor,
+        /// Contact address of the bridge operator
+        contact: Option<String> => DetailsField::Contact,
+    }
 }
 
 /// Summary version of exit policy
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ExitPolicySummary {
     pub accept: Option<Vec<String>>,
     pub reject: Option<Vec<String>>,
@@ -254,47 +383,42 @@ pub struct ExitPolicySummary {
 /// Bandwidth document response type
 pub type BandwidthResponse = OnionooResponse<RelayBandwidth, BridgeBandwidth>;
 
-/// Relay bandwidth object (Bandwidth endpoint)
-/// Contains aggregate statistics of a relay's consumed bandwidth for different time intervals
-#[derive(Debug, Deserialize, Serialize)]
-pub struct RelayBandwidth {
-    /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// Object containing graph history objects with written bytes for different time periods
-    #[serde(rename = "write_history")]
-    pub write_history: Option<BandwidthHistory>,
-    /// Object containing graph history objects with read bytes for different time periods
-    #[serde(rename = "read_history")]
-    pub read_history: Option<BandwidthHistory>,
-    /// JSON object containing the overload-ratelimits information for the relay
-    #[serde(rename = "overload_ratelimits")]
-    pub overload_ratelimits: Option<OverloadRatelimits>,
-    /// JSON object containing the overload-fd-exhausted information for the relay
-    #[serde(rename = "overload_fd_exhausted")]
-    pub overload_fd_exhausted: Option<OverloadFdExhausted>,
-}
+document_objects! {
+    /// Fields of relay and bridge bandwidth documents
+    enum BandwidthField;
 
-/// Bridge bandwidth object (Bandwidth endpoint)
-#[derive(Debug, Deserialize, Serialize)]
-pub struct BridgeBandwidth {
-    /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// Object containing graph history objects with written bytes for different time periods
-    #[serde(rename = "write_history")]
-    pub write_history: Option<BandwidthHistory>,
-    /// Object containing graph history objects with read bytes for different time periods
-    #[serde(rename = "read_history")]
-    pub read_history: Option<BandwidthHistory>,
-    /// JSON object containing the overload-ratelimits information for the bridge
-    #[serde(rename = "overload_ratelimits")]
-    pub overload_ratelimits: Option<OverloadRatelimits>,
-    /// JSON object containing the overload-fd-exhausted information for the bridge
-    #[serde(rename = "overload_fd_exhausted")]
-    pub overload_fd_exhausted: Option<OverloadFdExhausted>,
+    /// Relay bandwidth object (Bandwidth endpoint)
+    /// Contains aggregate statistics of a relay's consumed bandwidth for different time intervals
+    struct RelayBandwidth {
+        /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => Fingerprint,
+        /// Object containing graph history objects with written bytes for different time periods
+        write_history: Option<BandwidthHistory> => WriteHistory,
+        /// Object containing graph history objects with read bytes for different time periods
+        read_history: Option<BandwidthHistory> => ReadHistory,
+        /// JSON object containing the overload-ratelimits information for the relay
+        overload_ratelimits: Option<OverloadRatelimits> => OverloadRatelimits,
+        /// JSON object containing the overload-fd-exhausted information for the relay
+        overload_fd_exhausted: Option<OverloadFdExhausted> => OverloadFdExhausted,
+    }
+
+    /// Bridge bandwidth object (Bandwidth endpoint)
+    struct BridgeBandwidth {
+        /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => BandwidthField::Fingerprint,
+        /// Object containing graph history objects with written bytes for different time periods
+        write_history: Option<BandwidthHistory> => BandwidthField::WriteHistory,
+        /// Object containing graph history objects with read bytes for different time periods
+        read_history: Option<BandwidthHistory> => BandwidthField::ReadHistory,
+        /// JSON object containing the overload-ratelimits information for the bridge
+        overload_ratelimits: Option<OverloadRatelimits> => BandwidthField::OverloadRatelimits,
+        /// JSON object containing the overload-fd-exhausted information for the bridge
+        overload_fd_exhausted: Option<OverloadFdExhausted> => BandwidthField::OverloadFdExhausted,
+    }
 }
 
 /// Bandwidth history for different time periods
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct BandwidthHistory {
     /// History for 1 month
     #[serde(rename = "1_month")]
@@ -311,7 +435,7 @@ pub struct BandwidthHistory {
 }
 
 /// Overload ratelimits information
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OverloadRatelimits {
     pub timestamp: Option<u64>,
     #[serde(rename = "rate-limit")]
@@ -325,7 +449,7 @@ pub struct OverloadRatelimits {
 }
 
 /// Overload file descriptor exhausted information
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OverloadFdExhausted {
     pub timestamp: Option<u64>,
 }
@@ -334,31 +458,30 @@ pub struct OverloadFdExhausted {
 /// Weights document response type (relays only)
 pub type WeightsResponse = OnionooResponse<RelayWeights, ()>;
 
-/// Relay weights object (Weights endpoint)
-/// Contains aggregate statistics of a relay's probability to be selected by clients for building paths
-#[derive(Debug, Deserialize, Serialize)]
-pub struct RelayWeights {
-    /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// History object containing the fraction of this relay's consensus weight
-    #[serde(rename = "consensus_weight_fraction")]
-    pub consensus_weight_fraction: Option<WeightHistory>,
-    /// History object containing the probability of this relay to be selected for the guard position
-    #[serde(rename = "guard_probability")]
-    pub guard_probability: Option<WeightHistory>,
-    /// History object containing the probability of this relay to be selected for the middle position
-    #[serde(rename = "middle_probability")]
-    pub middle_probability: Option<WeightHistory>,
-    /// History object containing the probability of this relay to be selected for the exit position
-    #[serde(rename = "exit_probability")]
-    pub exit_probability: Option<WeightHistory>,
-    /// History object containing the absolute consensus weight of this relay
-    #[serde(rename = "consensus_weight")]
-    pub consensus_weight: Option<WeightHistory>,
+document_objects! {
+    /// Fields of relay weights documents
+    enum WeightsField;
+
+    /// Relay weights object (Weights endpoint)
+    /// Contains aggregate statistics of a relay's probability to be selected by clients for building paths
+    struct RelayWeights {
+        /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => Fingerprint,
+        /// History object containing the fraction of this relay's consensus weight
+        consensus_weight_fraction: Option<WeightHistory> => ConsensusWeightFraction,
+        /// History object containing the probability of this relay to be selected for the guard position
+        guard_probability: Option<WeightHistory> => GuardProbability,
+        /// History object containing the probability of this relay to be selected for the middle position
+        middle_probability: Option<WeightHistory> => MiddleProbability,
+        /// History object containing the probability of this relay to be selected for the exit position
+        exit_probability: Option<WeightHistory> => ExitProbability,
+        /// History object containing the absolute consensus weight of this relay
+        consensus_weight: Option<WeightHistory> => ConsensusWeight,
+    }
 }
 
 /// Weight history for different time periods
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WeightHistory {
     /// History for 1 month
     #[serde(rename = "1_month")]
@@ -378,19 +501,22 @@ pub struct WeightHistory {
 /// Clients document response type (bridges only)
 pub type ClientsResponse = OnionooResponse<(), BridgeClients>;
 
-/// Bridge clients object (Clients endpoint)
-/// Contains estimates of the average number of clients connecting to a bridge every day
-#[derive(Debug, Deserialize, Serialize)]
-pub struct BridgeClients {
-    /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// Object containing graph history objects with the average number of clients connecting to this bridge
-    #[serde(rename = "average_clients")]
-    pub average_clients: Option<ClientsHistory>,
+document_objects! {
+    /// Fields of bridge clients documents
+    enum ClientsField;
+
+    /// Bridge clients object (Clients endpoint)
+    /// Contains estimates of the average number of clients connecting to a bridge every day
+    struct BridgeClients {
+        /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => Fingerprint,
+        /// Object containing graph history objects with the average number of clients connecting to this bridge
+        average_clients: Option<ClientsHistory> => AverageClients,
+    }
 }
 
 /// Client history for different time periods
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ClientsHistory {
     /// History for 1 month
     #[serde(rename = "1_month")]
@@ -410,30 +536,33 @@ pub struct ClientsHistory {
 /// Uptime document response type
 pub type UptimeResponse = OnionooResponse<RelayUptime, BridgeUptime>;
 
-/// Relay uptime object (Uptime endpoint)
-/// Contains fractional uptimes of relays
-#[derive(Debug, Deserialize, Serialize)]
-pub struct RelayUptime {
-    /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// Object containing graph history objects with the fractional uptime of this relay
-    pub uptime: Option<UptimeHistory>,
-    /// Object containing fractional times of this relay having relay flags assigned
-    pub flags: Option<FlagHistory>,
-}
+document_objects! {
+    /// Fields of relay and bridge uptime documents
+    enum UptimeField;
 
-/// Bridge uptime object (Uptime endpoint)
-/// Contains fractional uptimes of bridges
-#[derive(Debug, Deserialize, Serialize)]
-pub struct BridgeUptime {
-    /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
-    pub fingerprint: String,
-    /// Uptime history objects for different time periods
-    pub uptime: Option<UptimeHistory>,
+    /// Relay uptime object (Uptime endpoint)
+    /// Contains fractional uptimes of relays
+    struct RelayUptime {
+        /// Relay fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => Fingerprint,
+        /// Object containing graph history objects with the fractional uptime of this relay
+        uptime: Option<UptimeHistory> => Uptime,
+        /// Object containing fractional times of this relay having relay flags assigned
+        flags: Option<FlagHistory> => Flags,
+    }
+
+    /// Bridge uptime object (Uptime endpoint)
+    /// Contains fractional uptimes of bridges
+    struct BridgeUptime {
+        /// SHA-1 hash of the bridge fingerprint consisting of 40 upper-case hexadecimal characters
+        fingerprint: String => UptimeField::Fingerprint,
+        /// Uptime history objects for different time periods
+        uptime: Option<UptimeHistory> => UptimeField::Uptime,
+    }
 }
 
 /// Uptime history for different time periods
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UptimeHistory {
     /// History for 1 month
     #[serde(rename = "1_month")]
@@ -450,7 +579,7 @@ pub struct UptimeHistory {
 }
 
 /// Flag history for different time periods
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FlagHistory {
     #[serde(flatten)]
     pub flags: std::collections::HashMap<String, UptimeHistory>,
@@ -458,7 +587,7 @@ pub struct FlagHistory {
 
 // ==================== GRAPH HISTORY OBJECTS ====================
 /// Graph history objects contained in bandwidth, weights, clients, and uptime documents
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GraphHistory {
     /// UTC timestamp of the first data point
     pub first: String,
@@ -473,3 +602,163 @@ pub struct GraphHistory {
     /// Array of normalized values between 0 and 999
     pub values: Vec<Option<f64>>,
 }
+
+impl GraphHistory {
+    /// Most recent data point multiplied by `factor`, skipping trailing gaps
+    pub fn latest(&self) -> Option<f64> {
+        self.values
+            .iter()
+            .rev()
+            .find_map(|value| *value)
+            .map(|value| value * self.factor)
+    }
+
+    /// Data points with their UTC timestamps and values multiplied by `factor`
+    ///
+    /// Gaps are kept as `None`. Returns no points if `first` is not a valid timestamp,
+    /// and stops at the first point whose timestamp is out of range.
+    pub fn points(&self) -> Vec<(NaiveDateTime, Option<f64>)> {
+        let Some(first) = parse_timestamp(&self.first) else {
+            return Vec::new();
+        };
+        let Some(interval) = i64::try_from(self.interval)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+        else {
+            return Vec::new();
+        };
+        self.values
+            .iter()
+            .enumerate()
+            .map_while(|(index, value)| {
+                let offset = i32::try_from(index)
+                    .ok()
+                    .and_then(|index| interval.checked_mul(index))?;
+                let timestamp = first.checked_add_signed(offset)?;
+                Some((timestamp, value.map(|value| value * self.factor)))
+            })
+            .collect()
+    }
+}
+
+macro_rules! history_periods {
+    ($($name:ty),* $(,)?) => {
+        $(
+            impl $name {
+                /// Histories by period name as on the wire, e.g. `1_month`, finest first
+                pub fn periods(&self) -> [(&'static str, Option<&GraphHistory>); 4] {
+                    [
+                        ("1_month", self.one_month.as_ref()),
+                        ("6_months", self.six_months.as_ref()),
+                        ("1_year", self.one_year.as_ref()),
+                        ("5_years", self.five_years.as_ref()),
+                    ]
+                }
+
+                /// Most recent data point of the finest-grained history that has one
+                pub fn latest(&self) -> Option<f64> {
+                    self.periods()
+                        .into_iter()
+                        .filter_map(|(_, history)| history)
+                        .find_map(GraphHistory::latest)
+                }
+            }
+        )*
+    };
+}
+
+history_periods!(
+    BandwidthHistory,
+    WeightHistory,
+    ClientsHistory,
+    UptimeHistory
+);
+
+// ==================== FINGERPRINTS ====================
+/// Relay or bridge object identified by a fingerprint
+///
+/// Relays are identified by their fingerprint and bridges by their hashed fingerprint.
+pub trait Fingerprinted {
+    /// Returns `None` if the object has no fingerprint, e.g. a partial details document
+    /// whose `fields` do not include it
+    fn fingerprint(&self) -> Option<&str>;
+}
+
+macro_rules! fingerprinted {
+    ($($name:ty => $field:ident),* $(,)?) => {
+        $(
+            impl Fingerprinted for $name {
+                fn fingerprint(&self) -> Option<&str> {
+                    Some(&self.$field)
+                }
+            }
+        )*
+    };
+}
+
+fingerprinted! {
+    RelaySummary => f,
+    BridgeSummary => h,
+    RelayDetails => fingerprint,
+    BridgeDetails => hashed_fingerprint,
+    RelayBandwidth => fingerprint,
+    BridgeBandwidth => fingerprint,
+    RelayWeights => fingerprint,
+    BridgeClients => fingerprint,
+    RelayUptime => fingerprint,
+    BridgeUptime => fingerprint,
+}
+
+impl Fingerprinted for PartialRelayDetails {
+    fn fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+}
+
+impl Fingerprinted for PartialBridgeDetails {
+    fn fingerprint(&self) -> Option<&str> {
+        self.hashed_fingerprint.as_deref()
+    }
+}
+
+/// Placeholder for the missing relays of clients documents and bridges of weights documents
+impl Fingerprinted for () {
+    fn fingerprint(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(interval: u64, values: usize) -> GraphHistory {
+        GraphHistory {
+            first: "2024-01-01 00:00:00".to_string(),
+            last: "2024-01-01 00:00:00".to_string(),
+            interval,
+            factor: 2.0,
+            count: None,
+            values: vec![Some(1.0); values],
+        }
+    }
+
+    #[test]
+    fn test_points() {
+        let points = history(3600, 3).points();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[2].0.to_string(), "2024-01-01 02:00:00");
+        assert_eq!(points[2].1, Some(2.0));
+    }
+
+    #[test]
+    fn test_points_out_of_range() {
+        assert!(history(u64::MAX, 3).points().is_empty());
+        // Fits a duration, but only the first point fits a timestamp
+        let points = history(i64::MAX as u64 / 1000, 3).points();
+        assert_eq!(points.len(), 1);
+        // 200,000 years: the third point is past the last representable date
+        let points = history(200_000 * 365 * 86400, 3).points();
+        assert_eq!(points.len(), 2);
+    }
+}
@@ -3,33 +3,67 @@
 //! This module provides structures and utilities for building query parameters
 //! for the Onionoo API.
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+/// Error returned when parsing query parameters
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterError {
+    /// The parameter name is not part of the Onionoo protocol
+    UnknownParameter(String),
+    /// The parameter value could not be parsed (name, value)
+    InvalidValue(String, String),
+    /// A days range is malformed or its minimum exceeds its maximum
+    InvalidDaysRange(String),
+    /// A date is not in yyyy-MM-dd format
+    InvalidDate(String),
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::UnknownParameter(name) => write!(f, "Unknown parameter: {}", name),
+            ParameterError::InvalidValue(name, value) => {
+                write!(f, "Invalid value for parameter {}: {}", name, value)
+            }
+            ParameterError::InvalidDaysRange(range) => write!(f, "Invalid days range: {}", range),
+            ParameterError::InvalidDate(date) => {
+                write!(f, "Invalid date (expected yyyy-MM-dd): {}", date)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
 
 /// Main structure for building query parameters
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct QueryParameters {
-    type_param: Option<TypeValue>,
-    running: Option<BoolValue>,
-    search: Option<String>,
-    lookup: Option<String>,
-    country: Option<String>,
-    as_param: Option<String>,
-    as_name: Option<String>,
-    flag: Option<String>,
-    first_seen_days: Option<DaysRange>,
-    last_seen_days: Option<DaysRange>,
-    first_seen_since: Option<Date>,
-    last_seen_since: Option<Date>,
-    contact: Option<String>,
-    family: Option<String>,
-    version: Option<VersionList>,
-    os: Option<String>,
-    host_name: Option<String>,
-    recommended_version: Option<BoolValue>,
-    fields: Option<FieldsList>,
-    order: Option<OrderList>,
-    offset: Option<u32>,
-    limit: Option<u32>,
+    pub(crate) type_param: Option<TypeValue>,
+    pub(crate) running: Option<BoolValue>,
+    pub(crate) search: Option<String>,
+    pub(crate) lookup: Option<String>,
+    pub(crate) country: Option<CountryCode>,
+    pub(crate) as_param: Option<AsList>,
+    pub(crate) as_name: Option<String>,
+    pub(crate) flag: Option<String>,
+    pub(crate) first_seen_days: Option<DaysRange>,
+    pub(crate) last_seen_days: Option<DaysRange>,
+    pub(crate) first_seen_since: Option<Date>,
+    pub(crate) last_seen_since: Option<Date>,
+    pub(crate) contact: Option<String>,
+    pub(crate) family: Option<String>,
+    pub(crate) version: Option<VersionList>,
+    pub(crate) os: Option<String>,
+    pub(crate) host_name: Option<String>,
+    pub(crate) recommended_version: Option<BoolValue>,
+    pub(crate) fields: Option<FieldsList>,
+    pub(crate) order: Option<OrderList>,
+    pub(crate) offset: Option<u32>,
+    pub(crate) limit: Option<u32>,
 }
 
 impl QueryParameters {
@@ -57,12 +91,14 @@ impl QueryParameters {
         self
     }
 
-    pub fn country<T: Into<String>>(mut self, country: T) -> Self {
+    /// Two-letter country code, normalized to lower case
+    pub fn country<T: Into<CountryCode>>(mut self, country: T) -> Self {
         self.country = Some(country.into());
         self
     }
 
-    pub fn as_param<T: Into<String>>(mut self, as_param: T) -> Self {
+    /// One or more AS numbers, e.g. `"AS3"`, `3` or an [`AsList`]
+    pub fn as_param<T: Into<AsList>>(mut self, as_param: T) -> Self {
         self.as_param = Some(as_param.into());
         self
     }
@@ -147,115 +183,351 @@ impl QueryParameters {
         self
     }
 
-    pub fn to_query_string(&self) -> String {
-        let mut params = HashMap::<String, String>::new();
-
-        if let Some(ref val) = self.type_param {
-            params.insert("type".to_string(), val.as_str().to_string());
-        }
-
-        if let Some(ref val) = self.running {
-            params.insert("running".to_string(), val.as_str().to_string());
-        }
-
-        if let Some(ref val) = self.search {
-            params.insert("search".to_string(), val.clone());
-        }
-
-        if let Some(ref val) = self.lookup {
-            params.insert("lookup".to_string(), val.clone());
-        }
-
-        if let Some(ref val) = self.country {
-            params.insert("country".to_string(), val.clone());
-        }
-
-        if let Some(ref val) = self.as_param {
-            params.insert("as".to_string(), val.clone());
-        }
-
-        if let Some(ref val) = self.as_name {
-            params.insert("as_name".to_string(), val.clone());
-        }
-
-        if let Some(ref val) = self.flag {
-            params.insert("flag".to_string(), val.clone());
-        }
-
-        if let Some(ref val) = self.first_seen_days {
-            params.insert("first_seen_days".to_string(), val.as_str().to_string());
-        }
-
-        if let Some(ref val) = self.last_seen_days {
-            params.insert("last_seen_days".to_string(), val.as_str().to_string());
+    /// Parse query parameters from a query string such as `?type=relay&limit=10`
+    ///
+    /// Values are percent-decoded. If a parameter is given more than once, only the
+    /// first value is considered. Negative `offset` and `limit` values are treated as zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::QueryParameters;
+    /// let params = QueryParameters::from_query_string("?as_name=Hurricane%20Electric&limit=5").unwrap();
+    /// assert_eq!(params.to_query_string(), "?as_name=Hurricane%20Electric&limit=5");
+    /// ```
+    pub fn from_query_string(query: &str) -> Result<Self, ParameterError> {
+        let mut params = Self::new();
+        let mut seen = Vec::new();
+
+        for pair in query.trim_start_matches('?').split('&') {
+            if pair.is_empty() {
+                continue;
+            }
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = percent_decode(name);
+            let value = percent_decode(value);
+            if seen.contains(&name) {
+                continue;
+            }
+            params.set(&name, value)?;
+            seen.push(name);
         }
 
-        if let Some(ref val) = self.first_seen_since {
-            params.insert("first_seen_since".to_string(), val.as_str().to_string());
-        }
+        Ok(params)
+    }
 
-        if let Some(ref val) = self.last_seen_since {
-            params.insert("last_seen_since".to_string(), val.as_str().to_string());
+    /// Set a parameter from its name and unencoded value
+    fn set(&mut self, name: &str, value: String) -> Result<(), ParameterError> {
+        let invalid = || ParameterError::InvalidValue(name.to_string(), value.clone());
+        match name {
+            "type" => {
+                self.type_param = Some(match value.to_lowercase().as_str() {
+                    "relay" => TypeValue::Relay,
+                    "bridge" => TypeValue::Bridge,
+                    _ => return Err(invalid()),
+                })
+            }
+            "running" => self.running = Some(parse_bool(&value).ok_or_else(invalid)?),
+            "search" => self.search = Some(value),
+            "lookup" => self.lookup = Some(value),
+            "country" => self.country = Some(CountryCode::from(value)),
+            "as" => self.as_param = Some(AsList(value)),
+            "as_name" => self.as_name = Some(value),
+            "flag" => self.flag = Some(value),
+            "first_seen_days" => self.first_seen_days = Some(DaysRange(value)),
+            "last_seen_days" => self.last_seen_days = Some(DaysRange(value)),
+            "first_seen_since" => self.first_seen_since = Some(Date(value)),
+            "last_seen_since" => self.last_seen_since = Some(Date(value)),
+            "contact" => self.contact = Some(value),
+            "family" => self.family = Some(value),
+            "version" => self.version = Some(VersionList(value)),
+            "os" => self.os = Some(value),
+            "host_name" => self.host_name = Some(value),
+            "recommended_version" => {
+                self.recommended_version = Some(parse_bool(&value).ok_or_else(invalid)?)
+            }
+            "fields" => self.fields = Some(FieldsList(value)),
+            "order" => self.order = Some(OrderList(value)),
+            "offset" => self.offset = Some(parse_count(&value).ok_or_else(invalid)?),
+            "limit" => self.limit = Some(parse_count(&value).ok_or_else(invalid)?),
+            _ => return Err(ParameterError::UnknownParameter(name.to_string())),
         }
+        Ok(())
+    }
 
-        if let Some(ref val) = self.contact {
-            params.insert("contact".to_string(), val.clone());
-        }
+    /// Parameter names and values, sorted by name
+    fn pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = [
+            ("type", self.type_param.map(|v| v.as_str().to_string())),
+            ("running", self.running.map(|v| v.as_str().to_string())),
+            ("search", self.search.clone()),
+            ("lookup", self.lookup.clone()),
+            (
+                "country",
+                self.country.as_ref().map(|v| v.as_str().to_string()),
+            ),
+            ("as", self.as_param.as_ref().map(|v| v.as_str().to_string())),
+            ("as_name", self.as_name.clone()),
+            ("flag", self.flag.clone()),
+            (
+                "first_seen_days",
+                self.first_seen_days
+                    .as_ref()
+                    .map(|v| v.as_str().to_string()),
+            ),
+            (
+                "last_seen_days",
+                self.last_seen_days.as_ref().map(|v| v.as_str().to_string()),
+            ),
+            (
+                "first_seen_since",
+                self.first_seen_since
+                    .as_ref()
+                    .map(|v| v.as_str().to_string()),
+            ),
+            (
+                "last_seen_since",
+                self.last_seen_since
+                    .as_ref()
+                    .map(|v| v.as_str().to_string()),
+            ),
+            ("contact", self.contact.clone()),
+            ("family", self.family.clone()),
+            (
+                "version",
+                self.version.as_ref().map(|v| v.as_str().to_string()),
+            ),
+            ("os", self.os.clone()),
+            ("host_name", self.host_name.clone()),
+            (
+                "recommended_version",
+                self.recommended_version.map(|v| v.as_str().to_string()),
+            ),
+            (
+                "fields",
+                self.fields.as_ref().map(|v| v.as_str().to_string()),
+            ),
+            ("order", self.order.as_ref().map(|v| v.as_str().to_string())),
+            ("offset", self.offset.map(|v| v.to_string())),
+            ("limit", self.limit.map(|v| v.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect::<Vec<_>>();
+        pairs.sort();
+        pairs
+    }
 
-        if let Some(ref val) = self.family {
-            params.insert("family".to_string(), val.clone());
+    /// Format the parameters as a query string, e.g. `?limit=10&type=relay`
+    ///
+    /// Parameters are sorted by name and values are percent-encoded.
+    pub fn to_query_string(&self) -> String {
+        let pairs = self.pairs();
+        if pairs.is_empty() {
+            String::new()
+        } else {
+            let query_parts = pairs
+                .iter()
+                .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+                .collect::<Vec<_>>();
+            format!("?{}", query_parts.join("&"))
         }
+    }
 
-        if let Some(ref val) = self.version {
-            params.insert("version".to_string(), val.as_str().to_string());
+    /// Returns the equivalent parameters in canonical form
+    ///
+    /// Parameter sets that differ only in ways the Onionoo server ignores compare equal,
+    /// and hash alike, once both are canonical. Case-insensitive values are lower-cased
+    /// and fingerprints upper-cased without a `$` prefix; AS numbers, days ranges and
+    /// dates are rewritten in their standard format; fields are sorted and deduplicated;
+    /// runs of whitespace are collapsed and an offset of zero is dropped. Malformed
+    /// values are kept as given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::QueryParameters;
+    ///
+    /// let a = QueryParameters::from_query_string("?flag=Exit&as=3&first_seen_days=7").unwrap();
+    /// let b = QueryParameters::from_query_string("?first_seen_days=7-7&as=AS3&flag=exit").unwrap();
+    /// assert_ne!(a, b);
+    /// assert_eq!(a.canonical(), b.canonical());
+    /// ```
+    pub fn canonical(&self) -> Self {
+        let words = |value: &String| value.split_whitespace().collect::<Vec<_>>().join(" ");
+        let lower = |value: &String| value.trim().to_lowercase();
+        let fingerprint = |value: &String| value.trim().trim_start_matches('$').to_uppercase();
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_lowercase())
+                .filter(|item| !item.is_empty())
+                .collect::<Vec<_>>()
+        };
+
+        Self {
+            type_param: self.type_param,
+            running: self.running,
+            search: self.search.as_ref().map(words),
+            lookup: self.lookup.as_ref().map(fingerprint),
+            country: self.country.clone(),
+            as_param: self.as_param.as_ref().map(|v| reparse(v, v.as_str())),
+            as_name: self.as_name.as_ref().map(words),
+            flag: self.flag.as_ref().map(lower),
+            first_seen_days: self
+                .first_seen_days
+                .as_ref()
+                .map(|v| reparse(v, v.as_str())),
+            last_seen_days: self.last_seen_days.as_ref().map(|v| reparse(v, v.as_str())),
+            first_seen_since: self
+                .first_seen_since
+                .as_ref()
+                .map(|v| reparse(v, v.as_str())),
+            last_seen_since: self
+                .last_seen_since
+                .as_ref()
+                .map(|v| reparse(v, v.as_str())),
+            contact: self.contact.as_ref().map(|v| words(&v.to_lowercase())),
+            family: self.family.as_ref().map(fingerprint),
+            version: self
+                .version
+                .as_ref()
+                .map(|v| VersionList(list(v.as_str()).join(","))),
+            os: self.os.as_ref().map(lower),
+            host_name: self.host_name.as_ref().map(lower),
+            recommended_version: self.recommended_version,
+            fields: self.fields.as_ref().map(|v| {
+                let mut fields = list(v.as_str());
+                fields.sort();
+                fields.dedup();
+                FieldsList(fields.join(","))
+            }),
+            order: self
+                .order
+                .as_ref()
+                .map(|v| OrderList(list(v.as_str()).join(","))),
+            offset: self.offset.filter(|&offset| offset > 0),
+            limit: self.limit,
         }
+    }
+}
 
-        if let Some(ref val) = self.os {
-            params.insert("os".to_string(), val.clone());
-        }
+/// Parses `text`, keeping `value` as given if it is malformed
+fn reparse<T: std::str::FromStr + Clone>(value: &T, text: &str) -> T {
+    text.parse().unwrap_or_else(|_| value.clone())
+}
 
-        if let Some(ref val) = self.host_name {
-            params.insert("host_name".to_string(), val.clone());
+/// Serialized as a map from parameter names to values, sorted like the query string
+///
+/// `running` and `recommended_version` are booleans and `offset` and `limit` are
+/// numbers; all other values are strings.
+impl Serialize for QueryParameters {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let is_true = |value: Option<BoolValue>| value.map(|v| v == BoolValue::True);
+        let pairs = self.pairs();
+        let mut map = serializer.serialize_map(Some(pairs.len()))?;
+        for (name, value) in &pairs {
+            match *name {
+                "running" => map.serialize_entry(name, &is_true(self.running))?,
+                "recommended_version" => {
+                    map.serialize_entry(name, &is_true(self.recommended_version))?
+                }
+                "offset" => map.serialize_entry(name, &self.offset)?,
+                "limit" => map.serialize_entry(name, &self.limit)?,
+                _ => map.serialize_entry(name, value)?,
+            }
         }
+        map.end()
+    }
+}
 
-        if let Some(ref val) = self.recommended_version {
-            params.insert("recommended_version".to_string(), val.as_str().to_string());
+/// Accepts the same names and values as [`QueryParameters::from_query_string`];
+/// booleans and numbers may also be given as strings
+impl<'de> Deserialize<'de> for QueryParameters {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Text(String),
+            Number(i64),
+            Bool(bool),
         }
 
-        if let Some(ref val) = self.fields {
-            params.insert("fields".to_string(), val.as_str().to_string());
+        let mut params = QueryParameters::new();
+        for (name, value) in BTreeMap::<String, Raw>::deserialize(deserializer)? {
+            let value = match value {
+                Raw::Text(text) => text,
+                Raw::Number(number) => number.to_string(),
+                Raw::Bool(value) => value.to_string(),
+            };
+            params.set(&name, value).map_err(de::Error::custom)?;
         }
+        Ok(params)
+    }
+}
 
-        if let Some(ref val) = self.order {
-            params.insert("order".to_string(), val.as_str().to_string());
-        }
+fn parse_bool(value: &str) -> Option<BoolValue> {
+    match value.to_lowercase().as_str() {
+        "true" => Some(BoolValue::True),
+        "false" => Some(BoolValue::False),
+        _ => None,
+    }
+}
 
-        if let Some(val) = self.offset {
-            params.insert("offset".to_string(), val.to_string());
-        }
+fn parse_count(value: &str) -> Option<u32> {
+    value
+        .parse::<i64>()
+        .ok()
+        .map(|n| n.clamp(0, u32::MAX as i64) as u32)
+}
 
-        if let Some(val) = self.limit {
-            params.insert("limit".to_string(), val.to_string());
+/// Escape a query string component as `%XX`, keeping characters that are safe in a
+/// query as they are
+fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$'()*,;:@/?".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
         }
+    }
+    encoded
+}
 
-        if params.is_empty() {
-            String::new()
-        } else {
-            let mut query_parts = params
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<_>>();
-            query_parts.sort();
-            format!("?{}", query_parts.join("&"))
+/// Decode `%XX` escapes and `+` in a query string component
+fn percent_decode(input: &str) -> String {
+    let hex = |byte: u8| (byte as char).to_digit(16).map(|d| d as u8);
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(high), Some(low)) => {
+                    decoded.push(high << 4 | low);
+                    i += 2;
+                }
+                _ => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
         }
+        i += 1;
     }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 /// Selection parameters for filtering results
 pub mod selection {
+    use super::ParameterError;
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+    use std::fmt;
+    use std::str::FromStr;
 
     /// Type parameter values
-    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum TypeValue {
         Relay,
         Bridge,
@@ -271,7 +543,7 @@ pub mod selection {
     }
 
     /// Boolean parameter values (for running, recommended_version, etc.)
-    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum BoolValue {
         True,
         False,
@@ -287,27 +559,127 @@ pub mod selection {
     }
 
     /// Time range format for days parameters (e.g., "7-14", "30-", "-90")
-    #[derive(Debug, Clone)]
+    ///
+    /// Ranges are inclusive and count days before now.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::parameters::DaysRange;
+    ///
+    /// assert_eq!(DaysRange::between(7, 14).as_str(), "7-14");
+    /// assert_eq!(DaysRange::at_least(30).as_str(), "30-");
+    /// assert_eq!(DaysRange::at_most(90).as_str(), "-90");
+    /// assert_eq!("7".parse::<DaysRange>().unwrap().as_str(), "7-7");
+    /// assert!("14-7".parse::<DaysRange>().is_err());
+    /// ```
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub struct DaysRange(pub String);
 
     impl DaysRange {
+        /// Between `min` and `max` days ago
+        pub fn between(min: u32, max: u32) -> Self {
+            DaysRange(format!("{}-{}", min, max))
+        }
+
+        /// Exactly `days` days ago
+        pub fn exactly(days: u32) -> Self {
+            Self::between(days, days)
+        }
+
+        /// At least `days` days ago
+        pub fn at_least(days: u32) -> Self {
+            DaysRange(format!("{}-", days))
+        }
+
+        /// At most `days` days ago
+        pub fn at_most(days: u32) -> Self {
+            DaysRange(format!("-{}", days))
+        }
+
+        /// Returns the minimum and, unless open-ended, the maximum number of days
+        ///
+        /// Fails if the range is malformed or its minimum exceeds its maximum.
+        pub fn bounds(&self) -> Result<(u32, Option<u32>), ParameterError> {
+            let invalid = || ParameterError::InvalidDaysRange(self.0.clone());
+            let number = |s: &str| s.trim().parse::<u32>().map_err(|_| invalid());
+            let (min, max) = match self.0.split_once('-') {
+                None => {
+                    let days = number(&self.0)?;
+                    (days, Some(days))
+                }
+                Some(("", max)) => (0, Some(number(max)?)),
+                Some((min, "")) => (number(min)?, None),
+                Some((min, max)) => (number(min)?, Some(number(max)?)),
+            };
+            if max.is_some_and(|max| max < min) {
+                return Err(invalid());
+            }
+            Ok((min, max))
+        }
+
         pub fn as_str(&self) -> &str {
             &self.0
         }
     }
 
+    /// Parses "x-y", "x-", "-y" or "x", which is normalized to "x-x"
+    impl FromStr for DaysRange {
+        type Err = ParameterError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let range = DaysRange(s.trim().to_string());
+            match range.bounds()? {
+                (min, Some(max)) if !range.0.contains('-') => Ok(Self::between(min, max)),
+                _ => Ok(range),
+            }
+        }
+    }
+
     /// Date format for since parameters (yyyy-MM-dd)
-    #[derive(Debug, Clone)]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use onionoo::parameters::Date;
+    ///
+    /// let date = Date::from(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+    /// assert_eq!(date.as_str(), "2024-03-01");
+    /// assert!("2024-13-01".parse::<Date>().is_err());
+    /// ```
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub struct Date(pub String);
 
     impl Date {
+        /// Returns the date, failing if it is not in yyyy-MM-dd format
+        pub fn to_naive_date(&self) -> Result<NaiveDate, ParameterError> {
+            NaiveDate::parse_from_str(self.0.trim(), "%Y-%m-%d")
+                .map_err(|_| ParameterError::InvalidDate(self.0.clone()))
+        }
+
         pub fn as_str(&self) -> &str {
             &self.0
         }
     }
 
+    impl From<NaiveDate> for Date {
+        fn from(date: NaiveDate) -> Self {
+            Date(date.format("%Y-%m-%d").to_string())
+        }
+    }
+
+    impl FromStr for Date {
+        type Err = ParameterError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let date = Date(s.trim().to_string());
+            date.to_naive_date().map(Date::from)
+        }
+    }
+
     /// Version list format (comma-separated, ranges with "..", or mixed)
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub struct VersionList(pub String);
 
     impl VersionList {
@@ -316,43 +688,436 @@ pub mod selection {
         }
     }
 
+    /// ISO 3166-1 alpha-2 country code, normalized to the lower case used by Onionoo
+    ///
+    /// Onionoo uses the special code `xz` for relays that were not found in the GeoIP
+    /// database. Conversions from strings only normalize the code; use
+    /// [`CountryCode::new`] or [`str::parse`] to reject codes that are not two letters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::parameters::CountryCode;
+    ///
+    /// assert_eq!(CountryCode::new("DE").unwrap().as_str(), "de");
+    /// assert!(CountryCode::new("DEU").is_err());
+    /// ```
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+    #[serde(from = "String", into = "String")]
+    pub struct CountryCode(String);
+
+    impl CountryCode {
+        /// Create a country code, failing unless it consists of two ASCII letters
+        pub fn new(code: &str) -> Result<Self, ParameterError> {
+            let code = CountryCode::from(code);
+            if code.is_valid() {
+                Ok(code)
+            } else {
+                Err(ParameterError::InvalidValue("country".to_string(), code.0))
+            }
+        }
+
+        /// Whether the code consists of two ASCII letters
+        pub fn is_valid(&self) -> bool {
+            self.0.len() == 2 && self.0.bytes().all(|b| b.is_ascii_alphabetic())
+        }
+
+        pub fn as_str(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl From<&str> for CountryCode {
+        fn from(code: &str) -> Self {
+            CountryCode(code.trim().to_ascii_lowercase())
+        }
+    }
+
+    impl From<String> for CountryCode {
+        fn from(code: String) -> Self {
+            CountryCode::from(code.as_str())
+        }
+    }
+
+    impl From<&String> for CountryCode {
+        fn from(code: &String) -> Self {
+            CountryCode::from(code.as_str())
+        }
+    }
+
+    impl From<CountryCode> for String {
+        fn from(code: CountryCode) -> Self {
+            code.0
+        }
+    }
+
+    impl FromStr for CountryCode {
+        type Err = ParameterError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            CountryCode::new(s)
+        }
+    }
+
+    impl fmt::Display for CountryCode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    /// Autonomous system number
+    ///
+    /// Parsed from `"AS3"`, `"as3"` or `"3"`, and written as `"AS3"` like in Onionoo
+    /// documents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::parameters::AsNumber;
+    ///
+    /// assert_eq!("AS3".parse::<AsNumber>().unwrap(), AsNumber(3));
+    /// assert_eq!("3".parse::<AsNumber>().unwrap(), AsNumber::from(3));
+    /// assert_eq!(AsNumber(24940).to_string(), "AS24940");
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct AsNumber(pub u32);
+
+    impl From<u32> for AsNumber {
+        fn from(number: u32) -> Self {
+            AsNumber(number)
+        }
+    }
+
+    impl FromStr for AsNumber {
+        type Err = ParameterError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let value = s.trim();
+            let digits = match value.get(..2) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("as") => &value[2..],
+                _ => value,
+            };
+            digits
+                .parse()
+                .map(AsNumber)
+                .map_err(|_| ParameterError::InvalidValue("as".to_string(), s.to_string()))
+        }
+    }
+
+    impl fmt::Display for AsNumber {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "AS{}", self.0)
+        }
+    }
+
+    impl Serialize for AsNumber {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for AsNumber {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            #[derive(Deserialize)]
+            #[serde(untagged)]
+            enum Raw {
+                Number(u32),
+                Text(String),
+            }
+
+            match Raw::deserialize(deserializer)? {
+                Raw::Number(number) => Ok(AsNumber(number)),
+                Raw::Text(text) => text.parse().map_err(de::Error::custom),
+            }
+        }
+    }
+
     /// AS numbers (can be comma-separated)
-    #[derive(Debug, Clone)]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::parameters::{AsList, AsNumber};
+    ///
+    /// let list = AsList::new().number(3).number(AsNumber(24940));
+    /// assert_eq!(list.as_str(), "AS3,AS24940");
+    /// assert_eq!("3, as16276".parse::<AsList>().unwrap().as_str(), "AS3,AS16276");
+    /// ```
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
     pub struct AsList(pub String);
 
     impl AsList {
+        /// Create an empty list
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Append an AS number
+        pub fn number<T: Into<AsNumber>>(mut self, number: T) -> Self {
+            if !self.0.is_empty() {
+                self.0.push(',');
+            }
+            self.0.push_str(&number.into().to_string());
+            self
+        }
+
+        /// Parses the AS numbers in the list
+        pub fn numbers(&self) -> Result<Vec<AsNumber>, ParameterError> {
+            self.0
+                .split(',')
+                .map(|number| {
+                    number
+                        .parse()
+                        .map_err(|_| ParameterError::InvalidValue("as".to_string(), self.0.clone()))
+                })
+                .collect()
+        }
+
         pub fn as_str(&self) -> &str {
             &self.0
         }
     }
+
+    /// Parses and normalizes a comma-separated list of AS numbers
+    impl FromStr for AsList {
+        type Err = ParameterError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            AsList(s.to_string()).numbers().map(AsList::from_iter)
+        }
+    }
+
+    impl FromIterator<AsNumber> for AsList {
+        fn from_iter<I: IntoIterator<Item = AsNumber>>(numbers: I) -> Self {
+            numbers
+                .into_iter()
+                .fold(AsList::new(), |list, number| list.number(number))
+        }
+    }
+
+    impl From<AsNumber> for AsList {
+        fn from(number: AsNumber) -> Self {
+            AsList::new().number(number)
+        }
+    }
+
+    impl From<u32> for AsList {
+        fn from(number: u32) -> Self {
+            AsList::new().number(number)
+        }
+    }
+
+    impl From<&str> for AsList {
+        fn from(list: &str) -> Self {
+            AsList(list.to_string())
+        }
+    }
+
+    impl From<String> for AsList {
+        fn from(list: String) -> Self {
+            AsList(list)
+        }
+    }
+
+    impl From<&String> for AsList {
+        fn from(list: &String) -> Self {
+            AsList(list.clone())
+        }
+    }
 }
 
 pub use selection::*;
 
 /// Field filtering parameters
+///
+/// Each document type has an enum of the top-level fields of its relay and bridge
+/// objects, named as on the wire and generated along with the objects in
+/// [`crate::models`]. The Onionoo server currently only honors the `fields` parameter
+/// for details documents; use [`crate::models::PartialDetailsResponse`] to deserialize
+/// the result.
 pub mod fields {
+    use std::fmt;
+
+    /// Top-level field of a relay or bridge object
+    pub trait Field: Copy + fmt::Debug + 'static {
+        /// All fields of the document type, in document order
+        const ALL: &'static [Self];
+
+        /// Field name as used in documents and in the `fields` parameter
+        fn as_str(&self) -> &'static str;
+    }
+
+    pub use crate::models::{
+        BandwidthField, ClientsField, DetailsField, SummaryField, UptimeField, WeightsField,
+    };
 
     /// Fields list (comma-separated)
-    #[derive(Debug, Clone)]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::parameters::{DetailsField, FieldsList};
+    ///
+    /// let fields = FieldsList::new()
+    ///     .field(DetailsField::Nickname)
+    ///     .field(DetailsField::Fingerprint)
+    ///     .field(DetailsField::HashedFingerprint);
+    /// assert_eq!(fields.as_str(), "nickname,fingerprint,hashed_fingerprint");
+    /// ```
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
     pub struct FieldsList(pub String);
 
     impl FieldsList {
+        /// Create an empty fields list
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Append a field, unless it is already in the list
+        pub fn field<F: Field>(mut self, field: F) -> Self {
+            let name = field.as_str();
+            if !self.0.split(',').any(|f| f.eq_ignore_ascii_case(name)) {
+                if !self.0.is_empty() {
+                    self.0.push(',');
+                }
+                self.0.push_str(name);
+            }
+            self
+        }
+
         pub fn as_str(&self) -> &str {
             &self.0
         }
     }
+
+    impl<F: Field> FromIterator<F> for FieldsList {
+        fn from_iter<I: IntoIterator<Item = F>>(fields: I) -> Self {
+            fields
+                .into_iter()
+                .fold(FieldsList::new(), |list, field| list.field(field))
+        }
+    }
 }
 
 pub use fields::*;
 
 /// Pagination and ordering parameters
 pub mod pagination {
+    use super::ParameterError;
+    use std::fmt;
+    use std::str::FromStr;
+
+    /// Field that results can be ordered by
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum OrderKey {
+        ConsensusWeight,
+        FirstSeen,
+    }
+
+    impl OrderKey {
+        /// All supported sort keys
+        pub const ALL: &'static [OrderKey] = &[OrderKey::ConsensusWeight, OrderKey::FirstSeen];
+
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                OrderKey::ConsensusWeight => "consensus_weight",
+                OrderKey::FirstSeen => "first_seen",
+            }
+        }
+    }
+
+    impl fmt::Display for OrderKey {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    /// Parses a sort key, ignoring case as the Onionoo server does
+    impl FromStr for OrderKey {
+        type Err = ParameterError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            Self::ALL
+                .iter()
+                .find(|key| key.as_str().eq_ignore_ascii_case(s.trim()))
+                .copied()
+                .ok_or_else(|| ParameterError::InvalidValue("order".to_string(), s.to_string()))
+        }
+    }
+
+    /// Sort direction of an order key
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Direction {
+        Ascending,
+        Descending,
+    }
 
     /// Order specifications (field names with optional "-" prefix for descending)
-    #[derive(Debug, Clone)]
+    ///
+    /// Results are ordered by the first key, then by the second, and so on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::parameters::{OrderKey, OrderList};
+    ///
+    /// let order = OrderList::new()
+    ///     .desc(OrderKey::ConsensusWeight)
+    ///     .asc(OrderKey::FirstSeen);
+    /// assert_eq!(order.as_str(), "-consensus_weight,first_seen");
+    /// ```
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
     pub struct OrderList(pub String);
 
     impl OrderList {
+        /// Create an empty order list
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Order by the key in ascending order
+        pub fn asc(self, key: OrderKey) -> Self {
+            self.key(key, Direction::Ascending)
+        }
+
+        /// Order by the key in descending order
+        pub fn desc(self, key: OrderKey) -> Self {
+            self.key(key, Direction::Descending)
+        }
+
+        /// Order by the key in the given direction
+        pub fn key(mut self, key: OrderKey, direction: Direction) -> Self {
+            if !self.0.is_empty() {
+                self.0.push(',');
+            }
+            if direction == Direction::Descending {
+                self.0.push('-');
+            }
+            self.0.push_str(key.as_str());
+            self
+        }
+
+        /// Parses the list into sort keys and directions
+        ///
+        /// Fails on keys other than those in [`OrderKey`], which the Onionoo server
+        /// rejects with `400 Bad Request`.
+        pub fn keys(&self) -> Result<Vec<(OrderKey, Direction)>, ParameterError> {
+            self.0
+                .split(',')
+                .map(|key| {
+                    let key = key.trim();
+                    let (direction, name) = match key.strip_prefix('-') {
+                        Some(name) => (Direction::Descending, name),
+                        None => (Direction::Ascending, key),
+                    };
+                    let key = name.parse().map_err(|_| {
+                        ParameterError::InvalidValue("order".to_string(), self.0.clone())
+                    })?;
+                    Ok((key, direction))
+                })
+                .collect()
+        }
+
         pub fn as_str(&self) -> &str {
             &self.0
         }
@@ -361,6 +1126,238 @@ pub mod pagination {
 
 pub use pagination::*;
 
+/// Validation of query parameters against endpoint capabilities
+pub mod validation {
+    use super::{ParameterError, QueryParameters, TypeValue};
+    use crate::endpoints::Endpoint;
+    use std::fmt;
+
+    /// How serious a validation issue is
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Severity {
+        /// The request is valid, but probably not what was intended
+        Warning,
+        /// The request is rejected by the server or can never return any results
+        Error,
+    }
+
+    /// A problem found by [`QueryParameters::validate`]
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ValidationIssue {
+        /// The type parameter excludes every document the endpoint serves (endpoint, type)
+        UnsupportedType(&'static str, TypeValue),
+        /// A relay-only parameter was sent to an endpoint that only serves bridges (endpoint, parameter)
+        RelayOnlyParameter(&'static str, &'static str),
+        /// The fields parameter is only supported by the details endpoint (endpoint)
+        UnsupportedFields(&'static str),
+        /// Both lookup and search are given, so search can only narrow down the lookup
+        LookupWithSearch,
+        /// A limit of zero always leads to an empty result
+        ZeroLimit,
+        /// A parameter value is malformed
+        InvalidValue(ParameterError),
+    }
+
+    impl ValidationIssue {
+        pub fn severity(&self) -> Severity {
+            match self {
+                ValidationIssue::UnsupportedType(..)
+                | ValidationIssue::RelayOnlyParameter(..)
+                | ValidationIssue::InvalidValue(_) => Severity::Error,
+                ValidationIssue::UnsupportedFields(_)
+                | ValidationIssue::LookupWithSearch
+                | ValidationIssue::ZeroLimit => Severity::Warning,
+            }
+        }
+    }
+
+    impl fmt::Display for ValidationIssue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ValidationIssue::UnsupportedType(endpoint, type_value) => write!(
+                    f,
+                    "{} documents contain no {}s",
+                    endpoint,
+                    type_value.as_str()
+                ),
+                ValidationIssue::RelayOnlyParameter(endpoint, parameter) => write!(
+                    f,
+                    "{} only matches relays, but {} documents only contain bridges",
+                    parameter, endpoint
+                ),
+                ValidationIssue::UnsupportedFields(endpoint) => {
+                    write!(f, "fields is ignored for {} documents", endpoint)
+                }
+                ValidationIssue::LookupWithSearch => {
+                    write!(f, "lookup and search are both given")
+                }
+                ValidationIssue::ZeroLimit => write!(f, "limit=0 always returns no results"),
+                ValidationIssue::InvalidValue(e) => write!(f, "{}", e),
+            }
+        }
+    }
+
+    /// Issues found by [`QueryParameters::validate`], in parameter order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onionoo::{QueryParameters, endpoints::Endpoint, selection::TypeValue};
+    ///
+    /// let report = QueryParameters::new()
+    ///     .type_param(TypeValue::Bridge)
+    ///     .limit(0)
+    ///     .validate(&Endpoint::Weights);
+    /// assert!(report.has_errors());
+    /// assert_eq!(report.warnings().count(), 1);
+    /// assert!(report.check(false).is_err());
+    /// ```
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct ValidationReport {
+        issues: Vec<ValidationIssue>,
+    }
+
+    impl ValidationReport {
+        pub fn issues(&self) -> &[ValidationIssue] {
+            &self.issues
+        }
+
+        pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+            self.issues
+                .iter()
+                .filter(|issue| issue.severity() == Severity::Error)
+        }
+
+        pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+            self.issues
+                .iter()
+                .filter(|issue| issue.severity() == Severity::Warning)
+        }
+
+        pub fn has_errors(&self) -> bool {
+            self.errors().next().is_some()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.issues.is_empty()
+        }
+
+        /// Fails if there are errors or, in strict mode, any issues at all
+        pub fn check(self, strict: bool) -> Result<(), ValidationReport> {
+            if self.has_errors() || (strict && !self.is_empty()) {
+                Err(self)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl fmt::Display for ValidationReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for (i, issue) in self.issues.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "; ")?;
+                }
+                write!(f, "{}", issue)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl std::error::Error for ValidationReport {}
+
+    impl QueryParameters {
+        /// Check the parameters against the capabilities of an endpoint
+        ///
+        /// Errors are requests the server rejects or that can never return any results,
+        /// such as `type=bridge` for weights documents or a days range whose minimum
+        /// exceeds its maximum. Warnings are requests that are valid but probably not
+        /// intended. [`Client::get_endpoint`](crate::Client::get_endpoint) runs this
+        /// before sending a request.
+        pub fn validate(&self, endpoint: &Endpoint) -> ValidationReport {
+            let mut issues = Vec::new();
+
+            if let Some(type_value) = self.type_param {
+                let excluded = match endpoint {
+                    Endpoint::Weights => type_value == TypeValue::Bridge,
+                    Endpoint::Clients => type_value == TypeValue::Relay,
+                    _ => false,
+                };
+                if excluded {
+                    issues.push(ValidationIssue::UnsupportedType(
+                        endpoint.name(),
+                        type_value,
+                    ));
+                }
+            }
+            if let Endpoint::Clients = endpoint {
+                issues.extend(
+                    self.relay_only_params()
+                        .into_iter()
+                        .map(|name| ValidationIssue::RelayOnlyParameter(endpoint.name(), name)),
+                );
+            }
+            if self.fields.is_some() && !matches!(endpoint, Endpoint::Details) {
+                issues.push(ValidationIssue::UnsupportedFields(endpoint.name()));
+            }
+            if self.lookup.is_some() && self.search.is_some() {
+                issues.push(ValidationIssue::LookupWithSearch);
+            }
+
+            let values = [
+                self.country
+                    .as_ref()
+                    .filter(|country| !country.is_valid())
+                    .map(|country| {
+                        Err(ParameterError::InvalidValue(
+                            "country".to_string(),
+                            country.to_string(),
+                        ))
+                    }),
+                self.as_param.as_ref().map(|list| list.numbers().map(drop)),
+                self.first_seen_days.as_ref().map(|r| r.bounds().map(drop)),
+                self.last_seen_days.as_ref().map(|r| r.bounds().map(drop)),
+                self.first_seen_since
+                    .as_ref()
+                    .map(|d| d.to_naive_date().map(drop)),
+                self.last_seen_since
+                    .as_ref()
+                    .map(|d| d.to_naive_date().map(drop)),
+                self.order.as_ref().map(|order| order.keys().map(drop)),
+            ];
+            issues.extend(
+                values
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::err)
+                    .map(ValidationIssue::InvalidValue),
+            );
+
+            if self.limit == Some(0) {
+                issues.push(ValidationIssue::ZeroLimit);
+            }
+
+            ValidationReport { issues }
+        }
+
+        /// Names of the given parameters that only match relays
+        pub(crate) fn relay_only_params(&self) -> Vec<&'static str> {
+            [
+                ("country", self.country.is_some()),
+                ("as", self.as_param.is_some()),
+                ("as_name", self.as_name.is_some()),
+                ("family", self.family.is_some()),
+                ("host_name", self.host_name.is_some()),
+            ]
+            .into_iter()
+            .filter_map(|(name, given)| given.then_some(name))
+            .collect()
+        }
+    }
+}
+
+pub use validation::*;
+
 /// Helper functions for creating parameter values
 pub mod helpers {
     use super::*;
@@ -371,8 +1368,11 @@ pub mod helpers {
     /// - "7-14" -> "7-14"
     /// - "30-" -> "30-"
     /// - "-90" -> "-90"
+    ///
+    /// Invalid ranges are kept as given; parse a [`DaysRange`] with [`str::parse`] to reject them.
     pub fn days_range<T: Into<String>>(range: T) -> DaysRange {
-        DaysRange(range.into())
+        let range = range.into();
+        range.parse().unwrap_or(DaysRange(range))
     }
 
     /// Create a Date from yyyy-MM-dd format
@@ -658,19 +1658,262 @@ mod tests {
         let query = params.to_query_string();
         assert!(query.contains("type=relay"));
         assert!(query.contains("running=true"));
-        assert!(query.contains("country=US"));
+        assert!(query.contains("country=us"));
+    }
+
+    #[test]
+    fn test_fields_list_builder() {
+        let fields: FieldsList = [
+            DetailsField::Nickname,
+            DetailsField::As,
+            DetailsField::Nickname,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(fields.as_str(), "nickname,as");
+
+        let params = QueryParameters::new().fields(fields.field(DetailsField::Fingerprint));
+        assert_eq!(params.to_query_string(), "?fields=nickname,as,fingerprint");
+
+        assert_eq!("AS_NAME".parse::<DetailsField>(), Ok(DetailsField::AsName));
+        assert!("nick".parse::<DetailsField>().is_err());
+        assert_eq!(SummaryField::ALL.len(), 5);
+    }
+
+    #[test]
+    fn test_order_list_builder() {
+        let order = OrderList::new()
+            .desc(OrderKey::ConsensusWeight)
+            .asc(OrderKey::FirstSeen);
+        let params = QueryParameters::new().order(order.clone());
+        assert_eq!(
+            params.to_query_string(),
+            "?order=-consensus_weight,first_seen"
+        );
+        assert_eq!(
+            order.keys(),
+            Ok(vec![
+                (OrderKey::ConsensusWeight, Direction::Descending),
+                (OrderKey::FirstSeen, Direction::Ascending),
+            ])
+        );
+
+        assert_eq!(
+            order_list("FIRST_SEEN").keys(),
+            Ok(vec![(OrderKey::FirstSeen, Direction::Ascending)])
+        );
+        assert!(order_list("nickname").keys().is_err());
+        assert!(order_list("").keys().is_err());
+    }
+
+    #[test]
+    fn test_country_and_as_numbers() {
+        let params = QueryParameters::new()
+            .country(" DE ")
+            .as_param(AsList::new().number(3).number(AsNumber(24940)));
+        assert_eq!(params.to_query_string(), "?as=AS3,AS24940&country=de");
+        assert!(CountryCode::new("d3").is_err());
+        assert!(!CountryCode::from("germany").is_valid());
+
+        assert_eq!("as16276".parse::<AsNumber>(), Ok(AsNumber(16276)));
+        assert!("ASX".parse::<AsNumber>().is_err());
+        assert!("3,x".parse::<AsList>().is_err());
+        assert_eq!(
+            AsList::from("AS3, 24940").numbers(),
+            Ok(vec![AsNumber(3), AsNumber(24940)])
+        );
+
+        let number: AsNumber = serde_json::from_str("\"AS3\"").unwrap();
+        assert_eq!(number, serde_json::from_str::<AsNumber>("3").unwrap());
+        assert_eq!(serde_json::to_string(&number).unwrap(), "\"AS3\"");
+        let country: CountryCode = serde_json::from_str("\"US\"").unwrap();
+        assert_eq!(serde_json::to_string(&country).unwrap(), "\"us\"");
+    }
+
+    #[test]
+    fn test_from_query_string() {
+        let params = QueryParameters::from_query_string(
+            "?type=RELAY&running=true&as_name=Hurricane+Electric&contact=a%40b&limit=5&flag=Exit&flag=Guard",
+        )
+        .unwrap();
+        assert_eq!(
+            params.to_query_string(),
+            "?as_name=Hurricane%20Electric&contact=a@b&flag=Exit&limit=5&running=true&type=relay"
+        );
+
+        let params = QueryParameters::from_query_string("offset=-3").unwrap();
+        assert_eq!(params.to_query_string(), "?offset=0");
+
+        assert_eq!(
+            QueryParameters::from_query_string("?nickname=moria1").unwrap_err(),
+            ParameterError::UnknownParameter("nickname".to_string())
+        );
+        assert_eq!(
+            QueryParameters::from_query_string("?running=yes").unwrap_err(),
+            ParameterError::InvalidValue("running".to_string(), "yes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_query_string_round_trip() {
+        let params = QueryParameters::new()
+            .contact("a+b@example.com & c#d")
+            .search("100% fast+stable")
+            .as_name("AT&T Services, Inc.");
+        let query = params.to_query_string();
+        assert!(!query.contains(' ') && !query.contains('#'));
+        assert_eq!(query.matches('&').count(), 2);
+        assert_eq!(QueryParameters::from_query_string(&query).unwrap(), params);
     }
 
     #[test]
     fn test_days_range() {
         let range = days_range("7-14");
         assert_eq!(range.as_str(), "7-14");
+        assert_eq!(days_range("7").as_str(), "7-7");
+        assert_eq!(DaysRange::exactly(3).as_str(), "3-3");
+        assert_eq!(DaysRange::at_least(30).bounds(), Ok((30, None)));
+        assert_eq!(DaysRange::at_most(90).bounds(), Ok((0, Some(90))));
+        assert_eq!(
+            "-5-9".parse::<DaysRange>().unwrap_err(),
+            ParameterError::InvalidDaysRange("-5-9".to_string())
+        );
+        assert!(DaysRange::between(14, 7).bounds().is_err());
+        assert!("seven".parse::<DaysRange>().is_err());
     }
 
     #[test]
     fn test_date() {
         let date = date("2023-10-27");
         assert_eq!(date.as_str(), "2023-10-27");
+        assert_eq!(
+            date.to_naive_date(),
+            Ok(chrono::NaiveDate::from_ymd_opt(2023, 10, 27).unwrap())
+        );
+        assert_eq!(
+            "27.10.2023".parse::<Date>().unwrap_err(),
+            ParameterError::InvalidDate("27.10.2023".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        use crate::endpoints::Endpoint;
+
+        let params = QueryParameters::new()
+            .country("usa")
+            .first_seen_days(DaysRange("14-7".to_string()))
+            .fields(fields_list("nickname"))
+            .limit(0);
+        let report = params.validate(&Endpoint::Summary);
+        assert_eq!(
+            report.issues(),
+            [
+                ValidationIssue::UnsupportedFields("summary"),
+                ValidationIssue::InvalidValue(ParameterError::InvalidValue(
+                    "country".to_string(),
+                    "usa".to_string()
+                )),
+                ValidationIssue::InvalidValue(ParameterError::InvalidDaysRange("14-7".to_string())),
+                ValidationIssue::ZeroLimit,
+            ]
+        );
+        assert_eq!(report.errors().count(), 2);
+        assert!(report.check(false).is_err());
+
+        let report = QueryParameters::new()
+            .fields(fields_list("nickname"))
+            .validate(&Endpoint::Bandwidth);
+        assert!(!report.has_errors());
+        assert!(report.clone().check(false).is_ok());
+        assert!(report.check(true).is_err());
+
+        assert!(
+            QueryParameters::new()
+                .type_param(TypeValue::Relay)
+                .validate(&Endpoint::Weights)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let params = QueryParameters::new()
+            .type_param(TypeValue::Relay)
+            .running(BoolValue::True)
+            .country("DE")
+            .as_param(3)
+            .order(OrderList::new().desc(OrderKey::ConsensusWeight))
+            .limit(10);
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            json,
+            r#"{"as":"AS3","country":"de","limit":10,"order":"-consensus_weight","running":true,"type":"relay"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<QueryParameters>(&json).unwrap(),
+            params
+        );
+
+        let params: QueryParameters =
+            serde_json::from_str(r#"{"running": "false", "offset": 5, "as": 3}"#).unwrap();
+        assert_eq!(params.to_query_string(), "?as=3&offset=5&running=false");
+        assert_eq!(
+            serde_json::from_str::<QueryParameters>("{}").unwrap(),
+            QueryParameters::new()
+        );
+        assert!(serde_json::from_str::<QueryParameters>(r#"{"nickname": "moria1"}"#).is_err());
+        assert!(serde_json::from_str::<QueryParameters>(r#"{"type": "guard"}"#).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_parameters_from_toml() {
+        let params: QueryParameters = toml::from_str(
+            r#"
+            type = "relay"
+            running = true
+            country = "DE"
+            limit = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            params.to_query_string(),
+            "?country=de&limit=10&running=true&type=relay"
+        );
+        assert_eq!(
+            toml::from_str::<QueryParameters>(&toml::to_string(&params).unwrap()).unwrap(),
+            params
+        );
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let a = QueryParameters::from_query_string(
+            "?lookup=$9695dfc35ffeb861329b9f1ab04c46397020ce31&fields=Nickname,fingerprint,nickname&offset=0&search=moria%20%201",
+        )
+        .unwrap();
+        let b = QueryParameters::from_query_string(
+            "?search=moria 1&fields=fingerprint,nickname&lookup=9695DFC35FFEB861329B9F1AB04C46397020CE31",
+        )
+        .unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.canonical(), b.canonical());
+        assert_eq!(
+            a.canonical().to_query_string(),
+            "?fields=fingerprint,nickname&lookup=9695DFC35FFEB861329B9F1AB04C46397020CE31&search=moria%201"
+        );
+
+        let set: std::collections::HashSet<_> =
+            [a.canonical(), b.canonical(), QueryParameters::new()]
+                .into_iter()
+                .collect();
+        assert_eq!(set.len(), 2);
+
+        // Malformed values are kept, so validation still reports them.
+        let params = QueryParameters::new().first_seen_days(DaysRange("14-7".to_string()));
+        assert_eq!(params.canonical(), params);
     }
 
     #[test]
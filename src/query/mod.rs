@@ -0,0 +1,754 @@
+//! Local Query Evaluation
+//!
+//! This module applies the selection, ordering and pagination semantics of the Onionoo
+//! server to documents held in memory, so that many views can be derived from a single
+//! fetched document without further requests. The same evaluation backs the mock
+//! server and the snapshot server in `mirror`.
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::models::{
+    BridgeDetails, BridgeSummary, DetailsResponse, OnionooResponse, RelayDetails, RelaySummary,
+    SummaryResponse,
+};
+use crate::parameters::{
+    AsList, AsNumber, BoolValue, CountryCode, Date, DaysRange, Direction, OrderKey, OrderList,
+    ParameterError, QueryParameters, TypeValue, all::SELECTION_PARAMS,
+};
+use crate::utils::parse_timestamp;
+
+/// Attributes of a relay or bridge that query parameters are evaluated against
+///
+/// Attributes that a document type does not carry default to `None` or empty, so
+/// filters on them never match.
+pub(crate) trait Record {
+    /// Relay fingerprint or hashed bridge fingerprint
+    fn fingerprint(&self) -> &str;
+    fn nickname(&self) -> &str;
+    fn running(&self) -> bool;
+    /// Onion-routing and exit addresses, possibly with ports
+    fn addresses(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    fn country(&self) -> Option<&str> {
+        None
+    }
+    fn as_number(&self) -> Option<u32> {
+        None
+    }
+    fn as_name(&self) -> Option<&str> {
+        None
+    }
+    fn flags(&self) -> &[String] {
+        &[]
+    }
+    fn consensus_weight(&self) -> Option<u64> {
+        None
+    }
+    fn first_seen(&self) -> Option<&str> {
+        None
+    }
+    fn last_seen(&self) -> Option<&str> {
+        None
+    }
+    fn contact(&self) -> Option<&str> {
+        None
+    }
+    fn effective_family(&self) -> &[String] {
+        &[]
+    }
+    fn version(&self) -> Option<&str> {
+        None
+    }
+    fn platform(&self) -> Option<&str> {
+        None
+    }
+    fn host_names(&self) -> &[String] {
+        &[]
+    }
+    fn recommended_version(&self) -> Option<bool> {
+        None
+    }
+}
+
+impl Record for RelayDetails {
+    fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+    fn nickname(&self) -> &str {
+        &self.nickname
+    }
+    fn running(&self) -> bool {
+        self.running
+    }
+    fn addresses(&self) -> Vec<&str> {
+        self.or_addresses
+            .iter()
+            .chain(self.exit_addresses.iter().flatten())
+            .map(String::as_str)
+            .collect()
+    }
+    fn country(&self) -> Option<&str> {
+        self.country.as_ref().map(CountryCode::as_str)
+    }
+    fn as_number(&self) -> Option<u32> {
+        self.as_number.map(|number| number.0)
+    }
+    fn as_name(&self) -> Option<&str> {
+        self.as_name.as_deref()
+    }
+    fn flags(&self) -> &[String] {
+        self.flags.as_deref().unwrap_or_default()
+    }
+    fn consensus_weight(&self) -> Option<u64> {
+        Some(self.consensus_weight)
+    }
+    fn first_seen(&self) -> Option<&str> {
+        Some(&self.first_seen)
+    }
+    fn last_seen(&self) -> Option<&str> {
+        Some(&self.last_seen)
+    }
+    fn contact(&self) -> Option<&str> {
+        self.contact.as_deref()
+    }
+    fn effective_family(&self) -> &[String] {
+        self.effective_family.as_deref().unwrap_or_default()
+    }
+    fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+    fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+    fn host_names(&self) -> &[String] {
+        self.verified_host_names.as_deref().unwrap_or_default()
+    }
+    fn recommended_version(&self) -> Option<bool> {
+        self.recommended_version
+    }
+}
+
+impl Record for BridgeDetails {
+    fn fingerprint(&self) -> &str {
+        &self.hashed_fingerprint
+    }
+    fn nickname(&self) -> &str {
+        &self.nickname
+    }
+    fn running(&self) -> bool {
+        self.running
+    }
+    fn flags(&self) -> &[String] {
+        self.flags.as_deref().unwrap_or_default()
+    }
+    fn first_seen(&self) -> Option<&str> {
+        Some(&self.first_seen)
+    }
+    fn last_seen(&self) -> Option<&str> {
+        Some(&self.last_seen)
+    }
+    fn contact(&self) -> Option<&str> {
+        self.contact.as_deref()
+    }
+    fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+    fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+    fn recommended_version(&self) -> Option<bool> {
+        self.recommended_version
+    }
+}
+
+impl Record for RelaySummary {
+    fn fingerprint(&self) -> &str {
+        &self.f
+    }
+    fn nickname(&self) -> &str {
+        &self.n
+    }
+    fn running(&self) -> bool {
+        self.r
+    }
+    fn addresses(&self) -> Vec<&str> {
+        self.a.iter().map(String::as_str).collect()
+    }
+}
+
+impl Record for BridgeSummary {
+    fn fingerprint(&self) -> &str {
+        &self.h
+    }
+    fn nickname(&self) -> &str {
+        &self.n
+    }
+    fn running(&self) -> bool {
+        self.r
+    }
+}
+
+/// Inclusive range of days, open-ended if `max` is `None`
+#[derive(Debug, Clone, Copy)]
+struct Days {
+    min: i64,
+    max: Option<i64>,
+}
+
+impl Days {
+    fn parse(range: &DaysRange) -> Result<Self, ParameterError> {
+        let (min, max) = range.bounds()?;
+        Ok(Days {
+            min: min.into(),
+            max: max.map(i64::from),
+        })
+    }
+
+    fn contains(&self, days: i64) -> bool {
+        days >= self.min && self.max.is_none_or(|max| days <= max)
+    }
+}
+
+/// Leading dotted numbers of a version, ignoring suffixes such as "-alpha"
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .trim()
+        .split(['-', ' '])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// Range of versions whose bounds match by dotted-number prefix
+#[derive(Debug, Clone)]
+struct VersionRange {
+    from: Vec<u32>,
+    to: Vec<u32>,
+}
+
+impl VersionRange {
+    /// Parses a comma-separated list of versions and "a..b" ranges
+    fn parse(list: &str) -> Result<Vec<Self>, ParameterError> {
+        list.split(',')
+            .map(|part| {
+                let (from, to) = part.split_once("..").unwrap_or((part, part));
+                let (from, to) = (parse_version(from), parse_version(to));
+                if from.is_empty() && to.is_empty() {
+                    return Err(ParameterError::InvalidValue(
+                        "version".to_string(),
+                        list.to_string(),
+                    ));
+                }
+                Ok(VersionRange { from, to })
+            })
+            .collect()
+    }
+
+    fn contains(&self, version: &[u32]) -> bool {
+        let prefix = |bound: &[u32]| &version[..bound.len().min(version.len())];
+        (self.from.is_empty() || prefix(&self.from) >= &self.from[..])
+            && (self.to.is_empty() || prefix(&self.to) <= &self.to[..])
+    }
+}
+
+/// SHA-1 hash of a hex-encoded fingerprint, upper-case hex-encoded
+///
+/// Onionoo matches `lookup` against fingerprints and their hashes, so that clients
+/// never need to reveal a fingerprint; for bridges the hash is taken of the already
+/// hashed fingerprint. Returns `None` if the fingerprint is not valid hex.
+fn hashed_fingerprint(fingerprint: &str) -> Option<String> {
+    let bytes = (0..fingerprint.len())
+        .step_by(2)
+        .map(|i| {
+            fingerprint
+                .get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    Some(
+        sha1_smol::Sha1::from(bytes)
+            .digest()
+            .to_string()
+            .to_uppercase(),
+    )
+}
+
+/// Whether every space-separated part of `needle` is contained in `haystack`
+fn contains_all_parts(haystack: &str, needle: &str) -> bool {
+    let haystack = haystack.to_lowercase();
+    needle
+        .to_lowercase()
+        .split_whitespace()
+        .all(|part| haystack.contains(part))
+}
+
+/// Term of the `search` parameter
+enum SearchTerm {
+    /// Unqualified term matched against nickname, fingerprint and addresses
+    Plain(String),
+    /// Qualified `name:value` term, evaluated like the parameter of the same name
+    Qualified(Box<QueryParameters>),
+}
+
+/// Query parameters parsed and validated for local evaluation
+pub(crate) struct Filter<'p> {
+    params: &'p QueryParameters,
+    search: Vec<SearchTerm>,
+    as_numbers: Option<Vec<AsNumber>>,
+    first_seen_days: Option<Days>,
+    last_seen_days: Option<Days>,
+    first_seen_since: Option<NaiveDate>,
+    last_seen_since: Option<NaiveDate>,
+    versions: Option<Vec<VersionRange>>,
+    order: Vec<(OrderKey, Direction)>,
+    now: NaiveDateTime,
+}
+
+impl<'p> Filter<'p> {
+    /// Parses the parameters, evaluating days ranges relative to `now`
+    pub fn new(params: &'p QueryParameters, now: NaiveDateTime) -> Result<Self, ParameterError> {
+        let search = params
+            .search
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|term| match term.split_once(':') {
+                Some((name, value)) if name != "search" && SELECTION_PARAMS.contains(&name) => {
+                    QueryParameters::from_query_string(&format!("{}={}", name, value))
+                        .map(|params| SearchTerm::Qualified(Box::new(params)))
+                }
+                _ => Ok(SearchTerm::Plain(term.to_lowercase())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let as_numbers = params.as_param.as_ref().map(AsList::numbers).transpose()?;
+
+        let order = params
+            .order
+            .as_ref()
+            .map(OrderList::keys)
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Filter {
+            params,
+            search,
+            as_numbers,
+            first_seen_days: params
+                .first_seen_days
+                .as_ref()
+                .map(Days::parse)
+                .transpose()?,
+            last_seen_days: params
+                .last_seen_days
+                .as_ref()
+                .map(Days::parse)
+                .transpose()?,
+            first_seen_since: params
+                .first_seen_since
+                .as_ref()
+                .map(Date::to_naive_date)
+                .transpose()?,
+            last_seen_since: params
+                .last_seen_since
+                .as_ref()
+                .map(Date::to_naive_date)
+                .transpose()?,
+            versions: params
+                .version
+                .as_ref()
+                .map(|versions| VersionRange::parse(versions.as_str()))
+                .transpose()?,
+            order,
+            now,
+        })
+    }
+
+    /// Whether any parameter other than `type` restricts the selection
+    fn has_attribute_filter(&self) -> bool {
+        let p = self.params;
+        p.running.is_some()
+            || p.search.is_some()
+            || p.lookup.is_some()
+            || p.country.is_some()
+            || p.as_param.is_some()
+            || p.as_name.is_some()
+            || p.flag.is_some()
+            || p.first_seen_days.is_some()
+            || p.last_seen_days.is_some()
+            || p.first_seen_since.is_some()
+            || p.last_seen_since.is_some()
+            || p.contact.is_some()
+            || p.family.is_some()
+            || p.version.is_some()
+            || p.os.is_some()
+            || p.host_name.is_some()
+            || p.recommended_version.is_some()
+    }
+
+    /// Returns whether a record is selected
+    ///
+    /// Entries without a record only match if no attribute filter is set. `family`
+    /// holds the upper-case fingerprints selected by the `family` parameter.
+    fn matches(
+        &self,
+        record: Option<&dyn Record>,
+        is_relay: bool,
+        family: Option<&HashSet<String>>,
+    ) -> bool {
+        let p = self.params;
+        match p.type_param {
+            Some(TypeValue::Relay) if !is_relay => return false,
+            Some(TypeValue::Bridge) if is_relay => return false,
+            _ => {}
+        }
+        let Some(record) = record else {
+            return !self.has_attribute_filter();
+        };
+
+        // These attributes only exist for relays, so bridges never match them.
+        if !is_relay && !p.relay_only_params().is_empty() {
+            return false;
+        }
+
+        let days_ago = |timestamp: Option<&str>| {
+            timestamp
+                .and_then(parse_timestamp)
+                .map(|t| (self.now - t).num_days())
+        };
+        let seen_since = |timestamp: Option<&str>, date: NaiveDate| {
+            timestamp
+                .and_then(parse_timestamp)
+                .is_some_and(|t| t.date() >= date)
+        };
+
+        p.running
+            .is_none_or(|running| record.running() == (running == BoolValue::True))
+            && self
+                .search
+                .iter()
+                .all(|term| term.matches(record, is_relay, self.now))
+            && p.lookup.as_ref().is_none_or(|lookup| {
+                let lookup = lookup.trim_start_matches('$');
+                record.fingerprint().eq_ignore_ascii_case(lookup)
+                    || hashed_fingerprint(record.fingerprint())
+                        .is_some_and(|hashed| hashed.eq_ignore_ascii_case(lookup))
+            })
+            && p.country.as_ref().is_none_or(|country| {
+                record
+                    .country()
+                    .is_some_and(|c| c.eq_ignore_ascii_case(country.as_str()))
+            })
+            && self.as_numbers.as_ref().is_none_or(|numbers| {
+                record
+                    .as_number()
+                    .is_some_and(|n| numbers.contains(&AsNumber(n)))
+            })
+            && p.as_name.as_ref().is_none_or(|name| {
+                record
+                    .as_name()
+                    .is_some_and(|as_name| contains_all_parts(as_name, name))
+            })
+            && p.flag
+                .as_ref()
+                .is_none_or(|flag| record.flags().iter().any(|f| f.eq_ignore_ascii_case(flag)))
+            && self
+                .first_seen_days
+                .is_none_or(|days| days_ago(record.first_seen()).is_some_and(|d| days.contains(d)))
+            && self
+                .last_seen_days
+                .is_none_or(|days| days_ago(record.last_seen()).is_some_and(|d| days.contains(d)))
+            && self
+                .first_seen_since
+                .is_none_or(|date| seen_since(record.first_seen(), date))
+            && self
+                .last_seen_since
+                .is_none_or(|date| seen_since(record.last_seen(), date))
+            && p.contact.as_ref().is_none_or(|contact| {
+                record
+                    .contact()
+                    .is_some_and(|c| contains_all_parts(c, contact))
+            })
+            && family.is_none_or(|family| family.contains(&record.fingerprint().to_uppercase()))
+            && self.versions.as_ref().is_none_or(|ranges| {
+                let version = record.version().map(parse_version).unwrap_or_default();
+                !version.is_empty() && ranges.iter().any(|r| r.contains(&version))
+            })
+            && p.os.as_ref().is_none_or(|os| {
+                record
+                    .platform()
+                    .and_then(|platform| platform.split_once(" on "))
+                    .is_some_and(|(_, platform_os)| {
+                        platform_os.to_lowercase().starts_with(&os.to_lowercase())
+                    })
+            })
+            && p.host_name.as_ref().is_none_or(|host_name| {
+                let host_name = host_name.to_lowercase();
+                record
+                    .host_names()
+                    .iter()
+                    .any(|h| h.to_lowercase().ends_with(&host_name))
+            })
+            && p.recommended_version.is_none_or(|recommended| {
+                record.recommended_version() == Some(recommended == BoolValue::True)
+            })
+    }
+
+    /// Keeps the selected entries, sorted by the `order` keys
+    fn apply<T>(
+        &self,
+        entries: Vec<(T, Option<&dyn Record>)>,
+        is_relay: bool,
+        family: Option<&HashSet<String>>,
+    ) -> Vec<T> {
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .filter(|(_, record)| self.matches(*record, is_relay, family))
+            .collect();
+        entries.sort_by(|(_, a), (_, b)| self.compare(*a, *b));
+        entries.into_iter().map(|(entry, _)| entry).collect()
+    }
+
+    /// Compares two records by the `order` keys
+    fn compare(&self, a: Option<&dyn Record>, b: Option<&dyn Record>) -> Ordering {
+        for &(key, direction) in &self.order {
+            let ordering = match key {
+                OrderKey::ConsensusWeight => a
+                    .and_then(|r| r.consensus_weight())
+                    .cmp(&b.and_then(|r| r.consensus_weight())),
+                OrderKey::FirstSeen => a
+                    .and_then(|r| r.first_seen())
+                    .cmp(&b.and_then(|r| r.first_seen())),
+            };
+            let ordering = if direction == Direction::Descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl SearchTerm {
+    fn matches(&self, record: &dyn Record, is_relay: bool, now: NaiveDateTime) -> bool {
+        match self {
+            SearchTerm::Qualified(params) => Filter::new(params, now)
+                .is_ok_and(|filter| filter.matches(Some(record), is_relay, None)),
+            SearchTerm::Plain(term) => {
+                let fingerprint = term.trim_start_matches('$');
+                let address = term.trim_start_matches('[');
+                record.nickname().to_lowercase().contains(term.as_str())
+                    || (!fingerprint.is_empty()
+                        && record.fingerprint().to_lowercase().starts_with(fingerprint))
+                    || (!address.is_empty()
+                        && record.addresses().iter().any(|a| {
+                            a.trim_start_matches('[')
+                                .to_lowercase()
+                                .starts_with(address)
+                        }))
+            }
+        }
+    }
+}
+
+/// Relays and bridges remaining after selection, ordering and pagination
+pub(crate) struct Selection<R, B> {
+    pub relays: Vec<R>,
+    pub bridges: Vec<B>,
+    pub relays_skipped: Option<u64>,
+    pub relays_truncated: Option<u64>,
+    pub bridges_skipped: Option<u64>,
+    pub bridges_truncated: Option<u64>,
+}
+
+/// Applies selection, ordering, offset and limit to relay and bridge entries
+///
+/// Each entry is paired with the record its attributes are read from. Relays are
+/// skipped and kept before bridges, as on the Onionoo server.
+pub(crate) fn select<R, B>(
+    filter: &Filter<'_>,
+    relays: Vec<(R, Option<&dyn Record>)>,
+    bridges: Vec<(B, Option<&dyn Record>)>,
+) -> Selection<R, B> {
+    // The family parameter selects the given relay and its effective family members.
+    let family = filter.params.family.as_ref().map(|fingerprint| {
+        let fingerprint = fingerprint.trim_start_matches('$').to_uppercase();
+        let mut members: HashSet<String> = relays
+            .iter()
+            .filter_map(|(_, record)| *record)
+            .find(|record| record.fingerprint().eq_ignore_ascii_case(&fingerprint))
+            .map(|record| {
+                record
+                    .effective_family()
+                    .iter()
+                    .map(|fp| fp.trim_start_matches('$').to_uppercase())
+                    .collect()
+            })
+            .unwrap_or_default();
+        members.insert(fingerprint);
+        members
+    });
+
+    let mut relays = filter.apply(relays, true, family.as_ref());
+    let mut bridges = filter.apply(bridges, false, family.as_ref());
+
+    let nonzero = |n: usize| (n > 0).then_some(n as u64);
+
+    let mut offset = filter.params.offset.unwrap_or(0) as usize;
+    let relays_skipped = offset.min(relays.len());
+    relays.drain(..relays_skipped);
+    offset -= relays_skipped;
+    let bridges_skipped = offset.min(bridges.len());
+    bridges.drain(..bridges_skipped);
+
+    let (mut relays_truncated, mut bridges_truncated) = (0, 0);
+    if let Some(limit) = filter.params.limit {
+        let limit = limit as usize;
+        let relays_kept = limit.min(relays.len());
+        relays_truncated = relays.len() - relays_kept;
+        relays.truncate(relays_kept);
+        let bridges_kept = (limit - relays_kept).min(bridges.len());
+        bridges_truncated = bridges.len() - bridges_kept;
+        bridges.truncate(bridges_kept);
+    }
+
+    Selection {
+        relays,
+        bridges,
+        relays_skipped: nonzero(relays_skipped),
+        relays_truncated: nonzero(relays_truncated),
+        bridges_skipped: nonzero(bridges_skipped),
+        bridges_truncated: nonzero(bridges_truncated),
+    }
+}
+
+/// Documents that query parameters can be applied to in memory
+///
+/// This is implemented for details and summary documents. Selection, ordering and
+/// pagination follow the semantics of the Onionoo server, with days ranges evaluated
+/// relative to the document's `relays_published` time. The `fields` parameter is
+/// ignored, since typed documents cannot omit fields.
+///
+/// Summary documents only carry nicknames, fingerprints, addresses and the running
+/// state, so filters on other attributes select nothing and ordering has no effect.
+///
+/// # Examples
+///
+/// ```
+/// use onionoo::{QueryParameters, models::DetailsResponse, query::LocalQuery};
+///
+/// fn running_exits_in_germany(details: &DetailsResponse) -> DetailsResponse {
+///     let params = QueryParameters::new()
+///         .running(onionoo::selection::BoolValue::True)
+///         .country("de")
+///         .flag("Exit");
+///     details.query(&params).expect("valid parameters")
+/// }
+/// ```
+pub trait LocalQuery: Sized {
+    /// Returns a copy of the document containing only the selected relays and bridges
+    ///
+    /// Fails if a parameter value cannot be evaluated, e.g. an unknown `order` key or an
+    /// invalid days range, where the Onionoo server would respond with `400 Bad Request`.
+    fn query(&self, params: &QueryParameters) -> Result<Self, ParameterError>;
+}
+
+impl LocalQuery for DetailsResponse {
+    fn query(&self, params: &QueryParameters) -> Result<Self, ParameterError> {
+        query_document(self, params)
+    }
+}
+
+impl LocalQuery for SummaryResponse {
+    fn query(&self, params: &QueryParameters) -> Result<Self, ParameterError> {
+        query_document(self, params)
+    }
+}
+
+fn query_document<R, B>(
+    document: &OnionooResponse<R, B>,
+    params: &QueryParameters,
+) -> Result<OnionooResponse<R, B>, ParameterError>
+where
+    R: Record + Clone,
+    B: Record + Clone,
+{
+    let now = parse_timestamp(&document.relays_published).unwrap_or_else(|| Utc::now().naive_utc());
+    let filter = Filter::new(params, now)?;
+
+    let relays = document
+        .relays
+        .iter()
+        .map(|r| (r, Some(r as &dyn Record)))
+        .collect();
+    let bridges = document
+        .bridges
+        .iter()
+        .map(|b| (b, Some(b as &dyn Record)))
+        .collect();
+    let selection = select(&filter, relays, bridges);
+
+    Ok(OnionooResponse {
+        version: document.version.clone(),
+        next_major_version_scheduled: document.next_major_version_scheduled.clone(),
+        build_revision: document.build_revision.clone(),
+        relays_published: document.relays_published.clone(),
+        relays_skipped: selection.relays_skipped,
+        relays: selection.relays.into_iter().cloned().collect(),
+        relays_truncated: selection.relays_truncated,
+        bridges_published: document.bridges_published.clone(),
+        bridges_skipped: selection.bridges_skipped,
+        bridges: selection.bridges.into_iter().cloned().collect(),
+        bridges_truncated: selection.bridges_truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_range_parsing() {
+        let days = |range: &str| Days::parse(&DaysRange(range.to_string()));
+        let exact = days("7").unwrap();
+        assert!(exact.contains(7) && !exact.contains(8));
+        let at_least = days("30-").unwrap();
+        assert!(at_least.contains(3000) && !at_least.contains(29));
+        let at_most = days("-90").unwrap();
+        assert!(at_most.contains(0) && !at_most.contains(91));
+        assert!(days("14-7").is_err());
+        assert!(days("abc").is_err());
+    }
+
+    #[test]
+    fn test_version_ranges() {
+        let ranges = VersionRange::parse("0.4.7..0.4.8,0.3.5").unwrap();
+        let matches = |v: &str| ranges.iter().any(|r| r.contains(&parse_version(v)));
+        assert!(matches("0.4.7.16"));
+        assert!(matches("0.4.8.13"));
+        assert!(matches("0.3.5.1-alpha"));
+        assert!(!matches("0.4.9.1-alpha"));
+        assert!(!matches("0.4.6.10"));
+        assert!(!matches("0.3.50.1"));
+        assert!(VersionRange::parse("latest").is_err());
+    }
+
+    #[test]
+    fn test_hashed_fingerprint() {
+        assert_eq!(
+            hashed_fingerprint("9695DFC35FFEB861329B9F1AB04C46397020CE31").as_deref(),
+            Some("DD51A2029FED0276866332EACC6459E1D015E349")
+        );
+        assert_eq!(hashed_fingerprint("not a fingerprint"), None);
+        assert_eq!(hashed_fingerprint("ABC"), None);
+    }
+}
@@ -0,0 +1,367 @@
+//! Onionoo-Compatible HTTP Serving
+//!
+//! Minimal HTTP/1.1 plumbing shared by the mock server in `mock`, the snapshot
+//! server in `mirror` and the metrics server in `exporter`. Each connection carries a
+//! single request and is closed after the response.
+
+use std::io;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::encoding::{ContentEncoding, encode};
+use crate::transport::find_header;
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+use {
+    crate::endpoints::Endpoint,
+    crate::models::{DetailsResponse, SummaryResponse},
+    crate::parameters::{ParameterError, QueryParameters},
+    crate::query::{Filter, Record, select},
+    crate::utils::parse_timestamp,
+    chrono::{NaiveDateTime, Utc},
+    serde::Deserialize,
+    serde_json::{Map, Value},
+    std::collections::HashMap,
+    std::sync::Arc,
+};
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+/// Documents served by path, with the attribute index used to filter them
+///
+/// Documents are parsed once up front, so that requests only copy the selected entries.
+#[derive(Default)]
+pub(crate) struct Documents {
+    documents: HashMap<&'static str, Arc<Value>>,
+    index: Index,
+}
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+impl Documents {
+    /// Indexes the details document, or the summary document if there is none
+    pub fn new(documents: HashMap<&'static str, Value>) -> Self {
+        let index = if let Some(document) = documents
+            .get(Endpoint::Details.path())
+            .and_then(|d| DetailsResponse::deserialize(d).ok())
+        {
+            Index::from_details(document)
+        } else if let Some(document) = documents
+            .get(Endpoint::Summary.path())
+            .and_then(|d| SummaryResponse::deserialize(d).ok())
+        {
+            Index::from_summary(document)
+        } else {
+            Index::default()
+        };
+        let documents = documents
+            .into_iter()
+            .map(|(path, document)| (path, Arc::new(document)))
+            .collect();
+        Self { documents, index }
+    }
+
+    /// Renders the document for a path with the query applied
+    pub fn render(&self, path: &str, query: &str) -> (u16, String) {
+        let Some(document) = self.documents.get(path) else {
+            return (404, String::new());
+        };
+        let params = match QueryParameters::from_query_string(query) {
+            Ok(params) => params,
+            Err(e) => return (400, e.to_string()),
+        };
+
+        match filter_document(
+            &params,
+            &self.index,
+            document,
+            path == Endpoint::Details.path(),
+        ) {
+            Ok(value) => (200, value.to_string()),
+            Err(e) => (400, e.to_string()),
+        }
+    }
+}
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+/// Relay and bridge records indexed by (hashed) fingerprint
+///
+/// Documents other than details and summary documents only contain fingerprints, so
+/// their entries are filtered using the attributes of the indexed records. Days ranges
+/// are evaluated relative to the publication time of the indexed document.
+#[derive(Default)]
+struct Index {
+    relays: HashMap<String, Box<dyn Record + Send + Sync>>,
+    bridges: HashMap<String, Box<dyn Record + Send + Sync>>,
+    published: Option<NaiveDateTime>,
+}
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+impl Index {
+    fn insert_all<R, B>(relays: Vec<R>, bridges: Vec<B>, published: &str) -> Self
+    where
+        R: Record + Send + Sync + 'static,
+        B: Record + Send + Sync + 'static,
+    {
+        let key = |r: &dyn Record| r.fingerprint().to_uppercase();
+        Self {
+            relays: relays
+                .into_iter()
+                .map(|r| (key(&r), Box::new(r) as Box<dyn Record + Send + Sync>))
+                .collect(),
+            bridges: bridges
+                .into_iter()
+                .map(|b| (key(&b), Box::new(b) as Box<dyn Record + Send + Sync>))
+                .collect(),
+            published: parse_timestamp(published),
+        }
+    }
+
+    pub fn from_details(document: DetailsResponse) -> Self {
+        Self::insert_all(
+            document.relays,
+            document.bridges,
+            &document.relays_published,
+        )
+    }
+
+    pub fn from_summary(document: SummaryResponse) -> Self {
+        Self::insert_all(
+            document.relays,
+            document.bridges,
+            &document.relays_published,
+        )
+    }
+
+    /// Reference time for days ranges
+    fn now(&self) -> NaiveDateTime {
+        self.published.unwrap_or_else(|| Utc::now().naive_utc())
+    }
+}
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+/// Fingerprint of a relay or bridge object in any document type
+fn entry_fingerprint(entry: &Value) -> Option<String> {
+    ["fingerprint", "hashed_fingerprint", "f", "h"]
+        .iter()
+        .find_map(|key| entry.get(key).and_then(Value::as_str))
+        .map(str::to_uppercase)
+}
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+/// Pairs each entry of a document's list with the indexed record of the same
+/// fingerprint
+fn with_records<'a>(
+    document: &'a Map<String, Value>,
+    key: &str,
+    records: &'a HashMap<String, Box<dyn Record + Send + Sync>>,
+) -> Vec<(&'a Value, Option<&'a dyn Record>)> {
+    let entries = match document.get(key) {
+        Some(Value::Array(entries)) => entries.as_slice(),
+        _ => &[],
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            let record = entry_fingerprint(entry)
+                .and_then(|fp| records.get(&fp))
+                .map(|r| r.as_ref() as &dyn Record);
+            (entry, record)
+        })
+        .collect()
+}
+
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+/// Applies the parameters to a JSON document
+///
+/// Only the selected entries are copied. The `fields` parameter is only honored if
+/// `apply_fields` is set, since the Onionoo server only supports it for details
+/// documents.
+fn filter_document(
+    params: &QueryParameters,
+    index: &Index,
+    document: &Value,
+    apply_fields: bool,
+) -> Result<Value, ParameterError> {
+    let filter = Filter::new(params, index.now())?;
+    let Some(object) = document.as_object() else {
+        return Ok(document.clone());
+    };
+
+    let relays = with_records(object, "relays", &index.relays);
+    let bridges = with_records(object, "bridges", &index.bridges);
+    let selection = select(&filter, relays, bridges);
+
+    let fields: Option<Vec<String>> =
+        params
+            .fields
+            .as_ref()
+            .filter(|_| apply_fields)
+            .map(|fields| {
+                fields
+                    .as_str()
+                    .split(',')
+                    .map(|f| f.trim().to_lowercase())
+                    .collect()
+            });
+    let copy = |entry: &Value| match (&fields, entry.as_object()) {
+        (Some(fields), Some(entry)) => Value::Object(
+            entry
+                .iter()
+                .filter(|(key, _)| fields.contains(&key.to_lowercase()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        ),
+        _ => entry.clone(),
+    };
+
+    let mut output: Map<String, Value> = object
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "relays" | "bridges"))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    output.insert(
+        "relays".to_string(),
+        selection.relays.into_iter().map(copy).collect(),
+    );
+    output.insert(
+        "bridges".to_string(),
+        selection.bridges.into_iter().map(copy).collect(),
+    );
+    for (key, value) in [
+        ("relays_skipped", selection.relays_skipped),
+        ("relays_truncated", selection.relays_truncated),
+        ("bridges_skipped", selection.bridges_skipped),
+        ("bridges_truncated", selection.bridges_truncated),
+    ] {
+        match value {
+            Some(count) => output.insert(key.to_string(), Value::from(count)),
+            None => output.remove(key),
+        };
+    }
+
+    Ok(Value::Object(output))
+}
+
+/// Maximum size of a request head
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+/// Request line and headers of a request
+pub(crate) struct RequestHead {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// First of the offered encodings that the client accepts and that can be produced
+    pub fn negotiate(&self, offered: &[ContentEncoding]) -> Option<ContentEncoding> {
+        let accepted: Vec<&str> = find_header(&self.headers, "accept-encoding")?
+            .split(',')
+            .filter_map(|token| {
+                let mut parts = token.split(';').map(str::trim);
+                let name = parts.next()?;
+                // `q=0` marks an encoding as not acceptable
+                let rejected = parts.any(|parameter| {
+                    parameter
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        == Some(0.0)
+                });
+                (!rejected).then_some(name)
+            })
+            .collect();
+        offered.iter().copied().find(|encoding| {
+            encoding.is_supported()
+                && accepted
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(encoding.as_str()))
+        })
+    }
+}
+
+/// Reads a request head
+///
+/// Returns `None` if the connection is closed or the head is too large.
+pub(crate) async fn read_request(stream: &mut TcpStream) -> io::Result<Option<RequestHead>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || request.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let head = String::from_utf8_lossy(&request);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default().to_string();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    Ok(Some(RequestHead {
+        method,
+        target,
+        headers,
+    }))
+}
+
+/// Writes a JSON response, compressed with `encoding` if given, and closes the connection
+#[cfg(any(feature = "test-util", feature = "mirror"))]
+pub(crate) async fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    body: &str,
+    encoding: Option<ContentEncoding>,
+) -> io::Result<()> {
+    write_typed_response(stream, status, "application/json", body, encoding).await
+}
+
+/// Writes a response with the given content type, compressed with `encoding` if given,
+/// and closes the connection
+pub(crate) async fn write_typed_response(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &str,
+    encoding: Option<ContentEncoding>,
+) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    };
+    let encoded =
+        encoding.and_then(|encoding| Some((encoding, encode(encoding, body.as_bytes())?)));
+    let (content_encoding, body) = match &encoded {
+        Some((encoding, encoded)) => (
+            format!("Content-Encoding: {}\r\n", encoding),
+            encoded.as_slice(),
+        ),
+        None => (String::new(), body.as_bytes()),
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        content_type,
+        content_encoding,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    if status != 304 {
+        stream.write_all(body).await?;
+    }
+    stream.shutdown().await
+}
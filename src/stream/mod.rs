@@ -0,0 +1,491 @@
+//! Streaming Deserialization
+//!
+//! Full details and bandwidth documents are tens of megabytes. [`DocumentParser`] reads
+//! a document in chunks as it arrives and deserializes its relays and bridges one at a
+//! time, so only the entry being parsed is held in memory. The remaining top-level
+//! fields are collected into a [`DocumentHeader`].
+//!
+//! [`Client::stream`](crate::Client::stream) feeds the parser from the response body.
+
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::client::{BODY_SNIPPET_LEN, ClientError, body_snippet};
+use crate::models::DocumentHeader;
+
+/// Relay or bridge object of a document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<T, U> {
+    Relay(T),
+    Bridge(U),
+}
+
+/// Top-level member whose value is being read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Key,
+    Header,
+    Relay,
+    Bridge,
+}
+
+/// Failure while processing a byte
+enum StepError {
+    /// Malformed document, with a message
+    Syntax(String),
+    /// Entry that does not match its type
+    Entry(ClientError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the opening brace
+    Start,
+    /// Expecting a key, or the closing brace if `first`
+    Key { first: bool },
+    /// Expecting the colon after a key
+    Colon,
+    /// Expecting a member value
+    Value,
+    /// Reading a complete JSON value
+    Raw(Target),
+    /// Inside the relays or bridges array, expecting an entry, or `]` if `first`
+    Entries { bridges: bool, first: bool },
+    /// After an entry, expecting `,` or `]`
+    EntriesNext { bridges: bool },
+    /// After a member value, expecting `,` or `}`
+    Next,
+    /// After the closing brace
+    Done,
+}
+
+/// Incremental parser for Onionoo documents
+///
+/// # Examples
+///
+/// ```
+/// use onionoo::models::{BridgeSummary, RelaySummary};
+/// use onionoo::stream::{DocumentParser, Entry};
+///
+/// let document = br#"{"version":"8.0","relays_published":"2024-01-01 00:00:00",
+///     "relays":[{"n":"moria1","f":"9695DFC35FFEB861329B9F1AB04C46397020CE31","a":[],"r":true}],
+///     "bridges_published":"2024-01-01 00:00:00","bridges":[]}"#;
+///
+/// let mut parser = DocumentParser::<RelaySummary, BridgeSummary>::new();
+/// let mut nicknames = Vec::new();
+/// for chunk in document.chunks(16) {
+///     parser
+///         .feed(chunk, &mut |entry| {
+///             if let Entry::Relay(relay) = entry {
+///                 nicknames.push(relay.n);
+///             }
+///         })
+///         .unwrap();
+/// }
+/// let header = parser.finish().unwrap();
+/// assert_eq!(header.version, "8.0");
+/// assert_eq!(nicknames, ["moria1"]);
+/// ```
+#[derive(Debug)]
+pub struct DocumentParser<T, U> {
+    state: State,
+    /// Bytes of the value being read
+    buffer: Vec<u8>,
+    /// Nesting depth of the value being read
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Most recent key
+    key: String,
+    header: Map<String, Value>,
+    relays: usize,
+    bridges: usize,
+    /// Position of the next byte, starting at line 1, column 1
+    line: usize,
+    column: usize,
+    /// Position of the first byte of the value being read
+    start: (usize, usize),
+    entries: PhantomData<fn() -> (T, U)>,
+}
+
+impl<T, U> Default for DocumentParser<T, U>
+where
+    T: DeserializeOwned,
+    U: DeserializeOwned,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, U> DocumentParser<T, U>
+where
+    T: DeserializeOwned,
+    U: DeserializeOwned,
+{
+    pub fn new() -> Self {
+        Self {
+            state: State::Start,
+            buffer: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            key: String::new(),
+            header: Map::new(),
+            relays: 0,
+            bridges: 0,
+            line: 1,
+            column: 1,
+            start: (1, 1),
+            entries: PhantomData,
+        }
+    }
+
+    /// Parse the next chunk of the document, passing each complete entry to `on_entry`
+    pub fn feed<F>(&mut self, chunk: &[u8], on_entry: &mut F) -> Result<(), ClientError>
+    where
+        F: FnMut(Entry<T, U>),
+    {
+        let mut index = 0;
+        while index < chunk.len() {
+            let byte = chunk[index];
+            let consumed = self.step(byte, on_entry).map_err(|e| match e {
+                StepError::Syntax(message) => self.syntax_error(message, chunk, index),
+                StepError::Entry(error) => error,
+            })?;
+            if consumed {
+                if byte == b'\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the document is complete and return its header fields
+    pub fn finish(self) -> Result<DocumentHeader, ClientError> {
+        if self.state != State::Done {
+            return Err(self.syntax_error("EOF while parsing the document".to_string(), &[], 0));
+        }
+        Ok(serde_json::from_value(Value::Object(self.header))?)
+    }
+
+    /// Number of relays and bridges parsed so far
+    pub fn counts(&self) -> (usize, usize) {
+        (self.relays, self.bridges)
+    }
+
+    /// Processes one byte and returns whether it was consumed
+    fn step<F>(&mut self, byte: u8, on_entry: &mut F) -> Result<bool, StepError>
+    where
+        F: FnMut(Entry<T, U>),
+    {
+        let whitespace = byte.is_ascii_whitespace();
+        match self.state {
+            State::Raw(target) => {
+                let (consumed, complete) = self.scan(byte);
+                if complete {
+                    self.complete(target, on_entry)?;
+                }
+                return Ok(consumed);
+            }
+            _ if whitespace => {}
+            State::Start => self.expect(byte, b'{', State::Key { first: true })?,
+            State::Key { first } => match byte {
+                b'"' => self.begin(Target::Key),
+                b'}' if first => self.state = State::Done,
+                _ => return Err(unexpected(byte, "a key")),
+            },
+            State::Colon => self.expect(byte, b':', State::Value)?,
+            State::Value => match (self.key.as_str(), byte) {
+                ("relays", b'[') => {
+                    self.state = State::Entries {
+                        bridges: false,
+                        first: true,
+                    }
+                }
+                ("bridges", b'[') => {
+                    self.state = State::Entries {
+                        bridges: true,
+                        first: true,
+                    }
+                }
+                _ => self.begin(Target::Header),
+            },
+            State::Entries { bridges, first } => match byte {
+                b']' if first => self.state = State::Next,
+                _ => self.begin(if bridges {
+                    Target::Bridge
+                } else {
+                    Target::Relay
+                }),
+            },
+            State::EntriesNext { bridges } => match byte {
+                b',' => {
+                    self.state = State::Entries {
+                        bridges,
+                        first: false,
+                    }
+                }
+                b']' => self.state = State::Next,
+                _ => return Err(unexpected(byte, "`,` or `]`")),
+            },
+            State::Next => match byte {
+                b',' => self.state = State::Key { first: false },
+                b'}' => self.state = State::Done,
+                _ => return Err(unexpected(byte, "`,` or `}`")),
+            },
+            State::Done => return Err(unexpected(byte, "the end of the document")),
+        }
+        // Value starts are scanned again in the raw state
+        Ok(!matches!(self.state, State::Raw(_)))
+    }
+
+    fn expect(&mut self, byte: u8, expected: u8, next: State) -> Result<(), StepError> {
+        if byte != expected {
+            return Err(unexpected(byte, &format!("`{}`", expected as char)));
+        }
+        self.state = next;
+        Ok(())
+    }
+
+    fn begin(&mut self, target: Target) {
+        self.buffer.clear();
+        self.depth = 0;
+        self.in_string = false;
+        self.escaped = false;
+        self.start = (self.line, self.column);
+        self.state = State::Raw(target);
+    }
+
+    /// Adds a byte to the value being read and returns (consumed, complete)
+    fn scan(&mut self, byte: u8) -> (bool, bool) {
+        if self.in_string {
+            self.buffer.push(byte);
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+                return (true, self.depth == 0);
+            }
+            return (true, false);
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            // A delimiter ends a number or literal without being part of it
+            b'}' | b']' | b',' if self.depth == 0 => return (false, true),
+            b'}' | b']' => {
+                self.buffer.push(byte);
+                self.depth -= 1;
+                return (true, self.depth == 0);
+            }
+            _ if byte.is_ascii_whitespace() && self.depth == 0 => return (true, true),
+            _ => {}
+        }
+        self.buffer.push(byte);
+        (true, false)
+    }
+
+    fn complete<F>(&mut self, target: Target, on_entry: &mut F) -> Result<(), StepError>
+    where
+        F: FnMut(Entry<T, U>),
+    {
+        match target {
+            Target::Key => {
+                self.key = serde_json::from_slice(&self.buffer).map_err(syntax)?;
+                self.state = State::Colon;
+            }
+            Target::Header => {
+                let value = serde_json::from_slice(&self.buffer).map_err(syntax)?;
+                self.header.insert(std::mem::take(&mut self.key), value);
+                self.state = State::Next;
+            }
+            Target::Relay => {
+                let relay = self
+                    .entry("relays", self.relays)
+                    .map_err(StepError::Entry)?;
+                self.relays += 1;
+                self.state = State::EntriesNext { bridges: false };
+                on_entry(Entry::Relay(relay));
+            }
+            Target::Bridge => {
+                let bridge = self
+                    .entry("bridges", self.bridges)
+                    .map_err(StepError::Entry)?;
+                self.bridges += 1;
+                self.state = State::EntriesNext { bridges: true };
+                on_entry(Entry::Bridge(bridge));
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserializes the buffered entry, locating errors within the whole document
+    fn entry<E: DeserializeOwned>(&self, array: &str, index: usize) -> Result<E, ClientError> {
+        let mut deserializer = serde_json::Deserializer::from_slice(&self.buffer);
+        serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+            let path = match e.path().to_string().as_str() {
+                "." => format!("{}[{}]", array, index),
+                inner => format!("{}[{}].{}", array, index, inner),
+            };
+            let error = e.into_inner();
+            let (line, column) = match (error.line(), error.column()) {
+                (1, column) => (self.start.0, self.start.1 + column - 1),
+                (line, column) => (self.start.0 + line - 1, column),
+            };
+            ClientError::Deserialization {
+                message: error.to_string(),
+                path,
+                line,
+                column,
+                snippet: body_snippet(&self.buffer, error.line(), error.column()),
+                context: None,
+            }
+        })
+    }
+
+    /// Locates a syntax error at `chunk[index]`
+    fn syntax_error(&self, message: String, chunk: &[u8], index: usize) -> ClientError {
+        let path = match self.state {
+            State::Entries { bridges, .. } | State::EntriesNext { bridges } => {
+                let (array, count) = if bridges {
+                    ("bridges", self.bridges)
+                } else {
+                    ("relays", self.relays)
+                };
+                format!("{}[{}]", array, count)
+            }
+            State::Value | State::Next | State::Raw(Target::Header) => self.key.clone(),
+            _ => ".".to_string(),
+        };
+        let start = index.saturating_sub(BODY_SNIPPET_LEN / 2);
+        ClientError::Deserialization {
+            message: format!("{} at line {} column {}", message, self.line, self.column),
+            path,
+            line: self.line,
+            column: self.column,
+            snippet: body_snippet(&chunk[start..], 1, index - start + 1),
+            context: None,
+        }
+    }
+}
+
+fn unexpected(byte: u8, expected: &str) -> StepError {
+    StepError::Syntax(format!(
+        "expected {}, found `{}`",
+        expected,
+        byte.escape_ascii()
+    ))
+}
+
+fn syntax(error: serde_json::Error) -> StepError {
+    StepError::Syntax(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BridgeSummary, RelaySummary};
+
+    const DOCUMENT: &str = r#"{
+        "version": "8.0",
+        "build_revision": "abc",
+        "relays_published": "2024-01-01 00:00:00",
+        "relays": [
+            {"n": "moria1", "f": "9695DFC35FFEB861329B9F1AB04C46397020CE31", "a": ["128.31.0.39"], "r": true},
+            {"n": "t\"or]", "f": "847B1F850344D7876491A54892F904934E4EB85D", "a": [], "r": false}
+        ],
+        "relays_truncated": 3,
+        "bridges_published": "2024-01-01 00:00:00",
+        "bridges": [{"n": "bridge", "h": "0000000000000000000000000000000000000000", "r": true}]
+    }"#;
+
+    fn parse(
+        document: &str,
+        chunk_size: usize,
+    ) -> Result<(DocumentHeader, Vec<Entry<String, String>>), ClientError> {
+        let mut parser = DocumentParser::<RelaySummary, BridgeSummary>::new();
+        let mut entries = Vec::new();
+        for chunk in document.as_bytes().chunks(chunk_size) {
+            parser.feed(chunk, &mut |entry| {
+                entries.push(match entry {
+                    Entry::Relay(relay) => Entry::Relay(relay.n),
+                    Entry::Bridge(bridge) => Entry::Bridge(bridge.n),
+                })
+            })?;
+        }
+        Ok((parser.finish()?, entries))
+    }
+
+    #[test]
+    fn test_entries_in_any_chunking() {
+        for chunk_size in [1, 2, 7, 64, DOCUMENT.len()] {
+            let (header, entries) = parse(DOCUMENT, chunk_size).unwrap();
+            assert_eq!(header.version, "8.0");
+            assert_eq!(header.build_revision.as_deref(), Some("abc"));
+            assert_eq!(header.relays_truncated, Some(3));
+            assert_eq!(header.bridges_skipped, None);
+            assert_eq!(
+                entries,
+                [
+                    Entry::Relay("moria1".to_string()),
+                    Entry::Relay("t\"or]".to_string()),
+                    Entry::Bridge("bridge".to_string()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_arrays() {
+        let document = r#"{"version":"8.0","relays_published":"x","relays":[],
+            "bridges_published":"y","bridges":[ ]}"#;
+        let (header, entries) = parse(document, 5).unwrap();
+        assert_eq!(header.bridges_published, "y");
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let document = DOCUMENT.replace(r#""r": false"#, r#""r": "no""#);
+        let error = parse(&document, 10).unwrap_err();
+        let ClientError::Deserialization {
+            path,
+            line,
+            snippet,
+            ..
+        } = error
+        else {
+            panic!("unexpected error: {error}");
+        };
+        assert_eq!(path, "relays[1].r");
+        assert_eq!(line, 7);
+        assert!(snippet.contains(r#""r": "no""#));
+
+        let error = parse(&DOCUMENT[..DOCUMENT.len() / 2], 10).unwrap_err();
+        assert!(error.to_string().contains("EOF while parsing"));
+
+        let document = DOCUMENT.replacen("},\n", "}\n", 1);
+        let error = parse(&document, 3).unwrap_err();
+        assert!(
+            matches!(&error, ClientError::Deserialization { path, line: 7, .. } if path == "relays[1]"),
+            "{error}"
+        );
+
+        let error = parse(r#"{"version": "8.0"} x"#, 4).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("expected the end of the document")
+        );
+    }
+}
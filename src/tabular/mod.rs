@@ -0,0 +1,601 @@
+//! CSV and NDJSON Export
+//!
+//! This module writes relay and bridge objects as rows of CSV or newline-delimited JSON,
+//! for loading into pandas, DuckDB or a spreadsheet. Every row type has a fixed list
+//! of columns, named and ordered as the fields of the document type, see
+//! [`fields`](crate::parameters::fields). A selection of columns maps onto the
+//! `fields` parameter, so only the selected fields need to be fetched.
+//!
+//! The graph histories of bandwidth, weights, clients and uptime documents are written
+//! in long format, one [`SeriesPoint`] per data point.
+//!
+//! # Encoding
+//!
+//! Missing values are empty in CSV and `null` in NDJSON. In CSV, booleans are `true`
+//! or `false`, lists such as `flags` and `or_addresses` are encoded as set by
+//! [`ListEncoding`], and objects such as `exit_policy_summary` are compact JSON. NDJSON
+//! keeps lists and objects as JSON, and every line has all selected columns in order.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+
+use chrono::NaiveDateTime;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::models::{
+    BridgeBandwidth, BridgeClients, BridgeDetails, BridgeSummary, BridgeUptime, GraphHistory,
+    OnionooResponse, PartialBridgeDetails, PartialRelayDetails, RelayBandwidth, RelayDetails,
+    RelaySummary, RelayUptime, RelayWeights,
+};
+use crate::parameters::fields::{DetailsField, Field, FieldsList, SummaryField};
+
+/// Error type for export operations
+#[derive(Debug)]
+pub enum ExportError {
+    /// Writing to the output failed
+    Io(io::Error),
+    /// A row could not be serialized
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "I/O error: {}", e),
+            ExportError::Json(e) => write!(f, "Serialization failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(e) => Some(e),
+            ExportError::Json(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> Self {
+        ExportError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(err: serde_json::Error) -> Self {
+        ExportError::Json(err)
+    }
+}
+
+/// Object that can be written as a row
+pub trait Row: Serialize {
+    /// Field enum naming the columns
+    type Field: Field;
+
+    /// All columns, in output order
+    const COLUMNS: &'static [Self::Field];
+}
+
+macro_rules! rows {
+    ($($name:ty => $field:ty;)*) => {
+        $(
+            impl Row for $name {
+                type Field = $field;
+                const COLUMNS: &'static [$field] = <$name>::FIELDS;
+            }
+        )*
+    };
+}
+
+rows! {
+    RelaySummary => SummaryField;
+    BridgeSummary => SummaryField;
+    RelayDetails => DetailsField;
+    PartialRelayDetails => DetailsField;
+    BridgeDetails => DetailsField;
+    PartialBridgeDetails => DetailsField;
+}
+
+/// Columns of a [`SeriesPoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeriesColumn {
+    Fingerprint,
+    Series,
+    Period,
+    Timestamp,
+    Value,
+}
+
+impl Field for SeriesColumn {
+    const ALL: &'static [Self] = &[
+        SeriesColumn::Fingerprint,
+        SeriesColumn::Series,
+        SeriesColumn::Period,
+        SeriesColumn::Timestamp,
+        SeriesColumn::Value,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SeriesColumn::Fingerprint => "fingerprint",
+            SeriesColumn::Series => "series",
+            SeriesColumn::Period => "period",
+            SeriesColumn::Timestamp => "timestamp",
+            SeriesColumn::Value => "value",
+        }
+    }
+}
+
+/// Data point of a graph history
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeriesPoint {
+    /// Fingerprint of the relay, or hashed fingerprint of the bridge
+    pub fingerprint: String,
+    /// Field of the history, e.g. `read_history`, or `flags.Guard` for flag uptimes
+    pub series: String,
+    /// Period of the history as on the wire, e.g. `1_month`
+    pub period: &'static str,
+    /// UTC time of the data point, written as `YYYY-MM-DD hh:mm:ss`
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub timestamp: NaiveDateTime,
+    /// Value multiplied by the history's factor, `None` for gaps
+    pub value: Option<f64>,
+}
+
+fn serialize_timestamp<S: Serializer>(
+    timestamp: &NaiveDateTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&timestamp.format("%Y-%m-%d %H:%M:%S"))
+}
+
+impl Row for SeriesPoint {
+    type Field = SeriesColumn;
+    const COLUMNS: &'static [SeriesColumn] = SeriesColumn::ALL;
+}
+
+/// Document or object containing graph histories
+pub trait Series {
+    /// All data points, by series, period and time
+    fn series(&self) -> Vec<SeriesPoint>;
+}
+
+fn push_series(
+    points: &mut Vec<SeriesPoint>,
+    fingerprint: &str,
+    series: &str,
+    periods: Option<[(&'static str, Option<&GraphHistory>); 4]>,
+) {
+    for (period, history) in periods.into_iter().flatten() {
+        let Some(history) = history else {
+            continue;
+        };
+        points.extend(
+            history
+                .points()
+                .into_iter()
+                .map(|(timestamp, value)| SeriesPoint {
+                    fingerprint: fingerprint.to_string(),
+                    series: series.to_string(),
+                    period,
+                    timestamp,
+                    value,
+                }),
+        );
+    }
+}
+
+macro_rules! bandwidth_series {
+    ($($name:ty),*) => {
+        $(
+            impl Series for $name {
+                fn series(&self) -> Vec<SeriesPoint> {
+                    let mut points = Vec::new();
+                    for (series, history) in [
+                        ("read_history", &self.read_history),
+                        ("write_history", &self.write_history),
+                    ] {
+                        let periods = history.as_ref().map(|h| h.periods());
+                        push_series(&mut points, &self.fingerprint, series, periods);
+                    }
+                    points
+                }
+            }
+        )*
+    };
+}
+
+bandwidth_series!(RelayBandwidth, BridgeBandwidth);
+
+impl Series for RelayWeights {
+    fn series(&self) -> Vec<SeriesPoint> {
+        let mut points = Vec::new();
+        for (series, history) in [
+            ("consensus_weight_fraction", &self.consensus_weight_fraction),
+            ("guard_probability", &self.guard_probability),
+            ("middle_probability", &self.middle_probability),
+            ("exit_probability", &self.exit_probability),
+            ("consensus_weight", &self.consensus_weight),
+        ] {
+            let periods = history.as_ref().map(|h| h.periods());
+            push_series(&mut points, &self.fingerprint, series, periods);
+        }
+        points
+    }
+}
+
+impl Series for BridgeClients {
+    fn series(&self) -> Vec<SeriesPoint> {
+        let mut points = Vec::new();
+        let periods = self.average_clients.as_ref().map(|h| h.periods());
+        push_series(&mut points, &self.fingerprint, "average_clients", periods);
+        points
+    }
+}
+
+impl Series for RelayUptime {
+    /// Flag uptimes are written as the series `flags.<flag>`, in flag order
+    fn series(&self) -> Vec<SeriesPoint> {
+        let mut points = Vec::new();
+        let periods = self.uptime.as_ref().map(|h| h.periods());
+        push_series(&mut points, &self.fingerprint, "uptime", periods);
+        if let Some(flags) = &self.flags {
+            let mut flags: Vec<_> = flags.flags.iter().collect();
+            flags.sort_by_key(|(flag, _)| flag.as_str());
+            for (flag, history) in flags {
+                let series = format!("flags.{}", flag);
+                push_series(
+                    &mut points,
+                    &self.fingerprint,
+                    &series,
+                    Some(history.periods()),
+                );
+            }
+        }
+        points
+    }
+}
+
+impl Series for BridgeUptime {
+    fn series(&self) -> Vec<SeriesPoint> {
+        let mut points = Vec::new();
+        let periods = self.uptime.as_ref().map(|h| h.periods());
+        push_series(&mut points, &self.fingerprint, "uptime", periods);
+        points
+    }
+}
+
+impl Series for () {
+    fn series(&self) -> Vec<SeriesPoint> {
+        Vec::new()
+    }
+}
+
+/// Data points of all relays, then all bridges
+impl<T: Series, U: Series> Series for OnionooResponse<T, U> {
+    fn series(&self) -> Vec<SeriesPoint> {
+        self.relays
+            .iter()
+            .map(Series::series)
+            .chain(self.bridges.iter().map(Series::series))
+            .flatten()
+            .collect()
+    }
+}
+
+/// Output format of a [`TableWriter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Comma-separated values with a header row (RFC 4180)
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Encoding of list values in CSV cells
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListEncoding {
+    /// Compact JSON array, e.g. `["Fast","Guard"]`
+    #[default]
+    Json,
+    /// Items joined with a delimiter, e.g. `Fast|Guard`
+    ///
+    /// Items are not escaped, so the delimiter should not occur in them. Lists of
+    /// objects are written as JSON.
+    Delimited(char),
+}
+
+/// Writer for rows of one type
+///
+/// # Examples
+///
+/// ```no_run
+/// use onionoo::{Client, QueryParameters, endpoints::PartialDetails};
+/// use onionoo::models::PartialRelayDetails;
+/// use onionoo::parameters::DetailsField;
+/// use onionoo::tabular::TableWriter;
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let writer = TableWriter::<_, PartialRelayDetails>::csv(std::io::stdout()).select([
+///     DetailsField::Nickname,
+///     DetailsField::Fingerprint,
+///     DetailsField::Flags,
+/// ]);
+///
+/// // Only fetch the selected columns
+/// let params = QueryParameters::new().fields(writer.fields_list());
+/// let response = Client::new().fetch::<PartialDetails>(params).await?;
+///
+/// let mut writer = writer;
+/// writer.write_all(&response.relays)?;
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TableWriter<W: Write, R: Row> {
+    out: W,
+    format: Format,
+    columns: Vec<R::Field>,
+    lists: ListEncoding,
+    header_written: bool,
+    _row: PhantomData<fn(&R)>,
+}
+
+impl<W: Write, R: Row> TableWriter<W, R> {
+    /// Write all columns of `R` to `out`
+    pub fn new(out: W, format: Format) -> Self {
+        Self {
+            out,
+            format,
+            columns: R::COLUMNS.to_vec(),
+            lists: ListEncoding::default(),
+            header_written: false,
+            _row: PhantomData,
+        }
+    }
+
+    /// Write CSV to `out`
+    pub fn csv(out: W) -> Self {
+        Self::new(out, Format::Csv)
+    }
+
+    /// Write NDJSON to `out`
+    pub fn ndjson(out: W) -> Self {
+        Self::new(out, Format::Ndjson)
+    }
+
+    /// Write only the given columns, in the given order
+    ///
+    /// Duplicates are ignored. Columns that `R` does not have, e.g. `transports` for
+    /// relays, are always empty.
+    pub fn select<I: IntoIterator<Item = R::Field>>(mut self, columns: I) -> Self
+    where
+        R::Field: PartialEq,
+    {
+        self.columns.clear();
+        for column in columns {
+            if !self.columns.contains(&column) {
+                self.columns.push(column);
+            }
+        }
+        self
+    }
+
+    /// Encoding of lists in CSV cells (default [`ListEncoding::Json`])
+    pub fn list_encoding(mut self, lists: ListEncoding) -> Self {
+        self.lists = lists;
+        self
+    }
+
+    /// Columns written, in order
+    pub fn columns(&self) -> &[R::Field] {
+        &self.columns
+    }
+
+    /// The selected columns as a `fields` parameter, see
+    /// [`QueryParameters::fields`](crate::QueryParameters::fields)
+    pub fn fields_list(&self) -> FieldsList {
+        self.columns.iter().copied().collect()
+    }
+
+    /// Write one row
+    pub fn write(&mut self, row: &R) -> Result<(), ExportError> {
+        self.write_header()?;
+        let value = serde_json::to_value(row)?;
+        let cell = |column: &R::Field| value.get(column.as_str()).unwrap_or(&Value::Null);
+        match self.format {
+            Format::Csv => {
+                let cells: Vec<String> = self
+                    .columns
+                    .iter()
+                    .map(|column| cell_text(cell(column), self.lists))
+                    .collect();
+                write_record(&mut self.out, &cells)?;
+            }
+            Format::Ndjson => {
+                let mut line = String::from("{");
+                for (index, column) in self.columns.iter().enumerate() {
+                    if index > 0 {
+                        line.push(',');
+                    }
+                    line.push_str(&serde_json::to_string(column.as_str())?);
+                    line.push(':');
+                    line.push_str(&serde_json::to_string(cell(column))?);
+                }
+                line.push('}');
+                writeln!(self.out, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write all rows
+    pub fn write_all<'a, I>(&mut self, rows: I) -> Result<(), ExportError>
+    where
+        R: 'a,
+        I: IntoIterator<Item = &'a R>,
+    {
+        rows.into_iter().try_for_each(|row| self.write(row))
+    }
+
+    /// Write the CSV header if no row was written, flush and return the output
+    pub fn finish(mut self) -> Result<W, ExportError> {
+        self.write_header()?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_header(&mut self) -> Result<(), ExportError> {
+        if self.format == Format::Csv && !self.header_written {
+            let names: Vec<&str> = self.columns.iter().map(Field::as_str).collect();
+            write_record(&mut self.out, &names)?;
+        }
+        self.header_written = true;
+        Ok(())
+    }
+}
+
+/// Renders a JSON value as the text of a CSV cell, before quoting
+///
+/// This is the encoding used by [`TableWriter`]: null is empty, strings are
+/// unquoted, lists are encoded as set by `lists` and other values are compact JSON.
+pub fn cell_text(value: &Value, lists: ListEncoding) -> String {
+    match (value, lists) {
+        (Value::Null, _) => String::new(),
+        (Value::String(s), _) => s.clone(),
+        (Value::Array(items), ListEncoding::Delimited(delimiter))
+            if items.iter().all(|v| !v.is_array() && !v.is_object()) =>
+        {
+            items
+                .iter()
+                .map(|item| cell_text(item, lists))
+                .collect::<Vec<_>>()
+                .join(&delimiter.to_string())
+        }
+        (other, _) => other.to_string(),
+    }
+}
+
+/// Writes one CSV record, quoting cells that contain commas, quotes or line breaks
+pub fn write_record<W: Write, S: AsRef<str>>(out: &mut W, cells: &[S]) -> io::Result<()> {
+    let cells: Vec<String> = cells.iter().map(|cell| escape(cell.as_ref())).collect();
+    writeln!(out, "{}", cells.join(","))
+}
+
+fn escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DetailsResponse, UptimeResponse};
+
+    fn parse<T: serde::de::DeserializeOwned>(json: &str) -> T {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_columns_match_fields() {
+        let relay = serde_json::to_value(
+            &parse::<DetailsResponse>(include_str!("../mock/fixtures/details.json")).relays[0],
+        )
+        .unwrap();
+        let keys: Vec<&str> = relay
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        for column in RelayDetails::COLUMNS {
+            assert!(keys.contains(&column.as_str()), "{}", column);
+        }
+        assert_eq!(keys.len(), RelayDetails::COLUMNS.len());
+    }
+
+    #[test]
+    fn test_csv_and_ndjson() {
+        let details: DetailsResponse = parse(include_str!("../mock/fixtures/details.json"));
+        let columns = [
+            DetailsField::Nickname,
+            DetailsField::Flags,
+            DetailsField::Running,
+            DetailsField::Transports,
+            DetailsField::Nickname,
+        ];
+
+        let mut writer = TableWriter::csv(Vec::new()).select(columns);
+        assert_eq!(
+            writer.fields_list().as_str(),
+            "nickname,flags,running,transports"
+        );
+        writer.write(&details.relays[1]).unwrap();
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "nickname,flags,running,transports\n\
+             bravo,\"[\"\"Exit\"\",\"\"Fast\"\",\"\"Running\"\",\"\"Stable\"\",\"\"Valid\"\"]\",true,\n"
+        );
+
+        let mut writer = TableWriter::csv(Vec::new())
+            .select(columns)
+            .list_encoding(ListEncoding::Delimited('|'));
+        writer.write(&details.relays[1]).unwrap();
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(csv.ends_with("bravo,Exit|Fast|Running|Stable|Valid,true,\n"));
+
+        let mut writer = TableWriter::ndjson(Vec::new()).select(columns);
+        writer.write_all(&details.relays[..2]).unwrap();
+        let ndjson = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"{"nickname":"bravo","flags":["Exit","Fast","Running","Stable","Valid"],"running":true,"transports":null}"#
+        );
+
+        // An empty CSV export still has the header
+        let writer = TableWriter::<_, BridgeSummary>::csv(Vec::new());
+        assert_eq!(writer.finish().unwrap(), b"n,h,r\n");
+    }
+
+    #[test]
+    fn test_series() {
+        let uptime: UptimeResponse = parse(include_str!("../mock/fixtures/uptime.json"));
+        let points = uptime.series();
+        let relay = &uptime.relays[0];
+        let history = relay
+            .uptime
+            .as_ref()
+            .and_then(|h| h.one_month.as_ref())
+            .unwrap();
+
+        let first = &points[0];
+        assert_eq!(first.fingerprint, relay.fingerprint);
+        assert_eq!((first.series.as_str(), first.period), ("uptime", "1_month"));
+        assert_eq!(
+            first.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            history.first
+        );
+        assert_eq!(first.value, history.values[0].map(|v| v * history.factor));
+        let count = points
+            .iter()
+            .filter(|p| p.fingerprint == relay.fingerprint && p.series == "uptime")
+            .filter(|p| p.period == "1_month")
+            .count();
+        assert_eq!(count, history.values.len());
+        assert!(points.iter().any(|p| p.series.starts_with("flags.")));
+
+        let mut writer = TableWriter::csv(Vec::new());
+        writer.write(first).unwrap();
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(csv.starts_with("fingerprint,series,period,timestamp,value\n"));
+        assert!(csv.contains(&format!(",uptime,1_month,{},", history.first)));
+    }
+}
@@ -0,0 +1,324 @@
+//! HTTP Transports
+//!
+//! The [`Client`](crate::Client) sends requests through a [`Transport`], which takes a
+//! URL and headers and returns the status, headers and body of the response. By default
+//! this is [`ReqwestTransport`]; other HTTP stacks can be plugged in with
+//! [`Client::with_transport`](crate::Client::with_transport). [`FixtureTransport`]
+//! answers from memory, so code using the client can be tested without any HTTP.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use crate::client::ClientError;
+
+/// Future returned by [`Transport::send`]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// HTTP GET request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// Absolute URL including the query string
+    pub url: String,
+    /// Request headers (name, value)
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        Self {
+            url: url.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Add a request header
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// HTTP response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// Status code
+    pub status: u16,
+    /// Response headers (name, value)
+    pub headers: Vec<(String, String)>,
+    /// Response body
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new<T: Into<Vec<u8>>>(status: u16, body: T) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Add a response header
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Value of the first header with the given name, compared case-insensitively
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Whether the status is in the 2xx range
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Response body read in chunks as it arrives
+pub trait BodyStream: Send {
+    /// Next chunk of the body, or `None` at the end
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>, ClientError>>;
+}
+
+/// Body that was received completely
+struct Buffered(Option<Vec<u8>>);
+
+impl BodyStream for Buffered {
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>, ClientError>> {
+        let chunk = self.0.take();
+        Box::pin(async move { Ok(chunk) })
+    }
+}
+
+/// HTTP response whose body has not been read yet
+pub struct StreamingResponse {
+    /// Status code
+    pub status: u16,
+    /// Response headers (name, value)
+    pub headers: Vec<(String, String)>,
+    /// Response body
+    pub body: Box<dyn BodyStream>,
+}
+
+impl StreamingResponse {
+    /// Read the whole body
+    pub async fn collect(mut self) -> Result<Response, ClientError> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.body.next_chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok(Response {
+            status: self.status,
+            headers: self.headers,
+            body,
+        })
+    }
+}
+
+impl fmt::Debug for StreamingResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamingResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<Response> for StreamingResponse {
+    fn from(response: Response) -> Self {
+        Self {
+            status: response.status,
+            headers: response.headers,
+            body: Box::new(Buffered(Some(response.body))),
+        }
+    }
+}
+
+/// HTTP stack used by the client to send requests
+///
+/// Implementations report failures to get a response at all, such as connection or
+/// timeout errors, as [`ClientError::Request`] or with [`ClientError::transport`]. Error
+/// statuses are returned as responses and mapped to errors by the client.
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Send a GET request
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, ClientError>>;
+
+    /// Send a GET request and return the body as it arrives
+    ///
+    /// Used by [`Client::stream`](crate::Client::stream). The default implementation
+    /// receives the whole body with [`send`](Self::send) first.
+    fn send_streaming(
+        &self,
+        request: Request,
+    ) -> BoxFuture<'_, Result<StreamingResponse, ClientError>> {
+        Box::pin(async move { Ok(self.send(request).await?.into()) })
+    }
+}
+
+/// Transport built on a `reqwest` client
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl From<reqwest::Client> for ReqwestTransport {
+    fn from(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl ReqwestTransport {
+    async fn request(&self, request: &Request) -> Result<reqwest::Response, ClientError> {
+        let mut builder = self.client.get(&request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.send().await?)
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, ClientError>> {
+        Box::pin(async move {
+            let response = self.request(&request).await?;
+            let status = response.status().as_u16();
+            let headers = header_pairs(response.headers());
+            let body = response.bytes().await?.to_vec();
+            Ok(Response {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+
+    fn send_streaming(
+        &self,
+        request: Request,
+    ) -> BoxFuture<'_, Result<StreamingResponse, ClientError>> {
+        Box::pin(async move {
+            let response = self.request(&request).await?;
+            Ok(StreamingResponse {
+                status: response.status().as_u16(),
+                headers: header_pairs(response.headers()),
+                body: Box::new(ReqwestBody(response)),
+            })
+        })
+    }
+}
+
+/// Body of a `reqwest` response, read chunk by chunk
+struct ReqwestBody(reqwest::Response);
+
+impl BodyStream for ReqwestBody {
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>, ClientError>> {
+        Box::pin(async move { Ok(self.0.chunk().await?.map(|chunk| chunk.to_vec())) })
+    }
+}
+
+/// Value of the first header with the given name, compared case-insensitively
+pub(crate) fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Converts `reqwest` headers to (name, value) pairs
+pub(crate) fn header_pairs(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Transport answering from canned responses in memory
+///
+/// Responses are registered by URL path, optionally with a query string; a request
+/// matching a path and query exactly is preferred over one matching the path only.
+/// Unregistered paths respond with `404 Not Found`. Clones share their responses and
+/// the record of requests.
+///
+/// # Examples
+///
+/// ```
+/// use onionoo::{Client, QueryParameters, transport::FixtureTransport};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let transport = FixtureTransport::new().respond("/summary", 503, "");
+/// let client = Client::new().with_transport(transport.clone());
+///
+/// let result = onionoo::summary(&client, QueryParameters::new().limit(1)).await;
+/// assert!(result.is_err());
+/// assert_eq!(
+///     transport.requests()[0].url,
+///     "https://onionoo.torproject.org/summary?limit=1"
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FixtureTransport {
+    responses: Arc<Mutex<HashMap<String, Response>>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl FixtureTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to requests for `path` with a status and body
+    pub fn respond<T: Into<Vec<u8>>>(self, path: &str, status: u16, body: T) -> Self {
+        self.respond_with(path, Response::new(status, body))
+    }
+
+    /// Respond to requests for `path` with a complete response
+    pub fn respond_with(self, path: &str, response: Response) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), response);
+        self
+    }
+
+    /// Requests received so far, in order
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for FixtureTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, ClientError>> {
+        let response = match reqwest::Url::parse(&request.url) {
+            Ok(url) => {
+                let responses = self.responses.lock().unwrap();
+                url.query()
+                    .and_then(|query| responses.get(&format!("{}?{}", url.path(), query)))
+                    .or_else(|| responses.get(url.path()))
+                    .cloned()
+                    .unwrap_or_else(|| Response::new(404, ""))
+            }
+            Err(e) => {
+                let error = ClientError::UrlConstruction(e.to_string());
+                return Box::pin(async move { Err(error) });
+            }
+        };
+        self.requests.lock().unwrap().push(request);
+        Box::pin(async move { Ok(response) })
+    }
+}
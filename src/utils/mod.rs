@@ -1 +1,13 @@
+//! Shared Helpers
+//!
+//! Small functions used by several modules of the crate.
 
+use chrono::NaiveDateTime;
+
+/// Timestamp format used by Onionoo documents
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parses an Onionoo timestamp such as `2024-01-01 12:00:00`
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()
+}
@@ -1,9 +1,35 @@
-use onionoo::parameters::{BoolValue, TypeValue, date, days_range};
+use onionoo::batch::BatchResponse;
+use onionoo::client::{ClientError, ErrorKind};
+use onionoo::encoding::ContentEncoding;
+use onionoo::endpoints::{Endpoint, PartialDetails};
+use onionoo::exporter::{Exporter, MetricsServer, RelayDocuments};
+use onionoo::limits::RateLimit;
+use onionoo::metrics::Recorder;
+use onionoo::mirror::{self, MirrorError, MirrorServer};
+use onionoo::mock::{Fault, MockServer};
+use onionoo::models::{
+    BridgeDetails, BridgeSummary, PartialDetailsResponse, PartialRelayDetails, RelayDetails,
+    RelaySummary, RelayWeights, SummaryResponse,
+};
+use onionoo::parameters::fields::{DetailsField, Field, FieldsList};
+use onionoo::parameters::{BoolValue, TypeValue, ValidationIssue, date, days_range};
+use onionoo::query::LocalQuery;
+use onionoo::stream::Entry;
+use onionoo::tabular::{ListEncoding, Series, TableWriter};
+use onionoo::transport::{FixtureTransport, Response as TransportResponse, Transport};
 use onionoo::{Client, QueryParameters, endpoints};
+use std::time::Duration;
+
+async fn mock_server() -> MockServer {
+    MockServer::start()
+        .await
+        .expect("failed to start mock server")
+}
 
 #[tokio::test]
 async fn test_summary_endpoint() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new();
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
@@ -13,7 +39,8 @@ async fn test_summary_endpoint() {
 
 #[tokio::test]
 async fn test_details_endpoint() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new();
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
@@ -23,7 +50,8 @@ async fn test_details_endpoint() {
 
 #[tokio::test]
 async fn test_bandwidth_endpoint() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new();
     let result = endpoints::bandwidth(&client, params).await;
     assert!(result.is_ok());
@@ -33,7 +61,8 @@ async fn test_bandwidth_endpoint() {
 
 #[tokio::test]
 async fn test_weights_endpoint() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new();
     let result = endpoints::weights(&client, params).await;
     assert!(result.is_ok());
@@ -43,7 +72,8 @@ async fn test_weights_endpoint() {
 
 #[tokio::test]
 async fn test_clients_endpoint() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new();
     let result = endpoints::clients(&client, params).await;
     assert!(result.is_ok());
@@ -53,7 +83,8 @@ async fn test_clients_endpoint() {
 
 #[tokio::test]
 async fn test_uptime_endpoint() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new();
     let result = endpoints::uptime(&client, params).await;
     assert!(result.is_ok());
@@ -65,17 +96,21 @@ async fn test_uptime_endpoint() {
 
 #[tokio::test]
 async fn test_summary_with_relay_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().type_param(TypeValue::Relay).limit(5);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 4);
+    assert!(response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_summary_with_bridge_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .type_param(TypeValue::Bridge)
         .limit(5);
@@ -83,31 +118,42 @@ async fn test_summary_with_bridge_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty());
+    assert_eq!(response.bridges.len(), 2);
 }
 
 #[tokio::test]
 async fn test_details_with_running_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().running(BoolValue::True).limit(10);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 3);
+    assert_eq!(response.bridges.len(), 1);
+    assert!(response.relays.iter().all(|relay| relay.running));
 }
 
 #[tokio::test]
 async fn test_bandwidth_with_country_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().country("US").limit(5);
     let result = endpoints::bandwidth(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    // Country filtering uses the details document and never returns bridges
+    assert_eq!(response.relays.len(), 1);
+    assert!(response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_weights_with_as_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().as_param("AS13335").limit(5);
     let result = endpoints::weights(&client, params).await;
     assert!(result.is_ok());
@@ -117,17 +163,21 @@ async fn test_weights_with_as_filter() {
 
 #[tokio::test]
 async fn test_uptime_with_flag_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().flag("Guard").limit(5);
     let result = endpoints::uptime(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 2);
+    assert!(response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_search_functionality() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().search("tor").limit(5);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
@@ -137,7 +187,8 @@ async fn test_search_functionality() {
 
 #[tokio::test]
 async fn test_version_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .version(onionoo::parameters::helpers::version_list("0.4.7"))
         .limit(5);
@@ -149,7 +200,8 @@ async fn test_version_filter() {
 
 #[tokio::test]
 async fn test_os_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().os("Linux").limit(5);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
@@ -161,17 +213,22 @@ async fn test_os_filter() {
 
 #[tokio::test]
 async fn test_pagination_with_offset_and_limit() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().offset(10).limit(5);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
+    assert_eq!(response.relays_skipped, Some(4));
+    assert_eq!(response.bridges_skipped, Some(2));
 }
 
 #[tokio::test]
 async fn test_pagination_only_offset() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().offset(20);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
@@ -181,19 +238,25 @@ async fn test_pagination_only_offset() {
 
 #[tokio::test]
 async fn test_pagination_only_limit() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(3);
     let result = endpoints::bandwidth(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 3);
+    assert!(response.bridges.is_empty());
+    assert_eq!(response.relays_truncated, Some(1));
+    assert_eq!(response.bridges_truncated, Some(2));
 }
 
 // ==================== ORDERING TESTS ====================
 
 #[tokio::test]
 async fn test_ordering_by_consensus_weight() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .order(onionoo::parameters::helpers::order_list("consensus_weight"))
         .limit(5);
@@ -201,11 +264,14 @@ async fn test_ordering_by_consensus_weight() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    let nicknames: Vec<&str> = response.relays.iter().map(|r| r.n.as_str()).collect();
+    assert_eq!(nicknames, vec!["charlie", "alpha", "delta", "bravo"]);
 }
 
 #[tokio::test]
 async fn test_ordering_by_first_seen_descending() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .order(onionoo::parameters::helpers::order_list("-first_seen"))
         .limit(5);
@@ -213,11 +279,18 @@ async fn test_ordering_by_first_seen_descending() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    let nicknames: Vec<&str> = response
+        .relays
+        .iter()
+        .map(|r| r.nickname.as_str())
+        .collect();
+    assert_eq!(nicknames, vec!["charlie", "bravo", "delta", "alpha"]);
 }
 
 #[tokio::test]
 async fn test_ordering_multiple_fields() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .order(onionoo::parameters::helpers::order_list(
             "consensus_weight,-first_seen",
@@ -233,7 +306,8 @@ async fn test_ordering_multiple_fields() {
 
 #[tokio::test]
 async fn test_field_filtering_summary() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .fields(onionoo::parameters::helpers::fields_list(
             "nickname,fingerprint,or_addresses",
@@ -247,7 +321,8 @@ async fn test_field_filtering_summary() {
 
 #[tokio::test]
 async fn test_field_filtering_details() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().type_param(TypeValue::Relay).limit(5);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
@@ -259,7 +334,8 @@ async fn test_field_filtering_details() {
 
 #[tokio::test]
 async fn test_weights_endpoint_relays_only() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().type_param(TypeValue::Relay).limit(5);
     let result = endpoints::weights(&client, params).await;
     assert!(result.is_ok());
@@ -271,7 +347,8 @@ async fn test_weights_endpoint_relays_only() {
 
 #[tokio::test]
 async fn test_clients_endpoint_bridges_only() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .type_param(TypeValue::Bridge)
         .limit(5);
@@ -285,7 +362,8 @@ async fn test_clients_endpoint_bridges_only() {
 
 #[tokio::test]
 async fn test_bridge_specific_parameters() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .type_param(TypeValue::Bridge)
         .limit(5);
@@ -299,7 +377,8 @@ async fn test_bridge_specific_parameters() {
 
 #[tokio::test]
 async fn test_first_seen_days_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .first_seen_days(days_range("7-30"))
         .limit(5);
@@ -311,7 +390,8 @@ async fn test_first_seen_days_filter() {
 
 #[tokio::test]
 async fn test_last_seen_days_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .last_seen_days(days_range("1-7"))
         .limit(5);
@@ -323,7 +403,8 @@ async fn test_last_seen_days_filter() {
 
 #[tokio::test]
 async fn test_first_seen_since_date() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .first_seen_since(date("2023-01-01"))
         .limit(5);
@@ -335,7 +416,8 @@ async fn test_first_seen_since_date() {
 
 #[tokio::test]
 async fn test_last_seen_since_date() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .last_seen_since(date("2023-10-01"))
         .limit(5);
@@ -349,7 +431,8 @@ async fn test_last_seen_since_date() {
 
 #[tokio::test]
 async fn test_multiple_parameters_combined() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .type_param(TypeValue::Relay)
         .running(BoolValue::True)
@@ -360,11 +443,14 @@ async fn test_multiple_parameters_combined() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 1);
+    assert_eq!(response.relays[0].n, "bravo");
 }
 
 #[tokio::test]
 async fn test_complex_query_with_ordering_and_fields() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().type_param(TypeValue::Relay).limit(5);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
@@ -437,7 +523,8 @@ async fn test_find_endpoint_by_name() {
 
 #[tokio::test]
 async fn test_summary_response_structure() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(1);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
@@ -454,7 +541,8 @@ async fn test_summary_response_structure() {
 
 #[tokio::test]
 async fn test_details_response_structure() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(1);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
@@ -473,7 +561,8 @@ async fn test_details_response_structure() {
 
 #[tokio::test]
 async fn test_bandwidth_response_structure() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(1);
     let result = endpoints::bandwidth(&client, params).await;
     assert!(result.is_ok());
@@ -492,7 +581,8 @@ async fn test_bandwidth_response_structure() {
 
 #[tokio::test]
 async fn test_weights_response_structure() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(1);
     let result = endpoints::weights(&client, params).await;
     assert!(result.is_ok());
@@ -513,7 +603,8 @@ async fn test_weights_response_structure() {
 
 #[tokio::test]
 async fn test_clients_response_structure() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(1);
     let result = endpoints::clients(&client, params).await;
     assert!(result.is_ok());
@@ -534,7 +625,8 @@ async fn test_clients_response_structure() {
 
 #[tokio::test]
 async fn test_uptime_response_structure() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(1);
     let result = endpoints::uptime(&client, params).await;
     assert!(result.is_ok());
@@ -554,17 +646,21 @@ async fn test_uptime_response_structure() {
 
 #[tokio::test]
 async fn test_limit_zero() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().limit(0);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
+    assert_eq!(response.relays_truncated, Some(4));
 }
 
 #[tokio::test]
 async fn test_offset_zero() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().offset(0).limit(5);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
@@ -574,7 +670,8 @@ async fn test_offset_zero() {
 
 #[tokio::test]
 async fn test_large_offset() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().offset(1000).limit(5);
     let result = endpoints::bandwidth(&client, params).await;
     assert!(result.is_ok());
@@ -584,17 +681,20 @@ async fn test_large_offset() {
 
 #[tokio::test]
 async fn test_search_with_special_characters() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().search("test").limit(5);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_multiple_version_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .version(onionoo::parameters::helpers::version_list("0.4.7,0.4.8"))
         .limit(5);
@@ -602,11 +702,14 @@ async fn test_multiple_version_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 3);
+    assert_eq!(response.bridges.len(), 2);
 }
 
 #[tokio::test]
 async fn test_version_range_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .version(onionoo::parameters::helpers::version_list("0.4.7..0.4.8"))
         .limit(5);
@@ -614,21 +717,32 @@ async fn test_version_range_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 3);
+    assert_eq!(response.bridges.len(), 2);
 }
 
 #[tokio::test]
 async fn test_contact_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().contact("tor").limit(5);
     let result = endpoints::details(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    let nicknames: Vec<_> = response
+        .relays
+        .iter()
+        .map(|r| r.nickname.as_str())
+        .collect();
+    assert_eq!(nicknames, ["alpha", "bravo"]);
+    assert!(response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_family_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .family("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF")
         .limit(5);
@@ -636,21 +750,25 @@ async fn test_family_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_host_name_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new().host_name(".torproject.org").limit(5);
     let result = endpoints::summary(&client, params).await;
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_recommended_version_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .recommended_version(BoolValue::True)
         .limit(5);
@@ -658,11 +776,14 @@ async fn test_recommended_version_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert_eq!(response.relays.len(), 3);
+    assert_eq!(response.bridges.len(), 1);
 }
 
 #[tokio::test]
 async fn test_as_name_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .as_name("Hurricane Electric")
         .limit(5);
@@ -670,11 +791,13 @@ async fn test_as_name_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
 }
 
 #[tokio::test]
 async fn test_lookup_filter() {
-    let client = Client::new();
+    let server = mock_server().await;
+    let client = server.client();
     let params = QueryParameters::new()
         .lookup("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF")
         .limit(5);
@@ -682,4 +805,1060 @@ async fn test_lookup_filter() {
     assert!(result.is_ok());
     let response = result.unwrap();
     assert!(!response.version.is_empty());
+    assert!(response.relays.is_empty() && response.bridges.is_empty());
+}
+
+/// Nicknames of the relays and bridges in a summary document, relays first
+fn summary_nicknames(response: &onionoo::models::SummaryResponse) -> Vec<&str> {
+    response
+        .relays
+        .iter()
+        .map(|r| r.n.as_str())
+        .chain(response.bridges.iter().map(|b| b.n.as_str()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_search_terms() {
+    let server = mock_server().await;
+    let client = server.client();
+    for (search, expected) in [
+        ("ALPH", vec!["alpha"]),
+        ("$b8d3a7", vec!["bravo"]),
+        ("193.23.244.245", vec!["bravo"]),
+        ("[2001:41d0", vec!["delta"]),
+        ("o country:de", vec!["bravo"]),
+        ("running:false", vec!["charlie", "foxtrot"]),
+    ] {
+        let params = QueryParameters::new().search(search);
+        let response = endpoints::summary(&client, params).await.unwrap();
+        assert_eq!(summary_nicknames(&response), expected, "search={}", search);
+    }
+}
+
+#[tokio::test]
+async fn test_relay_attribute_filters() {
+    let server = mock_server().await;
+    let client = server.client();
+    for (params, expected) in [
+        (
+            QueryParameters::new().as_param("3,as16276"),
+            vec!["alpha", "delta"],
+        ),
+        (
+            QueryParameters::new().as_name("hetzner gmbh"),
+            vec!["bravo", "charlie"],
+        ),
+        (
+            QueryParameters::new().family("$9695DFC35FFEB861329B9F1AB04C46397020CE31"),
+            vec!["alpha", "bravo"],
+        ),
+        (QueryParameters::new().os("freebsd"), vec!["delta"]),
+        (QueryParameters::new().os("Windows"), vec!["foxtrot"]),
+        (QueryParameters::new().host_name("mit.edu"), vec!["alpha"]),
+        (
+            QueryParameters::new().first_seen_days(days_range("0-365")),
+            vec!["charlie", "foxtrot"],
+        ),
+        (
+            QueryParameters::new().last_seen_since(date("2026-10-17")),
+            vec!["alpha", "bravo", "delta", "echo"],
+        ),
+    ] {
+        let query = params.to_query_string();
+        let response = endpoints::summary(&client, params).await.unwrap();
+        assert_eq!(summary_nicknames(&response), expected, "query {}", query);
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_parameter_values_are_rejected() {
+    let server = mock_server().await;
+    let client = server.client();
+    for params in [
+        QueryParameters::new().order(onionoo::parameters::helpers::order_list("nickname")),
+        QueryParameters::new().first_seen_days(days_range("14-7")),
+        QueryParameters::new().as_param("ASX"),
+    ] {
+        // The client refuses to send them, and the server rejects them if sent anyway.
+        let result = endpoints::summary(&client, params.clone()).await;
+        assert!(matches!(result, Err(ClientError::InvalidParameters(_))));
+        let result: Result<SummaryResponse, _> = client.get("/summary", params).await;
+        assert!(matches!(
+            result,
+            Err(ClientError::StatusCode { status: 400, .. })
+        ));
+    }
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_validation_against_endpoint() {
+    let server = mock_server().await;
+
+    let params = QueryParameters::new().type_param(TypeValue::Bridge);
+    let report = params.validate(&Endpoint::Weights);
+    assert_eq!(
+        report.issues(),
+        [ValidationIssue::UnsupportedType(
+            "weights",
+            TypeValue::Bridge
+        )]
+    );
+    let result = endpoints::weights(&server.client(), params).await;
+    assert!(matches!(result, Err(ClientError::InvalidParameters(_))));
+
+    let params = QueryParameters::new().country("de").as_param(3);
+    let report = params.validate(&Endpoint::Clients);
+    assert_eq!(
+        report.issues(),
+        [
+            ValidationIssue::RelayOnlyParameter("clients", "country"),
+            ValidationIssue::RelayOnlyParameter("clients", "as"),
+        ]
+    );
+    assert!(params.validate(&Endpoint::Details).is_empty());
+
+    // Warnings are sent by default and rejected in strict mode.
+    let params = QueryParameters::new()
+        .search("no-such-relay")
+        .lookup("B8D3A7C11E2F4C9A0D5E6F708192A3B4C5D6E7F8");
+    let report = params.validate(&Endpoint::Summary);
+    assert!(!report.has_errors());
+    assert_eq!(report.warnings().count(), 1);
+    let response = endpoints::summary(&server.client(), params.clone())
+        .await
+        .unwrap();
+    assert!(response.relays.is_empty());
+    let result = endpoints::summary(&server.client().strict(true), params).await;
+    assert!(matches!(result, Err(ClientError::InvalidParameters(_))));
+
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_local_query_matches_server() {
+    let server = mock_server().await;
+    let client = server.client();
+    let details = endpoints::details(&client, QueryParameters::new())
+        .await
+        .unwrap();
+    let summary = endpoints::summary(&client, QueryParameters::new())
+        .await
+        .unwrap();
+
+    for params in [
+        QueryParameters::new()
+            .running(BoolValue::True)
+            .country("DE")
+            .flag("exit"),
+        QueryParameters::new()
+            .as_param("AS24940")
+            .version(onionoo::parameters::helpers::version_list("0.4.8")),
+        QueryParameters::new()
+            .search("$3E1C9F0A")
+            .type_param(TypeValue::Relay),
+        QueryParameters::new().family("B8D3A7C11E2F4C9A0D5E6F708192A3B4C5D6E7F8"),
+        QueryParameters::new().os("linux").host_name("example.net"),
+        QueryParameters::new().first_seen_days(days_range("2000-")),
+        QueryParameters::new()
+            .order(onionoo::parameters::helpers::order_list("-first_seen"))
+            .offset(1)
+            .limit(4),
+        QueryParameters::new()
+            .running(BoolValue::False)
+            .lookup("C0FFEE0123456789ABCDEF0123456789ABCDEF01"),
+    ] {
+        let query = params.to_query_string();
+        let local = details.query(&params).unwrap();
+        let remote = endpoints::details(&client, params.clone()).await.unwrap();
+        let fingerprints = |r: &onionoo::models::DetailsResponse| {
+            r.relays
+                .iter()
+                .map(|r| r.fingerprint.clone())
+                .chain(r.bridges.iter().map(|b| b.hashed_fingerprint.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            fingerprints(&local),
+            fingerprints(&remote),
+            "query {}",
+            query
+        );
+        assert_eq!(local.relays_skipped, remote.relays_skipped);
+        assert_eq!(local.relays_truncated, remote.relays_truncated);
+        assert_eq!(local.bridges_skipped, remote.bridges_skipped);
+        assert_eq!(local.bridges_truncated, remote.bridges_truncated);
+    }
+
+    let params = QueryParameters::new().search("a").limit(2);
+    let local = summary.query(&params).unwrap();
+    assert_eq!(summary_nicknames(&local), ["alpha", "bravo"]);
+    assert_eq!(local.relays_truncated, Some(2));
+
+    let params = QueryParameters::new().order(onionoo::parameters::helpers::order_list("nickname"));
+    assert!(details.query(&params).is_err());
+}
+
+/// Copies the default fixtures into a fresh snapshot directory
+fn snapshot_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("onionoo-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for endpoint in onionoo::endpoints::helpers::all_endpoints() {
+        std::fs::write(
+            dir.join(mirror::file_name(&endpoint)),
+            onionoo::mock::fixtures::for_endpoint(&endpoint),
+        )
+        .unwrap();
+    }
+    dir
+}
+
+#[tokio::test]
+async fn test_mirror_serves_snapshot() {
+    let dir = snapshot_dir("mirror");
+    std::fs::remove_file(dir.join("weights.json")).unwrap();
+    let server = MirrorServer::bind(&dir, "127.0.0.1:0").await.unwrap();
+    let client = server.client();
+
+    let params =
+        QueryParameters::new()
+            .country("de")
+            .order(onionoo::parameters::helpers::order_list(
+                "-consensus_weight",
+            ));
+    let response = endpoints::details(&client, params).await.unwrap();
+    let nicknames: Vec<_> = response
+        .relays
+        .iter()
+        .map(|r| r.nickname.as_str())
+        .collect();
+    assert_eq!(nicknames, ["bravo", "charlie"]);
+
+    let params = QueryParameters::new().flag("Guard");
+    let response = endpoints::uptime(&client, params).await.unwrap();
+    assert_eq!(response.relays.len(), 2);
+
+    let result = endpoints::weights(&client, QueryParameters::new()).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::StatusCode { status: 404, .. })
+    ));
+
+    std::fs::write(
+        dir.join("weights.json"),
+        onionoo::mock::fixtures::for_endpoint(&Endpoint::Weights),
+    )
+    .unwrap();
+    server.reload().unwrap();
+    let response = endpoints::weights(&client, QueryParameters::new().limit(1))
+        .await
+        .unwrap();
+    assert_eq!(response.relays.len(), 1);
+    assert_eq!(response.relays_truncated, Some(3));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_metrics_server() {
+    let server = mock_server().await;
+    let client = server.client();
+    let documents = RelayDocuments::fetch(&client, QueryParameters::new().country("de"))
+        .await
+        .unwrap();
+    assert_eq!(documents.details.relays.len(), 2);
+    assert!(
+        documents
+            .uptime
+            .as_ref()
+            .is_some_and(|u| u.relays.len() == 2)
+    );
+
+    let metrics = MetricsServer::bind("127.0.0.1:0").await.unwrap();
+    let transport = onionoo::transport::ReqwestTransport::new();
+    let scrape = || transport.send(onionoo::transport::Request::new(metrics.url()));
+    assert_eq!(scrape().await.unwrap().status, 503);
+
+    metrics.update(Exporter::new().render(&documents));
+    let response = scrape().await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.header_value("content-type"),
+        Some(onionoo::exporter::CONTENT_TYPE)
+    );
+    let text = String::from_utf8(response.body).unwrap();
+    let running: Vec<_> = text
+        .lines()
+        .filter(|line| line.starts_with("onionoo_relay_running{"))
+        .collect();
+    assert_eq!(running.len(), 2);
+    assert!(running.iter().all(|line| line.contains("country=\"de\"")));
+    assert!(text.contains("onionoo_relay_written_bytes_per_second{"));
+
+    let request =
+        onionoo::transport::Request::new(format!("http://{}/other", metrics.local_addr()));
+    assert_eq!(transport.send(request).await.unwrap().status, 404);
+}
+
+#[tokio::test]
+async fn test_mirror_rejects_invalid_snapshots() {
+    let dir = snapshot_dir("mirror-invalid");
+    for endpoint in onionoo::endpoints::helpers::all_endpoints() {
+        std::fs::remove_file(dir.join(mirror::file_name(&endpoint))).unwrap();
+    }
+    let result = MirrorServer::bind(&dir, "127.0.0.1:0").await;
+    assert!(matches!(result, Err(MirrorError::EmptySnapshot(_))));
+
+    std::fs::write(dir.join("details.json"), "{\"relays\": [").unwrap();
+    let result = MirrorServer::bind(&dir, "127.0.0.1:0").await;
+    assert!(matches!(result, Err(MirrorError::Document(_, _))));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_lookup_existing_relay() {
+    let server = mock_server().await;
+    let client = server.client();
+    let params = QueryParameters::new().lookup("d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f607");
+    let response = endpoints::weights(&client, params).await.unwrap();
+    assert_eq!(response.relays.len(), 1);
+    assert_eq!(
+        response.relays[0].fingerprint,
+        "D4E5F60718293A4B5C6D7E8F90A1B2C3D4E5F607"
+    );
+
+    // Onionoo also accepts the SHA-1 hash of the fingerprint
+    let params = QueryParameters::new().lookup("C229FCC56778A0AD2CB4DB0447AF73BA182CE8B3");
+    let response = endpoints::weights(&client, params).await.unwrap();
+    assert_eq!(response.relays.len(), 1);
+}
+
+#[tokio::test]
+async fn test_fields_on_details_document() {
+    let server = mock_server().await;
+    let client = server.client();
+    let params = QueryParameters::new()
+        .fields(onionoo::parameters::helpers::fields_list(
+            "nickname,Fingerprint",
+        ))
+        .limit(1);
+    let response: serde_json::Value = client
+        .get_endpoint(Endpoint::Details, params)
+        .await
+        .unwrap();
+    let relay = response["relays"][0].as_object().unwrap();
+    let mut keys: Vec<&str> = relay.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, vec!["fingerprint", "nickname"]);
+}
+
+/// Keys of the relay and bridge objects in a typed document, re-serialized
+fn object_keys<T: serde::Serialize>(document: &T) -> std::collections::BTreeSet<String> {
+    let value = serde_json::to_value(document).unwrap();
+    ["relays", "bridges"]
+        .iter()
+        .filter_map(|key| value[key].as_array())
+        .flatten()
+        .filter_map(|entry| entry.as_object())
+        .flat_map(|entry| entry.keys().cloned())
+        .collect()
+}
+
+fn field_names<F: Field>() -> std::collections::BTreeSet<String> {
+    F::ALL.iter().map(|f| f.as_str().to_string()).collect()
+}
+
+#[test]
+fn test_field_enums_match_models() {
+    use onionoo::mock::fixtures;
+    use onionoo::models::*;
+
+    let summary: SummaryResponse = serde_json::from_str(fixtures::SUMMARY).unwrap();
+    assert_eq!(object_keys(&summary), field_names::<SummaryField>());
+    let details: DetailsResponse = serde_json::from_str(fixtures::DETAILS).unwrap();
+    assert_eq!(object_keys(&details), field_names::<DetailsField>());
+    let bandwidth: BandwidthResponse = serde_json::from_str(fixtures::BANDWIDTH).unwrap();
+    assert_eq!(object_keys(&bandwidth), field_names::<BandwidthField>());
+    let weights: WeightsResponse = serde_json::from_str(fixtures::WEIGHTS).unwrap();
+    assert_eq!(object_keys(&weights), field_names::<WeightsField>());
+    let clients: ClientsResponse = serde_json::from_str(fixtures::CLIENTS).unwrap();
+    assert_eq!(object_keys(&clients), field_names::<ClientsField>());
+    let uptime: UptimeResponse = serde_json::from_str(fixtures::UPTIME).unwrap();
+    assert_eq!(object_keys(&uptime), field_names::<UptimeField>());
+}
+
+#[tokio::test]
+async fn test_typed_fields_with_partial_documents() {
+    let server = mock_server().await;
+    let client = server.client();
+    let fields = FieldsList::new()
+        .field(DetailsField::Nickname)
+        .field(DetailsField::Fingerprint)
+        .field(DetailsField::HashedFingerprint)
+        .field(DetailsField::As);
+    let params = QueryParameters::new().fields(fields);
+
+    // The full models cannot represent documents with omitted fields.
+    let result = endpoints::details(&client, params.clone()).await;
+    assert!(matches!(result, Err(ClientError::Deserialization { .. })));
+
+    let response: PartialDetailsResponse = client.fetch::<PartialDetails>(params).await.unwrap();
+    assert_eq!(response.relays.len(), 4);
+    assert_eq!(response.bridges.len(), 2);
+    let alpha = &response.relays[0];
+    assert_eq!(alpha.nickname.as_deref(), Some("alpha"));
+    assert_eq!(alpha.as_number, Some(onionoo::parameters::AsNumber(3)));
+    assert!(alpha.last_seen.is_none() && alpha.flags.is_none());
+    let echo = &response.bridges[0];
+    assert!(echo.hashed_fingerprint.is_some() && echo.running.is_none());
+}
+
+#[tokio::test]
+async fn test_tabular_export() {
+    let server = mock_server().await;
+    let client = server.client();
+
+    let writer = TableWriter::<_, PartialRelayDetails>::csv(Vec::new())
+        .select([
+            DetailsField::Nickname,
+            DetailsField::OrAddresses,
+            DetailsField::Flags,
+        ])
+        .list_encoding(ListEncoding::Delimited(' '));
+    let params = QueryParameters::new()
+        .type_param(TypeValue::Relay)
+        .fields(writer.fields_list());
+    let response = client.fetch::<PartialDetails>(params).await.unwrap();
+    assert_eq!(
+        server.requests(),
+        vec!["/details?fields=nickname,or_addresses,flags&type=relay"]
+    );
+
+    let mut writer = writer;
+    writer.write_all(&response.relays).unwrap();
+    let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), response.relays.len() + 1);
+    assert_eq!(lines[0], "nickname,or_addresses,flags");
+    assert!(lines[1].starts_with("alpha,"));
+
+    let bandwidth = endpoints::bandwidth(&client, QueryParameters::new())
+        .await
+        .unwrap();
+    let points = bandwidth.series();
+    assert!(!points.is_empty());
+    let mut writer = TableWriter::ndjson(Vec::new());
+    writer.write_all(&points).unwrap();
+    let ndjson = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(ndjson.lines().count(), points.len());
+    let first: serde_json::Value = serde_json::from_str(ndjson.lines().next().unwrap()).unwrap();
+    assert_eq!(first["fingerprint"], bandwidth.relays[0].fingerprint);
+    assert_eq!(first["series"], "read_history");
+}
+
+#[tokio::test]
+async fn test_requests_are_recorded() {
+    let server = mock_server().await;
+    let client = server.client();
+    let params = QueryParameters::new().type_param(TypeValue::Relay).limit(1);
+    endpoints::summary(&client, params).await.unwrap();
+    assert_eq!(server.requests(), vec!["/summary?limit=1&type=relay"]);
+}
+
+#[tokio::test]
+async fn test_invalid_parameter_is_rejected() {
+    let server = mock_server().await;
+    let client = server.client();
+    let result: Result<serde_json::Value, _> = client
+        .get("/summary?type=guard", QueryParameters::new())
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::StatusCode { status: 400, .. })
+    ));
+}
+
+// ==================== SCRIPTED FAILURE TESTS ====================
+
+#[tokio::test]
+async fn test_server_error_status() {
+    let server = mock_server().await;
+    let client = server.client();
+    server.push_fault(Endpoint::Details, Fault::Status(503));
+    let result = endpoints::details(&client, QueryParameters::new().limit(2)).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::StatusCode { status: 503, .. })
+    ));
+    let error = result.unwrap_err();
+    assert!(error.is_retryable() && !error.is_not_found());
+    let context = error.context().unwrap();
+    assert_eq!(context.endpoint, "/details");
+    assert_eq!(context.query, QueryParameters::new().limit(2));
+    assert!(context.url.ends_with("/details?limit=2"));
+
+    // Faults are consumed one per request
+    let result = endpoints::details(&client, QueryParameters::new()).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_not_modified_status() {
+    let server = mock_server().await;
+    let client = server.client();
+    server.push_fault(Endpoint::Summary, Fault::NotModified);
+    let result = endpoints::summary(&client, QueryParameters::new()).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::StatusCode { status: 304, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_malformed_json() {
+    let server = mock_server().await;
+    let client = server.client();
+    server.push_fault(Endpoint::Uptime, Fault::MalformedJson);
+    let result = endpoints::uptime(&client, QueryParameters::new()).await;
+    assert!(matches!(result, Err(ClientError::Deserialization { .. })));
+    let error = result.unwrap_err();
+    assert!(!error.is_retryable());
+    assert!(error.url().unwrap().ends_with("/uptime"));
+}
+
+#[tokio::test]
+async fn test_slow_response() {
+    let server = mock_server().await;
+    let client = server.client();
+    server.push_fault(
+        Endpoint::Bandwidth,
+        Fault::Delay(Duration::from_millis(200)),
+    );
+    let started = std::time::Instant::now();
+    let result = endpoints::bandwidth(&client, QueryParameters::new().limit(1)).await;
+    assert!(result.is_ok());
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn test_missing_fixture_returns_not_found() {
+    let server = MockServer::builder()
+        .without(Endpoint::Clients)
+        .start()
+        .await
+        .unwrap();
+    let client = server.client();
+    let result = endpoints::clients(&client, QueryParameters::new()).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::StatusCode { status: 404, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_invalid_fixture_is_rejected() {
+    let result = MockServer::builder()
+        .fixture(Endpoint::Details, "<html></html>")
+        .start()
+        .await;
+    let error = result.err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn test_batch_lookup() {
+    let server = mock_server().await;
+    let client = server.client();
+    let fingerprints = [
+        "9695DFC35FFEB861329B9F1AB04C46397020CE31",
+        "B8D3A7C11E2F4C9A0D5E6F708192A3B4C5D6E7F8",
+        "$9695dfc35ffeb861329b9f1ab04c46397020ce31",
+        "0000000000000000000000000000000000000000",
+    ];
+
+    let batch = client
+        .batch(Endpoint::Details)
+        .concurrency(2)
+        .lookup::<RelayDetails, BridgeDetails, _>(fingerprints, QueryParameters::new())
+        .await;
+    assert!(batch.is_complete());
+    let relays: Vec<_> = batch
+        .response
+        .unwrap()
+        .relays
+        .into_iter()
+        .map(|relay| relay.fingerprint)
+        .collect();
+    assert_eq!(relays, &fingerprints[..2]);
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn test_batch_queries_report_failures_per_input() {
+    let server = mock_server().await;
+    let client = server.client();
+    let queries = vec![
+        QueryParameters::new().country("de"),
+        QueryParameters::new().country("us"),
+        QueryParameters::new().flag("exit"),
+    ];
+
+    let mut expected = std::collections::BTreeSet::new();
+    for params in &queries[1..] {
+        let response = endpoints::summary(&client, params.clone()).await.unwrap();
+        expected.extend(summary_nicknames(&response).into_iter().map(String::from));
+    }
+    assert!(expected.len() > 1);
+
+    server.push_fault(Endpoint::Summary, Fault::Status(503));
+    let batch: BatchResponse<RelaySummary, BridgeSummary> = client
+        .batch(Endpoint::Summary)
+        .concurrency(1)
+        .queries(queries)
+        .await;
+    assert_eq!(batch.failures.len(), 1);
+    assert!(matches!(
+        batch.failures[0],
+        (0, ClientError::StatusCode { status: 503, .. })
+    ));
+    let response = batch.response.unwrap();
+    let nicknames = summary_nicknames(&response);
+    assert_eq!(nicknames.len(), expected.len());
+    assert!(nicknames.iter().all(|name| expected.contains(*name)));
+
+    let batch: BatchResponse<RelayWeights, ()> = client
+        .batch(Endpoint::Weights)
+        .queries(vec![QueryParameters::new().type_param(TypeValue::Bridge)])
+        .await;
+    assert!(batch.response.is_none());
+    assert!(matches!(
+        batch.failures[0],
+        (0, ClientError::InvalidParameters(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_response_metadata() {
+    let server = mock_server().await;
+    let client = server.client();
+    let params = QueryParameters::new().type_param(TypeValue::Relay).limit(2);
+
+    let response = client
+        .fetch_with_metadata::<endpoints::Summary>(params.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.document.relays.len(), 2);
+    let metadata = &response.metadata;
+    assert_eq!(metadata.status, 200);
+    assert_eq!(metadata.header("Content-Type"), Some("application/json"));
+    let length: usize = metadata.header("content-length").unwrap().parse().unwrap();
+    assert_eq!(metadata.body_size, length);
+    assert!(metadata.last_modified.is_none() && metadata.content_encoding.is_none());
+
+    let plain = endpoints::summary(&client, params).await.unwrap();
+    assert_eq!(plain.relays.len(), response.document.relays.len());
+
+    server.push_fault(Endpoint::Summary, Fault::Delay(Duration::from_millis(100)));
+    let response = client
+        .get_with_metadata::<SummaryResponse>("/summary", QueryParameters::new())
+        .await
+        .unwrap();
+    assert!(response.metadata.elapsed >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_limits_are_shared_across_clones() {
+    let server = mock_server().await;
+    let client = server.client().max_in_flight(1);
+    for _ in 0..2 {
+        server.push_fault(Endpoint::Summary, Fault::Delay(Duration::from_millis(100)));
+    }
+
+    let tasks: Vec<_> = (0..3)
+        .map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .get_with_metadata::<SummaryResponse>("/summary", QueryParameters::new())
+                    .await
+            })
+        })
+        .collect();
+    // Wait for one request to be sent and the other two to queue behind it
+    let stats = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let stats = client.limit_stats();
+            if (stats.in_flight, stats.waiting) == (1, 2) {
+                break stats;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    })
+    .await
+    .expect("requests never queued behind the shared cap");
+    assert!(stats.is_saturated());
+
+    let mut queued = Vec::new();
+    for task in tasks {
+        queued.push(task.await.unwrap().unwrap().metadata.queued);
+    }
+    queued.sort();
+    assert_eq!(queued[0], Duration::ZERO);
+    assert!(queued[2] > queued[1]);
+    assert!(queued[1] > Duration::ZERO);
+
+    let stats = client.limit_stats();
+    assert_eq!((stats.in_flight, stats.waiting, stats.throttled), (0, 0, 2));
+    assert!(stats.total_wait >= queued[2]);
+
+    // A client configured separately does not share the cap
+    let other = server.client().max_in_flight(1);
+    assert_eq!(other.limit_stats().throttled, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limit() {
+    let transport =
+        FixtureTransport::new().respond("/summary", 200, onionoo::mock::fixtures::SUMMARY);
+    let client = Client::new()
+        .with_transport(transport)
+        .rate_limit(RateLimit::per_second(20));
+    let started = tokio::time::Instant::now();
+    for _ in 0..3 {
+        endpoints::summary(&client.clone(), QueryParameters::new().limit(1))
+            .await
+            .unwrap();
+    }
+    // The first request takes the only token, the others wait 50ms each
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert_eq!(client.limit_stats().throttled, 2);
+
+    let mut entries = 0;
+    let response = client
+        .stream::<endpoints::Summary, _>(QueryParameters::new(), |_| entries += 1)
+        .await
+        .unwrap();
+    assert!(entries > 0);
+    assert_eq!(response.metadata.queued, Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn test_metrics_hook() {
+    let server = mock_server().await;
+    let recorder = Recorder::new();
+    let client = server.client().with_metrics(recorder.clone());
+    let params = || QueryParameters::new().limit(1);
+
+    endpoints::summary(&client, params()).await.unwrap();
+    server.push_fault(Endpoint::Summary, Fault::Status(503));
+    server.push_fault(Endpoint::Summary, Fault::Status(429));
+    server.push_fault(Endpoint::Summary, Fault::MalformedJson);
+    for _ in 0..3 {
+        let error = endpoints::summary(&client, params()).await.unwrap_err();
+        if error.is_retryable() {
+            client.report_retry(&error);
+        }
+    }
+    client
+        .stream::<endpoints::Summary, _>(params(), |_| {})
+        .await
+        .unwrap();
+
+    // Rejected before sending, but still reported
+    let bridges = || params().type_param(TypeValue::Bridge);
+    let error = endpoints::weights(&client, bridges()).await.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidRequest);
+    client
+        .stream::<endpoints::Weights, _>(bridges(), |_| {})
+        .await
+        .unwrap_err();
+    assert_eq!(server.requests().len(), 5);
+
+    let snapshot = recorder.snapshot();
+    assert_eq!(snapshot.requests, 7);
+    assert_eq!(
+        snapshot.errors,
+        [
+            (ErrorKind::RateLimited, 1),
+            (ErrorKind::Status, 1),
+            (ErrorKind::Deserialization, 1),
+            (ErrorKind::InvalidRequest, 2)
+        ]
+        .into_iter()
+        .collect()
+    );
+    assert_eq!(snapshot.retries, 2);
+    assert_eq!(snapshot.cache_hits, 0);
+    assert_eq!(snapshot.latency_count(), snapshot.requests);
+}
+
+#[tokio::test]
+async fn test_cache_hits_are_reported() {
+    let transport = FixtureTransport::new().respond_with(
+        "/summary",
+        TransportResponse::new(200, "{}").header("Age", "42"),
+    );
+    let recorder = Recorder::new();
+    let client = Client::new()
+        .with_transport(transport)
+        .with_metrics(recorder.clone());
+    let response = client
+        .get_with_metadata::<serde_json::Value>("/summary", QueryParameters::new())
+        .await
+        .unwrap();
+    assert!(response.metadata.is_cache_hit());
+    assert_eq!(recorder.snapshot().cache_hits, 1);
+}
+
+#[tokio::test]
+async fn test_streaming_matches_buffered_documents() {
+    let server = mock_server().await;
+    let client = server.client();
+
+    let mut relays = Vec::new();
+    let mut bridges = Vec::new();
+    let response = client
+        .stream::<endpoints::Details, _>(QueryParameters::new(), |entry| match entry {
+            Entry::Relay(relay) => relays.push(relay.fingerprint),
+            Entry::Bridge(bridge) => bridges.push(bridge.hashed_fingerprint),
+        })
+        .await
+        .unwrap();
+    let document = endpoints::details(&client, QueryParameters::new())
+        .await
+        .unwrap();
+    let header = response.document;
+    assert_eq!(header.version, document.version);
+    assert_eq!(header.relays_published, document.relays_published);
+    assert_eq!(header.bridges_published, document.bridges_published);
+    assert_eq!(
+        relays,
+        document
+            .relays
+            .into_iter()
+            .map(|r| r.fingerprint)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        bridges,
+        document
+            .bridges
+            .into_iter()
+            .map(|b| b.hashed_fingerprint)
+            .collect::<Vec<_>>()
+    );
+    assert!(response.metadata.body_size > 0);
+
+    let params = QueryParameters::new().type_param(TypeValue::Relay).limit(1);
+    let mut entries = 0;
+    let response = client
+        .stream::<endpoints::Uptime, _>(params, |_| entries += 1)
+        .await
+        .unwrap();
+    assert_eq!(entries, 1);
+    assert!(response.document.relays_truncated.is_some());
+
+    server.push_fault(Endpoint::Bandwidth, Fault::MalformedJson);
+    let result = client
+        .stream::<endpoints::Bandwidth, _>(QueryParameters::new(), |_| {})
+        .await;
+    assert!(matches!(result, Err(ClientError::Deserialization { .. })));
+    server.push_fault(Endpoint::Bandwidth, Fault::Status(503));
+    let result = client
+        .stream::<endpoints::Bandwidth, _>(QueryParameters::new(), |_| {})
+        .await;
+    assert!(result.unwrap_err().is_retryable());
+}
+
+#[cfg(all(feature = "gzip", feature = "deflate", feature = "brotli"))]
+#[tokio::test]
+async fn test_compressed_responses_decode_identically() {
+    let server = mock_server().await;
+    let plain = server
+        .client()
+        .fetch_with_metadata::<endpoints::Details>(QueryParameters::new())
+        .await
+        .unwrap();
+    assert_eq!(plain.metadata.compressed_size, None);
+    let expected = serde_json::to_value(&plain.document).unwrap();
+
+    for encoding in ContentEncoding::ALL {
+        let server = MockServer::builder()
+            .encodings([encoding])
+            .start()
+            .await
+            .unwrap();
+        let client = server.client();
+
+        let response = client
+            .fetch_with_metadata::<endpoints::Details>(QueryParameters::new())
+            .await
+            .unwrap();
+        assert_eq!(serde_json::to_value(&response.document).unwrap(), expected);
+        let metadata = &response.metadata;
+        assert_eq!(
+            metadata.content_encoding.as_deref(),
+            Some(encoding.as_str())
+        );
+        assert_eq!(metadata.body_size, plain.metadata.body_size);
+        let compressed: usize = metadata.header("content-length").unwrap().parse().unwrap();
+        assert_eq!(metadata.compressed_size, Some(compressed));
+        assert!(compressed < metadata.body_size);
+
+        let mut relays = 0;
+        let streamed = client
+            .stream::<endpoints::Details, _>(QueryParameters::new(), |entry| {
+                if let Entry::Relay(_) = entry {
+                    relays += 1;
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(relays, plain.document.relays.len());
+        assert_eq!(streamed.metadata.body_size, metadata.body_size);
+        assert_eq!(streamed.metadata.compressed_size, Some(compressed));
+    }
+
+    // The server sends an uncompressed body if the client refuses its encodings
+    let server = MockServer::builder()
+        .encodings([ContentEncoding::Gzip])
+        .start()
+        .await
+        .unwrap();
+    let transport = onionoo::transport::ReqwestTransport::new();
+    let request = onionoo::transport::Request::new(format!("{}/summary", server.base_url()))
+        .header("Accept-Encoding", "br, gzip;q=0");
+    let response = transport.send(request).await.unwrap();
+    assert_eq!(response.header_value("content-encoding"), None);
+}
+
+#[tokio::test]
+async fn test_typed_endpoints() {
+    use onionoo::endpoints::{
+        Bandwidth, Clients, Details, OnionooEndpoint, Summary, Uptime, Weights,
+    };
+
+    let server = mock_server().await;
+    let client = server.client();
+    let params = QueryParameters::new;
+
+    let summary: SummaryResponse = client.fetch::<Summary>(params()).await.unwrap();
+    let details = client.fetch::<Details>(params()).await.unwrap();
+    assert_eq!(summary.relays.len(), details.relays.len());
+    assert!(
+        !client
+            .fetch::<Bandwidth>(params())
+            .await
+            .unwrap()
+            .relays
+            .is_empty()
+    );
+    assert!(
+        !client
+            .fetch::<Weights>(params())
+            .await
+            .unwrap()
+            .relays
+            .is_empty()
+    );
+    assert!(
+        !client
+            .fetch::<Clients>(params())
+            .await
+            .unwrap()
+            .bridges
+            .is_empty()
+    );
+    assert!(
+        !client
+            .fetch::<Uptime>(params())
+            .await
+            .unwrap()
+            .relays
+            .is_empty()
+    );
+
+    assert_eq!(Details::PATH, "/details");
+    assert_eq!(PartialDetails::ENDPOINT, Endpoint::Details);
+    assert_eq!(Weights::PATH, Weights::ENDPOINT.path());
+    assert_eq!(
+        server.requests(),
+        [
+            "/summary",
+            "/details",
+            "/bandwidth",
+            "/weights",
+            "/clients",
+            "/uptime"
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_blocking_client() {
+    use onionoo::blocking;
+
+    let server = MockServer::builder()
+        .encodings(ContentEncoding::ALL)
+        .start()
+        .await
+        .unwrap();
+    let base_url = server.base_url().to_string();
+    // The blocking client must not run on the async runtime's worker threads.
+    tokio::task::spawn_blocking(move || {
+        let client = blocking::Client::new_with_base_url(base_url);
+        let params = QueryParameters::new().type_param(TypeValue::Relay).limit(2);
+        let response = blocking::summary(&client, params).unwrap();
+        assert_eq!(response.relays.len(), 2);
+        assert!(
+            !blocking::clients(&client, QueryParameters::new())
+                .unwrap()
+                .bridges
+                .is_empty()
+        );
+
+        let result = blocking::weights(
+            &client,
+            QueryParameters::new().type_param(TypeValue::Bridge),
+        );
+        assert!(matches!(result, Err(ClientError::InvalidParameters(_))));
+        let result: Result<SummaryResponse, _> = client.get("/missing", QueryParameters::new());
+        assert!(matches!(
+            result,
+            Err(ClientError::StatusCode { status: 404, .. })
+        ));
+        let result: Result<SummaryResponse, _> = client.get("summary", QueryParameters::new());
+        assert!(matches!(result, Err(ClientError::UrlConstruction(_))));
+        let response = client
+            .fetch_with_metadata::<onionoo::endpoints::Uptime>(QueryParameters::new())
+            .unwrap();
+        assert_eq!(response.metadata.status, 200);
+        assert!(response.metadata.body_size > 0);
+        assert_eq!(
+            response.metadata.content_encoding.is_some(),
+            response.metadata.compressed_size.is_some()
+        );
+        let mut entries = 0;
+        let response = client
+            .stream::<onionoo::endpoints::Summary, _>(QueryParameters::new(), |_| entries += 1)
+            .unwrap();
+        assert_eq!(entries, 6);
+        assert_eq!(response.document.version, "8.0");
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        server.requests(),
+        [
+            "/summary?limit=2&type=relay",
+            "/clients",
+            "/missing",
+            "/uptime",
+            "/summary"
+        ]
+    );
 }